@@ -1,39 +1,74 @@
 use leptos::*;
-use leptos_router::{ActionForm, FromFormData};
+use leptos_router::{use_query_map, ActionForm, FromFormData};
 
 use crate::components::Toast;
-use common::{Error, FnError, LoginArg};
+use common::{Error, FnError, LoginArg, VerifyLoginArg};
 
 // ==================== // LoginForm // ==================== //
 
+/// Returns the challenge if the code of the 2FA is required
+///
 #[server]
-async fn login(arg: LoginArg) -> Result<(), ServerFnError<Error>> {
-    use crate::CHATS_PATH;
-    use common::{ArgsValidator, CookieManager, StoreExtractor};
+async fn login(arg: LoginArg) -> Result<Option<String>, ServerFnError<Error>> {
+    use crate::{CHATS_PATH, PASSWORD_PATH};
+    use common::{
+        ArgsValidator, ClientIpExtractor, ConfigExtractor, CookieManager, LoginStep, StoreExtractor,
+    };
 
     let arg = ArgsValidator::validate(arg)?;
     let store = StoreExtractor::use_store()?;
+    let config = ConfigExtractor::use_config()?;
+    let client = ClientIpExtractor::use_client(config.login.trust_proxy).await?;
 
-    let (session, user) = arg.call(&store).await?;
-    CookieManager::add_auth(user.id, session)?;
+    match arg.call(&client, &config.login, &store).await? {
+        LoginStep::Session(session, user) => {
+            CookieManager::add_auth(user.id, session)?;
+            if user.must_change_password {
+                leptos_axum::redirect(PASSWORD_PATH);
+            } else {
+                leptos_axum::redirect(CHATS_PATH);
+            }
+            Ok(None)
+        }
+        LoginStep::TwoFactor(challenge) => Ok(Some(challenge)),
+    }
+}
 
-    leptos_axum::redirect(CHATS_PATH);
-    Ok(())
+/// Returns the label of the identity provider if the single sign-on is configured
+///
+#[server]
+async fn get_sso_label() -> Result<Option<String>, ServerFnError<Error>> {
+    use common::ConfigExtractor;
+
+    let config = ConfigExtractor::use_config()?;
+    Ok(config.oidc.as_ref().map(|v| v.label.clone()))
 }
 
 #[component]
 pub fn LoginForm() -> impl IntoView {
     let toast = expect_context::<Toast>();
+    // failed logins are shown in the form, including the lockout and the errors
+    // of the single sign-on which come back in the query, as well as its challenge
+    // for users with 2FA
+    let query = use_query_map();
+    let challenge = create_rw_signal(query.with_untracked(|q| q.get("sso_challenge").cloned()));
+    let error = create_rw_signal(query.with_untracked(|q| q.get("sso_error").cloned().unwrap_or_default()));
+
+    let sso_resource = create_resource(|| (), |_| get_sso_label());
 
     let action = create_server_action::<Login>();
     let pending = action.pending();
     let value = action.value();
 
     create_effect(move |_| {
-        value.with(|val| {
-            if let Some(Err(FnError::WrappedServerError(e))) = val {
-                toast.error(e.to_string());
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => error.set(e.to_string()),
+            Some(Err(e)) => error.set(e.to_string()),
+            Some(Ok(Some(v))) => {
+                error.set(String::new());
+                challenge.set(Some(v.clone()));
             }
+            _ => {}
         });
     });
 
@@ -48,40 +83,150 @@ pub fn LoginForm() -> impl IntoView {
         }
     };
 
+    let password_form = move || {
+        view! {
+            <ActionForm class="mt-6 grid gap-6" action on:submit=on_submit>
+                <div class="grid gap-2">
+                    <label for="username" class="text-sm font-medium leading-none">
+                        Username
+                    </label>
+                    <input
+                        id="username"
+                        type="text"
+                        name="arg[username]"
+                        disabled=pending
+                        autocomplete="off"
+                        placeholder="Enter your username"
+                        class="w-full h-11 px-3 input"
+                    />
+                </div>
+
+                <div class="grid gap-2">
+                    <label for="password" class="text-sm font-medium leading-none">
+                        Password
+                    </label>
+                    <input
+                        id="password"
+                        type="password"
+                        name="arg[password]"
+                        disabled=pending
+                        autocomplete="off"
+                        placeholder="Enter your password"
+                        class="w-full h-11 px-3 input"
+                    />
+                </div>
+
+                <Show when=move || !error.with(String::is_empty)>
+                    <p class="-mb-4 text-sm text-center text-danger">{error}</p>
+                </Show>
+                <button type="submit" disabled=pending class="mt-4 w-full h-11 btn-primary">
+                    "Sign in"
+                </button>
+                <Transition fallback=|| ()>
+                    {move || {
+                        sso_resource
+                            .get()
+                            .and_then(Result::ok)
+                            .flatten()
+                            .map(|label| {
+                                view! {
+                                    <a
+                                        rel="external"
+                                        href="/auth/oidc/login"
+                                        class="-mt-2 w-full h-11 btn-outline flex items-center justify-center"
+                                    >
+                                        {format!("Sign in with {}", label)}
+                                    </a>
+                                }
+                            })
+                    }}
+                </Transition>
+            </ActionForm>
+        }
+    };
+
+    view! {
+        <Show when=move || challenge.with(Option::is_some) fallback=password_form>
+            <TwoFactorForm challenge />
+        </Show>
+    }
+}
+
+// ==================== // TwoFactorForm // ==================== //
+
+#[server]
+async fn verify_login(arg: VerifyLoginArg) -> Result<(), ServerFnError<Error>> {
+    use crate::{CHATS_PATH, PASSWORD_PATH};
+    use common::{
+        ArgsValidator, ClientIpExtractor, ConfigExtractor, CookieManager, StoreExtractor,
+    };
+
+    let arg = ArgsValidator::validate(arg)?;
+    let store = StoreExtractor::use_store()?;
+    let config = ConfigExtractor::use_config()?;
+    let client = ClientIpExtractor::use_client(config.login.trust_proxy).await?;
+
+    let (session, user) = arg.call(&client, &config.login, &store).await?;
+    CookieManager::add_auth(user.id, session)?;
+
+    if user.must_change_password {
+        leptos_axum::redirect(PASSWORD_PATH);
+    } else {
+        leptos_axum::redirect(CHATS_PATH);
+    }
+    Ok(())
+}
+
+/// The second step of the login for users with 2FA
+///
+#[component]
+fn TwoFactorForm(challenge: RwSignal<Option<String>>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+
+    let action = create_server_action::<VerifyLogin>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| {
+            if let Some(Err(FnError::WrappedServerError(e))) = val {
+                toast.error(e.to_string());
+            }
+        });
+    });
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        let data = VerifyLogin::from_event(&ev).expect("failed to parse form data");
+        if data.arg.code.trim().is_empty() {
+            toast.info(String::from("Code is blank"));
+            ev.prevent_default();
+        }
+    };
+
     view! {
         <ActionForm class="mt-6 grid gap-6" action on:submit=on_submit>
+            <input type="hidden" name="arg[challenge]" prop:value=move || challenge.get().unwrap_or_default() />
             <div class="grid gap-2">
-                <label for="username" class="text-sm font-medium leading-none">
-                    Username
+                <label for="code" class="text-sm font-medium leading-none">
+                    "Authentication code"
                 </label>
                 <input
-                    id="username"
+                    id="code"
                     type="text"
-                    name="arg[username]"
+                    name="arg[code]"
                     disabled=pending
-                    autocomplete="off"
-                    placeholder="Enter your username"
-                    class="w-full h-11 px-3 input"
-                />
-            </div>
-
-            <div class="grid gap-2">
-                <label for="password" class="text-sm font-medium leading-none">
-                    Password
-                </label>
-                <input
-                    id="password"
-                    type="password"
-                    name="arg[password]"
-                    disabled=pending
-                    autocomplete="off"
-                    placeholder="Enter your password"
+                    inputmode="numeric"
+                    autocomplete="one-time-code"
+                    placeholder="Code from your app or a recovery code"
                     class="w-full h-11 px-3 input"
                 />
             </div>
 
             <button type="submit" disabled=pending class="mt-4 w-full h-11 btn-primary">
-                "Sign in"
+                "Verify"
+            </button>
+            <button type="button" on:click=move |_| challenge.set(None) class="w-full h-9 btn-ghost">
+                "Back to sign in"
             </button>
         </ActionForm>
     }
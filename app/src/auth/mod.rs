@@ -2,11 +2,16 @@ use leptos::*;
 use leptos_meta::Title;
 
 use crate::components::Logo;
+use crate::{LOGIN_PATH, REGISTER_PATH};
+use password_form::PasswordForm;
 use login_form::LoginForm;
 use login_svg::LoginSvgImage;
+use register_form::RegisterForm;
 
 mod login_form;
 mod login_svg;
+mod password_form;
+mod register_form;
 
 #[component]
 pub fn LoginPage() -> impl IntoView {
@@ -27,6 +32,60 @@ pub fn LoginPage() -> impl IntoView {
                 <h3 class="font-semibold tracking-tight text-center text-2xl">"Welcome Back"</h3>
                 <p class="text-sm text-center text-muted">"Please login to your account"</p>
                 <LoginForm />
+                <p class="mt-6 text-sm text-center text-muted">
+                    "Have an invite code? "
+                    <a href=REGISTER_PATH class="text-accent-on hover:text-success underline">
+                        "Create an account"
+                    </a>
+                </p>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn RegisterPage() -> impl IntoView {
+    view! {
+        <Title text="Register" />
+
+        <div class="flex w-full max-w-md lg:max-w-4xl overflow-hidden rounded-lg bg-surface text-surface-on border border-border shadow-sm">
+            <div class="p-2 basis-1/2 hidden lg:block">
+                <div class="h-full rounded-md flex flex-col items-center justify-center gap-8 bg-accent">
+                    <LoginSvgImage class="h-64 w-64" />
+                    <h3 class="font-medium text-lg text-accent-on">"Ready to chat"</h3>
+                </div>
+            </div>
+            <div class="grow px-16 py-14 flex flex-col justify-center">
+                <div class="mb-4 flex justify-center">
+                    <Logo />
+                </div>
+                <h3 class="font-semibold tracking-tight text-center text-2xl">"Create Account"</h3>
+                <p class="text-sm text-center text-muted">"Sign up with the invite code from an admin"</p>
+                <RegisterForm />
+                <p class="mt-6 text-sm text-center text-muted">
+                    "Already have an account? "
+                    <a href=LOGIN_PATH class="text-accent-on hover:text-success underline">
+                        "Sign in"
+                    </a>
+                </p>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn PasswordPage() -> impl IntoView {
+    view! {
+        <Title text="Change Password" />
+
+        <div class="w-full max-w-md overflow-hidden rounded-lg bg-surface text-surface-on border border-border shadow-sm">
+            <div class="px-16 py-14 flex flex-col justify-center">
+                <div class="mb-4 flex justify-center">
+                    <Logo />
+                </div>
+                <h3 class="font-semibold tracking-tight text-center text-2xl">"Change Password"</h3>
+                <p class="text-sm text-center text-muted">"Please choose your own password to continue"</p>
+                <PasswordForm />
             </div>
         </div>
     }
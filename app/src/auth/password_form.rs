@@ -0,0 +1,130 @@
+use leptos::*;
+use leptos_router::{ActionForm, FromFormData};
+
+use crate::components::Toast;
+use crate::LOGIN_PATH;
+use common::{Error, FnError};
+
+// ==================== // PasswordForm // ==================== //
+
+#[server]
+async fn change_password(
+    old_password: String,
+    new_password: String,
+    confirm_password: String,
+) -> Result<(), ServerFnError<Error>> {
+    use crate::CHATS_PATH;
+    use common::{
+        ArgsValidator, AuthExtractor, CookieManager, HubManager, Session, StoreExtractor,
+        UpdatePasswordArg,
+    };
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_login(false, &store).await?;
+
+    let arg = ArgsValidator::validate(UpdatePasswordArg {
+        id: user.id,
+        old_password,
+        new_password,
+        confirm_password,
+    })?;
+    arg.call(&store).await?;
+
+    // other devices sign in again with the new password
+    let (_, session) = CookieManager::get_auth()?;
+    let others = Session::delete_others(user.id, session, &store).await?;
+    HubManager::kick_sessions(user.id, &others, "Your password has been changed")?;
+
+    leptos_axum::redirect(CHATS_PATH);
+    Ok(())
+}
+
+/// The password set by an admin must be changed before using the app
+///
+#[component]
+pub fn PasswordForm() -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let error = create_rw_signal(String::new());
+
+    let action = create_server_action::<ChangePassword>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => error.set(e.to_string()),
+            Some(Err(e)) => error.set(e.to_string()),
+            _ => {}
+        });
+    });
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        let data = ChangePassword::from_event(&ev).expect("failed to parse form data");
+        if data.old_password.is_empty() || data.new_password.is_empty() {
+            toast.info(String::from("Password is blank"));
+            ev.prevent_default();
+        } else if data.new_password != data.confirm_password {
+            toast.info(String::from("The password confirmation does not match"));
+            ev.prevent_default();
+        }
+    };
+
+    view! {
+        <ActionForm class="mt-6 grid gap-6" action on:submit=on_submit>
+            <div class="grid gap-2">
+                <label for="old_password" class="text-sm font-medium leading-none">
+                    "Current password"
+                </label>
+                <input
+                    id="old_password"
+                    type="password"
+                    name="old_password"
+                    disabled=pending
+                    autocomplete="current-password"
+                    placeholder="Enter the password you signed in with"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <div class="grid gap-2">
+                <label for="new_password" class="text-sm font-medium leading-none">
+                    "New password"
+                </label>
+                <input
+                    id="new_password"
+                    type="password"
+                    name="new_password"
+                    disabled=pending
+                    autocomplete="new-password"
+                    placeholder="At least 8 characters with letters and digits"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <div class="grid gap-2">
+                <label for="confirm_password" class="text-sm font-medium leading-none">
+                    "Confirm password"
+                </label>
+                <input
+                    id="confirm_password"
+                    type="password"
+                    name="confirm_password"
+                    disabled=pending
+                    autocomplete="new-password"
+                    placeholder="Enter the new password again"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <Show when=move || !error.with(String::is_empty)>
+                <p class="-mb-4 text-sm text-center text-danger">{error}</p>
+            </Show>
+            <button type="submit" disabled=pending class="mt-4 w-full h-11 btn-primary">
+                "Change password"
+            </button>
+            <a href=LOGIN_PATH class="w-full h-9 btn-ghost flex items-center justify-center">
+                "Back to sign in"
+            </a>
+        </ActionForm>
+    }
+}
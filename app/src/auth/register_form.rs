@@ -0,0 +1,140 @@
+use leptos::*;
+use leptos_router::{use_query_map, ActionForm, FromFormData};
+
+use crate::components::Toast;
+use common::{Error, FnError, RegisterArg};
+
+// ==================== // RegisterForm // ==================== //
+
+#[server]
+async fn register(arg: RegisterArg) -> Result<(), ServerFnError<Error>> {
+    use crate::CHATS_PATH;
+    use common::{
+        ArgsValidator, ClientIpExtractor, ConfigExtractor, CookieManager, HubManager,
+        StoreExtractor,
+    };
+
+    let arg = ArgsValidator::validate(arg)?;
+    let store = StoreExtractor::use_store()?;
+    let config = ConfigExtractor::use_config()?;
+    let client = ClientIpExtractor::use_client(config.login.trust_proxy).await?;
+
+    let (session, user, friendship) = arg.call(&client, &store).await?;
+    if let Some(fsp) = friendship {
+        // the inviter is the other one of the friendship
+        let inviter_id = if fsp.id0 == user.id { fsp.id1 } else { fsp.id0 };
+        HubManager::add_friend_room(inviter_id, fsp, &store).await?;
+    }
+    CookieManager::add_auth(user.id, session)?;
+
+    leptos_axum::redirect(CHATS_PATH);
+    Ok(())
+}
+
+#[component]
+pub fn RegisterForm() -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let error = create_rw_signal(String::new());
+
+    // the code is filled in when the page is opened from the QR code
+    let query = use_query_map();
+    let code = move || query.with(|q| q.get("code").cloned().unwrap_or_default());
+
+    let action = create_server_action::<Register>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => error.set(e.to_string()),
+            Some(Err(e)) => error.set(e.to_string()),
+            _ => {}
+        });
+    });
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        let data = Register::from_event(&ev).expect("failed to parse form data");
+        if data.arg.code.trim().is_empty() {
+            toast.info(String::from("Invite code is blank"));
+            ev.prevent_default();
+        } else if data.arg.username.trim().is_empty() {
+            toast.info(String::from("Username is blank"));
+            ev.prevent_default();
+        } else if data.arg.password != data.arg.confirm_password {
+            toast.info(String::from("The password confirmation does not match"));
+            ev.prevent_default();
+        }
+    };
+
+    view! {
+        <ActionForm class="mt-6 grid gap-6" action on:submit=on_submit>
+            <div class="grid gap-2">
+                <label for="code" class="text-sm font-medium leading-none">
+                    "Invite code"
+                </label>
+                <input
+                    id="code"
+                    type="text"
+                    name="arg[code]"
+                    prop:value=code
+                    disabled=pending
+                    autocomplete="off"
+                    placeholder="Enter your invite code"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <div class="grid gap-2">
+                <label for="username" class="text-sm font-medium leading-none">
+                    Username
+                </label>
+                <input
+                    id="username"
+                    type="text"
+                    name="arg[username]"
+                    disabled=pending
+                    autocomplete="off"
+                    placeholder="Choose a username"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <div class="grid gap-2">
+                <label for="password" class="text-sm font-medium leading-none">
+                    Password
+                </label>
+                <input
+                    id="password"
+                    type="password"
+                    name="arg[password]"
+                    disabled=pending
+                    autocomplete="new-password"
+                    placeholder="At least 8 characters with letters and digits"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <div class="grid gap-2">
+                <label for="confirm_password" class="text-sm font-medium leading-none">
+                    "Confirm password"
+                </label>
+                <input
+                    id="confirm_password"
+                    type="password"
+                    name="arg[confirm_password]"
+                    disabled=pending
+                    autocomplete="new-password"
+                    placeholder="Enter the password again"
+                    class="w-full h-11 px-3 input"
+                />
+            </div>
+
+            <Show when=move || !error.with(String::is_empty)>
+                <p class="-mb-4 text-sm text-center text-danger">{error}</p>
+            </Show>
+            <button type="submit" disabled=pending class="mt-4 w-full h-11 btn-primary">
+                "Sign up"
+            </button>
+        </ActionForm>
+    }
+}
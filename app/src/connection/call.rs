@@ -2,10 +2,10 @@ use leptos::*;
 use std::time::Duration;
 
 use super::{RtcStatus, WebRtcState, WebSocketState};
-use crate::components::icons::{MicOff, MicOn, PhoneSolid};
+use crate::components::icons::{ChartBars, MicOff, MicOn, PhoneSolid};
 use crate::components::{Avatar, Toast};
 use crate::home::ChatsState;
-use common::HungUpReson;
+use common::{CallStats, HungUpReson};
 
 #[component]
 pub fn CallSection() -> impl IntoView {
@@ -43,6 +43,7 @@ pub fn CallSection() -> impl IntoView {
         move || friend.with(|v| v.as_ref().map(|x| x.nickname.clone()).unwrap_or_default());
 
     let muted = create_rw_signal(!rtc.has_audio());
+    let show_stats = create_rw_signal(false);
 
     view! {
         <div class="fixed inset-x-0 top-4 flex justify-center">
@@ -52,61 +53,99 @@ pub fn CallSection() -> impl IntoView {
                 hide_class="animate-slide-out-up"
                 hide_delay=Duration::from_millis(150)
             >
-                <div class="flex items-center gap-3 rounded-md px-4 py-2 bg-surface text-surface-on border border-border shadow-sm">
-                    <Avatar src=Signal::derive(get_src) />
-                    <div class="text-sm pr-2">
-                        <p class="text-surface-on font-semibold text-center">{get_nickname}</p>
-                        <div class="flex items-center space-x-2">
-                            <Show
-                                when=move || status.get() == RtcStatus::Calling
-                                fallback=|| {
-                                    view! {
-                                        <span class="relative flex size-2">
-                                            <span class="animate-ping absolute inline-flex h-full w-full rounded-full bg-success opacity-75"></span>
-                                            <span class="relative inline-flex rounded-full size-2 bg-success"></span>
-                                        </span>
-                                        <p class="text-xs text-success">Waiting</p>
+                <div class="rounded-md px-4 py-2 bg-surface text-surface-on border border-border shadow-sm">
+                    <div class="flex items-center gap-3">
+                        <Avatar src=Signal::derive(get_src) />
+                        <div class="text-sm pr-2">
+                            <p class="text-surface-on font-semibold text-center">{get_nickname}</p>
+                            <div class="flex items-center space-x-2">
+                                <Show
+                                    when=move || status.get() == RtcStatus::Calling
+                                    fallback=|| {
+                                        view! {
+                                            <span class="relative flex size-2">
+                                                <span class="animate-ping absolute inline-flex h-full w-full rounded-full bg-success opacity-75"></span>
+                                                <span class="relative inline-flex rounded-full size-2 bg-success"></span>
+                                            </span>
+                                            <p class="text-xs text-success">Waiting</p>
+                                        }
                                     }
-                                }
-                            >
-                                <span class="relative inline-flex rounded-full size-2 bg-success"></span>
-                                <p class="text-xs text-success">Speaking</p>
-                            </Show>
-                        </div>
-                    </div>
-                    <div>
-                        <audio id="pcaudio" autoplay=true></audio>
-                    </div>
-                    <Show
-                        when=move || status.get() == RtcStatus::Calling
-                        fallback=move || {
-                            view! {
-                                <button
-                                    type="button"
-                                    on:click=move |_| rtc.send_reply(ws)
-                                    disabled=move || status.get() == RtcStatus::Caller
-                                    class="rounded-full p-2 bg-success text-success-on disabled:bg-muted"
                                 >
-                                    <PhoneSolid class="size-4" />
-                                </button>
+                                    <span class="relative inline-flex rounded-full size-2 bg-success"></span>
+                                    <p class="text-xs text-success">Speaking</p>
+                                </Show>
+                            </div>
+                        </div>
+                        <div>
+                            <audio id="pcaudio" autoplay=true></audio>
+                        </div>
+                        <Show
+                            when=move || status.get() == RtcStatus::Calling
+                            fallback=move || {
+                                view! {
+                                    <button
+                                        type="button"
+                                        on:click=move |_| rtc.send_reply(ws)
+                                        disabled=move || status.get() == RtcStatus::Caller
+                                        class="rounded-full p-2 bg-success text-success-on disabled:bg-muted"
+                                    >
+                                        <PhoneSolid class="size-4" />
+                                    </button>
+                                }
                             }
-                        }
-                    >
-                        <button
-                            type="button"
-                            on:click=move |_| rtc.toggle_mute(muted, toast)
-                            class="rounded-full p-2 bg-accent text-accent-on border border-border"
                         >
-                            <Show when=move || muted.get() fallback=|| view! { <MicOn class="size-4" /> }>
-                                <MicOff class="size-4" />
-                            </Show>
+                            <button
+                                type="button"
+                                on:click=move |_| rtc.toggle_mute(muted, toast)
+                                class="rounded-full p-2 bg-accent text-accent-on border border-border"
+                            >
+                                <Show when=move || muted.get() fallback=|| view! { <MicOn class="size-4" /> }>
+                                    <MicOff class="size-4" />
+                                </Show>
+                            </button>
+                        </Show>
+                        <Show when=move || status.get() == RtcStatus::Calling>
+                            <button
+                                type="button"
+                                on:click=move |_| show_stats.update(|v| *v = !*v)
+                                class="rounded-full p-2 bg-accent text-accent-on border border-border"
+                            >
+                                <ChartBars class="size-4" />
+                            </button>
+                        </Show>
+                        <button type="button" on:click=on_hungup class="rounded-full p-2 bg-danger text-danger-on">
+                            <PhoneSolid class="size-4 origin-center rotate-[135deg] translate-y-0.5" />
                         </button>
+                    </div>
+                    <Show when=move || show_stats.get() && status.get() == RtcStatus::Calling>
+                        <CallDiagnostics stats=rtc.stats() />
                     </Show>
-                    <button type="button" on:click=on_hungup class="rounded-full p-2 bg-danger text-danger-on">
-                        <PhoneSolid class="size-4 origin-center rotate-[135deg] translate-y-0.5" />
-                    </button>
                 </div>
             </AnimatedShow>
         </div>
     }
 }
+
+// ==================== // CallDiagnostics // ==================== //
+
+#[component]
+fn CallDiagnostics(stats: RwSignal<Option<CallStats>>) -> impl IntoView {
+    let item = move |f: fn(&CallStats) -> String| {
+        move || stats.with(|v| v.as_ref().map(f).unwrap_or_else(|| String::from("-")))
+    };
+
+    view! {
+        <div class="mt-2 pt-2 border-t border-border grid grid-cols-2 gap-x-6 gap-y-1 text-xs">
+            <p class="text-muted">"Round-trip time"</p>
+            <p class="text-right">{item(|v| format!("{:.0} ms", v.rtt))}</p>
+            <p class="text-muted">"Jitter"</p>
+            <p class="text-right">{item(|v| format!("{:.1} ms", v.jitter))}</p>
+            <p class="text-muted">"Packet loss"</p>
+            <p class="text-right">{item(|v| format!("{:.1} %", v.packet_loss))}</p>
+            <p class="text-muted">"Bitrate"</p>
+            <p class="text-right">{item(|v| format!("{:.1} kbps", v.bitrate))}</p>
+            <p class="text-muted">"Candidate"</p>
+            <p class="text-right">{item(|v| v.candidate_type.clone())}</p>
+        </div>
+    }
+}
@@ -6,3 +6,11 @@ mod webrtc;
 
 pub use call::CallSection;
 mod call;
+
+mod stats;
+
+pub use room_rtc::RoomRtcState;
+mod room_rtc;
+
+pub use room_call::RoomCallSection;
+mod room_call;
@@ -0,0 +1,112 @@
+use leptos::*;
+use std::time::Duration;
+
+use super::{RoomRtcState, WebSocketState};
+use crate::components::icons::{MicOff, MicOn, PhoneSolid};
+use crate::components::{Avatar, Toast};
+use crate::home::{ChatsState, UserState};
+
+#[component]
+pub fn RoomCallSection() -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let chats = expect_context::<ChatsState>();
+    let user = expect_context::<UserState>().get();
+    let ws = expect_context::<WebSocketState>();
+    let rtc = expect_context::<RoomRtcState>();
+    let room_id = rtc.room_id();
+    let calls = rtc.calls();
+
+    let room_name = move || {
+        room_id.with(|id| {
+            id.as_ref()
+                .map(|id| {
+                    chats.rooms().with(|rooms| {
+                        rooms
+                            .iter()
+                            .find(|v| &v.id == id)
+                            .map(|v| v.name.clone())
+                            .unwrap_or_default()
+                    })
+                })
+                .unwrap_or_default()
+        })
+    };
+
+    // avatars of the members in the joined call
+    let avatars = move || {
+        let members = with!(|room_id, calls| room_id
+            .as_ref()
+            .and_then(|id| calls.get(id).cloned())
+            .unwrap_or_default());
+
+        members
+            .into_iter()
+            .map(|member| {
+                let src = if member.user_id == user.with_untracked(|v| v.id) {
+                    user.with_untracked(|v| v.avatar.clone())
+                } else {
+                    chats.friends().with_untracked(|fds| {
+                        fds.iter()
+                            .find(|v| v.id == member.user_id)
+                            .map(|v| v.avatar.clone())
+                            .unwrap_or_default()
+                    })
+                };
+                (member.client_id, src)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let muted = create_rw_signal(false);
+    create_effect(move |_| {
+        if room_id.with(Option::is_none) {
+            muted.set(false);
+        }
+    });
+
+    view! {
+        <div class="fixed inset-x-0 top-4 flex justify-center">
+            <AnimatedShow
+                when=Signal::derive(move || room_id.with(Option::is_some))
+                show_class="animate-slide-in-down"
+                hide_class="animate-slide-out-up"
+                hide_delay=Duration::from_millis(150)
+            >
+                <div class="flex items-center gap-3 rounded-md px-4 py-2 bg-surface text-surface-on border border-border shadow-sm">
+                    <div class="flex -space-x-3">
+                        <For
+                            each=avatars
+                            key=|(client_id, _)| *client_id
+                            children=|(_, src)| {
+                                view! {
+                                    <div class="rounded-full ring-2 ring-surface">
+                                        <Avatar src />
+                                    </div>
+                                }
+                            }
+                        />
+                    </div>
+                    <div class="text-sm pr-2">
+                        <p class="text-surface-on font-semibold text-center">{room_name}</p>
+                        <div class="flex items-center space-x-2">
+                            <span class="relative inline-flex rounded-full size-2 bg-success"></span>
+                            <p class="text-xs text-success">"Room call"</p>
+                        </div>
+                    </div>
+                    <button
+                        type="button"
+                        on:click=move |_| rtc.toggle_mute(muted, toast)
+                        class="rounded-full p-2 bg-accent text-accent-on border border-border"
+                    >
+                        <Show when=move || muted.get() fallback=|| view! { <MicOn class="size-4" /> }>
+                            <MicOff class="size-4" />
+                        </Show>
+                    </button>
+                    <button type="button" on:click=move |_| rtc.leave(ws) class="rounded-full p-2 bg-danger text-danger-on">
+                        <PhoneSolid class="size-4 origin-center rotate-[135deg] translate-y-0.5" />
+                    </button>
+                </div>
+            </AnimatedShow>
+        </div>
+    }
+}
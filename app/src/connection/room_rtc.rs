@@ -0,0 +1,393 @@
+use leptos::*;
+use std::collections::HashMap;
+
+use super::WebSocketState;
+use crate::components::Toast;
+use common::{CallPeer, Event};
+
+cfg_if::cfg_if! { if #[cfg(feature = "hydrate")] {
+    use uuid::Uuid;
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{
+        HtmlAudioElement, MediaStream, MediaStreamConstraints, MediaStreamTrack,
+        RtcPeerConnection, RtcPeerConnectionIceEvent, RtcTrackEvent,
+    };
+    use super::webrtc::{
+        add_ice_candidate, create_plain_connection, create_sdp_answer, create_sdp_offer,
+        store_sdp_answer,
+    };
+    use common::{IceCandidate, PeerSignal, SignalData};
+}}
+
+// ==================== // RoomRtcState // ==================== //
+
+/// State of a full-mesh voice call in a room, every member keeps
+/// a peer connection to each of the other members
+#[derive(Clone)]
+pub struct RoomRtcState(StoredValue<RoomRtcInner>);
+
+impl Copy for RoomRtcState {}
+
+#[derive(Clone)]
+struct RoomRtcInner {
+    #[cfg(feature = "hydrate")]
+    stream: Option<MediaStream>,
+    #[cfg(feature = "hydrate")]
+    peers: HashMap<Uuid, PeerConn>,
+    #[cfg(feature = "hydrate")]
+    pending: HashMap<Uuid, Vec<IceCandidate>>,
+    room_id: RwSignal<Option<String>>,
+    calls: RwSignal<HashMap<String, Vec<CallPeer>>>,
+}
+
+#[cfg(feature = "hydrate")]
+#[derive(Clone)]
+struct PeerConn {
+    pc: RtcPeerConnection,
+    audio: HtmlAudioElement,
+    ready: bool,
+}
+
+impl RoomRtcState {
+    pub fn new() -> Self {
+        let inner = RoomRtcInner {
+            #[cfg(feature = "hydrate")]
+            stream: None,
+            #[cfg(feature = "hydrate")]
+            peers: HashMap::new(),
+            #[cfg(feature = "hydrate")]
+            pending: HashMap::new(),
+            room_id: create_rw_signal(None),
+            calls: create_rw_signal(HashMap::new()),
+        };
+        Self(store_value(inner))
+    }
+
+    /// Returns the room id of the joined call
+    ///
+    pub fn room_id(&self) -> RwSignal<Option<String>> {
+        self.0.with_value(|v| v.room_id)
+    }
+
+    /// Returns the members of all ongoing room calls
+    ///
+    pub fn calls(&self) -> RwSignal<HashMap<String, Vec<CallPeer>>> {
+        self.0.with_value(|v| v.calls)
+    }
+
+    /// Open the microphone and ask the server to join the call of a room
+    ///
+    pub fn join(&self, room_id: String, ws: WebSocketState) {
+        #[cfg(feature = "hydrate")]
+        {
+            let rtc_ref = self.0;
+            spawn_local(async move {
+                if let Ok(stream) = get_local_stream().await {
+                    rtc_ref.update_value(|v| v.stream = Some(stream));
+                }
+                ws.send(Event::JoinRoomCall(room_id));
+            });
+        }
+
+        #[cfg(not(feature = "hydrate"))]
+        ws.send(Event::JoinRoomCall(room_id));
+    }
+
+    /// Leave the joined call and close all peer connections
+    ///
+    pub fn leave(&self, ws: WebSocketState) {
+        if let Some(room_id) = self.room_id().get_untracked() {
+            ws.send(Event::LeaveRoomCall(room_id));
+        }
+        self.reset();
+    }
+
+    /// Close all peer connections and release the microphone
+    ///
+    pub fn reset(&self) {
+        #[cfg(feature = "hydrate")]
+        self.0.update_value(|v| {
+            for (_, conn) in v.peers.drain() {
+                conn.close();
+            }
+            v.pending.clear();
+            if let Some(stream) = v.stream.take() {
+                for track in stream.get_tracks() {
+                    MediaStreamTrack::from(track).stop();
+                }
+            }
+        });
+        self.room_id().set(None);
+    }
+
+    /// Mute or unmute the local audio tracks
+    ///
+    pub fn toggle_mute(&self, muted: RwSignal<bool>, toast: Toast) {
+        let value = muted.get_untracked();
+
+        #[cfg(feature = "hydrate")]
+        if let Some(stream) = self.0.with_value(|v| v.stream.clone()) {
+            for track in stream.get_audio_tracks() {
+                MediaStreamTrack::from(track).set_enabled(value);
+            }
+            muted.set(!value);
+            return;
+        }
+
+        if !value {
+            muted.set(true);
+        }
+        toast.error(String::from("No audio input found"))
+    }
+
+    /// Receive the members of the call before joining and send offers to them
+    ///
+    #[cfg(feature = "hydrate")]
+    pub fn receive_room_call(&self, room_id: String, peers: Vec<CallPeer>, ws: WebSocketState) {
+        self.room_id().set(Some(room_id.clone()));
+
+        for peer in peers {
+            let Ok(pc) = self.connect(&room_id, peer, ws) else {
+                continue;
+            };
+
+            let room_id = room_id.clone();
+            spawn_local(async move {
+                let Ok(offer) = create_sdp_offer(&pc).await else {
+                    return;
+                };
+                ws.send(Event::SendSignal(PeerSignal {
+                    room_id,
+                    peer,
+                    data: SignalData::Offer(offer),
+                }));
+            });
+        }
+    }
+
+    /// Receive the current members of a room call
+    ///
+    #[cfg(feature = "hydrate")]
+    pub fn receive_members(&self, room_id: String, members: Vec<CallPeer>) {
+        if self
+            .room_id()
+            .with_untracked(|v| v.as_ref() == Some(&room_id))
+        {
+            self.0.update_value(|v| {
+                let left: Vec<Uuid> = v
+                    .peers
+                    .keys()
+                    .filter(|client_id| !members.iter().any(|m| m.client_id == **client_id))
+                    .copied()
+                    .collect();
+                for client_id in left {
+                    if let Some(conn) = v.peers.remove(&client_id) {
+                        conn.close();
+                    }
+                    v.pending.remove(&client_id);
+                }
+            });
+        }
+
+        self.calls().update(|calls| {
+            if members.is_empty() {
+                calls.remove(&room_id);
+            } else {
+                calls.insert(room_id, members);
+            }
+        });
+    }
+
+    /// Receive signalling data from another member of the joined call
+    ///
+    #[cfg(feature = "hydrate")]
+    pub fn receive_signal(&self, signal: PeerSignal, ws: WebSocketState) {
+        let PeerSignal {
+            room_id,
+            peer,
+            data,
+        } = signal;
+
+        if !self
+            .room_id()
+            .with_untracked(|v| v.as_ref() == Some(&room_id))
+        {
+            return;
+        }
+
+        let rtc_ref = self.0;
+        match data {
+            SignalData::Offer(offer) => {
+                let Ok(pc) = self.connect(&room_id, peer, ws) else {
+                    return;
+                };
+                spawn_local(async move {
+                    let Ok(answer) = create_sdp_answer(&pc, offer).await else {
+                        return;
+                    };
+                    flush_candidates(rtc_ref, peer.client_id);
+                    ws.send(Event::SendSignal(PeerSignal {
+                        room_id,
+                        peer,
+                        data: SignalData::Answer(answer),
+                    }));
+                });
+            }
+            SignalData::Answer(answer) => {
+                let Some(pc) = rtc_ref
+                    .with_value(|v| v.peers.get(&peer.client_id).map(|conn| conn.pc.clone()))
+                else {
+                    return;
+                };
+                spawn_local(async move {
+                    if store_sdp_answer(&pc, answer).await.is_ok() {
+                        flush_candidates(rtc_ref, peer.client_id);
+                    }
+                });
+            }
+            SignalData::Candidate(candidate) => {
+                let pc = rtc_ref.with_value(|v| {
+                    v.peers
+                        .get(&peer.client_id)
+                        .filter(|conn| conn.ready)
+                        .map(|conn| conn.pc.clone())
+                });
+                if let Some(pc) = pc {
+                    spawn_local(async move {
+                        let _ = add_ice_candidate(&pc, candidate).await;
+                    });
+                } else {
+                    rtc_ref.update_value(|v| {
+                        v.pending.entry(peer.client_id).or_default().push(candidate);
+                    });
+                }
+            }
+        }
+    }
+
+    /// Create a peer connection to a member and attach the local audio
+    ///
+    #[cfg(feature = "hydrate")]
+    fn connect(
+        &self,
+        room_id: &str,
+        peer: CallPeer,
+        ws: WebSocketState,
+    ) -> Result<RtcPeerConnection, JsValue> {
+        let pc = create_plain_connection()?;
+        let audio = HtmlAudioElement::new()?;
+        audio.set_autoplay(true);
+
+        set_on_icecandidate(&pc, room_id.to_owned(), peer, ws);
+        set_on_track(&pc, audio.clone());
+
+        if let Some(stream) = self.0.with_value(|v| v.stream.clone()) {
+            for track in stream.get_audio_tracks() {
+                let track = MediaStreamTrack::from(track);
+                pc.add_track_0(&track, &stream);
+            }
+        }
+
+        let conn = PeerConn {
+            pc: pc.clone(),
+            audio,
+            ready: false,
+        };
+        self.0.update_value(|v| {
+            if let Some(old) = v.peers.insert(peer.client_id, conn) {
+                old.close();
+            }
+        });
+
+        Ok(pc)
+    }
+}
+
+#[cfg(feature = "hydrate")]
+impl PeerConn {
+    fn close(&self) {
+        self.audio.set_src_object(None);
+        self.pc.close();
+    }
+}
+
+/// Mark the peer connection ready and add the buffered ice candidates
+///
+#[cfg(feature = "hydrate")]
+fn flush_candidates(rtc_ref: StoredValue<RoomRtcInner>, client_id: Uuid) {
+    let mut pc = None;
+    let mut candidates = Vec::new();
+
+    rtc_ref.update_value(|v| {
+        if let Some(conn) = v.peers.get_mut(&client_id) {
+            conn.ready = true;
+            pc = Some(conn.pc.clone());
+        }
+        candidates = v.pending.remove(&client_id).unwrap_or_default();
+    });
+
+    if let Some(pc) = pc {
+        spawn_local(async move {
+            for candidate in candidates {
+                let _ = add_ice_candidate(&pc, candidate).await;
+            }
+        });
+    }
+}
+
+/// Set ice candidate callback to a peer conn of the room call
+///
+#[cfg(feature = "hydrate")]
+fn set_on_icecandidate(
+    pc: &RtcPeerConnection,
+    room_id: String,
+    peer: CallPeer,
+    ws: WebSocketState,
+) {
+    let onicecandidate_callback =
+        Closure::<dyn FnMut(_)>::new(move |ev: RtcPeerConnectionIceEvent| {
+            if let Some(rtc_candidate) = ev.candidate() {
+                let candidate = IceCandidate {
+                    candidate: rtc_candidate.candidate(),
+                    sdp_mid: rtc_candidate.sdp_mid().unwrap_or_default(),
+                    sdp_m_line_index: rtc_candidate.sdp_m_line_index().unwrap_or_default(),
+                };
+                ws.send(Event::SendSignal(PeerSignal {
+                    room_id: room_id.clone(),
+                    peer,
+                    data: SignalData::Candidate(candidate),
+                }));
+            }
+        });
+    pc.set_onicecandidate(Some(onicecandidate_callback.as_ref().unchecked_ref()));
+    onicecandidate_callback.forget();
+}
+
+/// Play the remote stream of a peer conn in its own audio element
+///
+#[cfg(feature = "hydrate")]
+fn set_on_track(pc: &RtcPeerConnection, audio: HtmlAudioElement) {
+    let ontrack_callback = Closure::<dyn FnMut(_)>::new(move |ev: RtcTrackEvent| {
+        let stream = MediaStream::from(ev.streams().get(0));
+        audio.set_src_object(Some(&stream));
+        let _ = audio.play();
+    });
+    pc.set_ontrack(Some(ontrack_callback.as_ref().unchecked_ref()));
+    ontrack_callback.forget();
+}
+
+/// Get the local audio stream from the microphone
+///
+#[cfg(feature = "hydrate")]
+async fn get_local_stream() -> Result<MediaStream, JsValue> {
+    let media_devices = window().navigator().media_devices()?;
+
+    let constraints = MediaStreamConstraints::new();
+    constraints.set_audio(&JsValue::TRUE);
+    constraints.set_video(&JsValue::FALSE);
+
+    let stream_promise = media_devices.get_user_media_with_constraints(&constraints)?;
+    let ms = JsFuture::from(stream_promise).await?;
+    Ok(MediaStream::from(ms))
+}
@@ -0,0 +1,112 @@
+use leptos::*;
+
+use common::{Error, InsertCallReportArg};
+
+cfg_if::cfg_if! { if #[cfg(feature = "hydrate")] {
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::js_sys::{Map, Reflect};
+    use web_sys::RtcPeerConnection;
+    use common::CallStats;
+}}
+
+/// Save the summary of a finished call for the dashboard
+///
+#[server]
+pub async fn report_call(arg: InsertCallReportArg) -> Result<(), ServerFnError<Error>> {
+    use common::{ArgsValidator, AuthExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let arg = ArgsValidator::validate(arg)?;
+    arg.insert(user.id, &store).await?;
+    Ok(())
+}
+
+/// Sample the quality statistics of a peer connection, `prev` keeps the
+/// received bytes and timestamp of the last sample to compute the bitrate
+///
+#[cfg(feature = "hydrate")]
+pub(super) async fn sample_stats(
+    pc: &RtcPeerConnection,
+    prev: Option<(f64, f64)>,
+) -> Result<(CallStats, Option<(f64, f64)>), JsValue> {
+    let report = JsFuture::from(pc.get_stats()).await?;
+    let report = report.unchecked_into::<Map>();
+
+    let mut entries = Vec::new();
+    report.for_each(&mut |value, _| entries.push(value));
+    let find = |key: &str, value: &str| entries.iter().find(|v| get_str(v, key) == value).cloned();
+
+    let mut stats = CallStats::default();
+
+    // the selected candidate pair is referenced by the transport
+    let pair = entries
+        .iter()
+        .find(|v| get_str(v, "type") == "transport")
+        .map(|v| get_str(v, "selectedCandidatePairId"))
+        .filter(|id| !id.is_empty())
+        .and_then(|id| find("id", &id))
+        .or_else(|| {
+            entries
+                .iter()
+                .find(|v| {
+                    get_str(v, "type") == "candidate-pair"
+                        && get_str(v, "state") == "succeeded"
+                        && Reflect::get(v, &"nominated".into())
+                            .map(|x| x.is_truthy())
+                            .unwrap_or(false)
+                })
+                .cloned()
+        });
+
+    if let Some(pair) = pair {
+        stats.rtt = get_f64(&pair, "currentRoundTripTime") * 1000.0;
+        if let Some(local) = find("id", &get_str(&pair, "localCandidateId")) {
+            stats.candidate_type = get_str(&local, "candidateType");
+        }
+    }
+
+    let mut current = None;
+    let inbound = entries
+        .iter()
+        .find(|v| get_str(v, "type") == "inbound-rtp" && get_str(v, "kind") == "audio");
+    if let Some(inbound) = inbound {
+        stats.jitter = get_f64(inbound, "jitter") * 1000.0;
+
+        let lost = get_f64(inbound, "packetsLost").max(0.0);
+        let received = get_f64(inbound, "packetsReceived");
+        if lost + received > 0.0 {
+            stats.packet_loss = lost / (lost + received) * 100.0;
+        }
+
+        // bits per millisecond equals kilobits per second
+        let bytes = get_f64(inbound, "bytesReceived");
+        let timestamp = get_f64(inbound, "timestamp");
+        if let Some((prev_bytes, prev_timestamp)) = prev {
+            if timestamp > prev_timestamp {
+                stats.bitrate = (bytes - prev_bytes) * 8.0 / (timestamp - prev_timestamp);
+            }
+        }
+        current = Some((bytes, timestamp));
+    }
+
+    Ok((stats, current))
+}
+
+#[cfg(feature = "hydrate")]
+fn get_f64(value: &JsValue, key: &str) -> f64 {
+    Reflect::get(value, &JsValue::from_str(key))
+        .ok()
+        .and_then(|v| v.as_f64())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "hydrate")]
+fn get_str(value: &JsValue, key: &str) -> String {
+    Reflect::get(value, &JsValue::from_str(key))
+        .ok()
+        .and_then(|v| v.as_string())
+        .unwrap_or_default()
+}
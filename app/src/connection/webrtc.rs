@@ -3,7 +3,7 @@ use uuid::Uuid;
 
 use super::WebSocketState;
 use crate::components::Toast;
-use common::{Event, HungUpReson};
+use common::{CallStats, Event, HungUpReson};
 
 cfg_if::cfg_if! { if #[cfg(feature = "hydrate")] {
     use wasm_bindgen::{prelude::*, UnwrapThrowExt};
@@ -14,7 +14,9 @@ cfg_if::cfg_if! { if #[cfg(feature = "hydrate")] {
         RtcIceCandidate, RtcIceCandidateInit, RtcPeerConnectionIceEvent, RtcSdpType,
         RtcSessionDescriptionInit, RtcTrackEvent, RtcOfferOptions, RtcPeerConnection,
     };
-    use common::IceCandidate;
+    use std::time::Duration;
+    use super::stats::{report_call, sample_stats};
+    use common::{DateTime, IceCandidate, InsertCallReportArg};
 }}
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -40,6 +42,20 @@ struct WebRtcInner {
     friend_id: Option<i64>,
     client_id: Option<Uuid>,
     status: RwSignal<RtcStatus>,
+    stats: RwSignal<Option<CallStats>>,
+    #[cfg(feature = "hydrate")]
+    sampler: StatsSampler,
+}
+
+/// Periodic sampling of the call statistics
+///
+#[cfg(feature = "hydrate")]
+#[derive(Clone, Default)]
+struct StatsSampler {
+    interval: Option<IntervalHandle>,
+    started_at: i64,
+    last_bytes: Option<(f64, f64)>,
+    samples: Vec<CallStats>,
 }
 
 impl WebRtcState {
@@ -51,6 +67,9 @@ impl WebRtcState {
             friend_id: None,
             client_id: None,
             status: create_rw_signal(RtcStatus::Idle),
+            stats: create_rw_signal(None),
+            #[cfg(feature = "hydrate")]
+            sampler: StatsSampler::default(),
         };
         Self(store_value(inner))
     }
@@ -84,6 +103,12 @@ impl WebRtcState {
         self.0.with_value(|v| v.status)
     }
 
+    /// Returns the latest statistics of the call
+    ///
+    pub fn stats(&self) -> RwSignal<Option<CallStats>> {
+        self.0.with_value(|v| v.stats)
+    }
+
     /// Returns the friend id of calling
     ///
     pub fn friend_id(&self) -> Option<i64> {
@@ -135,6 +160,14 @@ impl WebRtcState {
     ///
     #[cfg(feature = "hydrate")]
     pub fn receive_hung_up(&self) {
+        if let Some(friend_id) = self.friend_id() {
+            if let Some(arg) = self.stop_stats(friend_id) {
+                spawn_local(async move {
+                    let _ = report_call(arg).await;
+                });
+            }
+        }
+
         if let Some(pc) = self.0.with_value(|v| v.pc.clone()) {
             let _ = stop_tracks();
             pc.close();
@@ -182,6 +215,8 @@ impl WebRtcState {
             v.pc = Some(pc.clone());
         });
 
+        self.start_stats();
+
         spawn_local(async move {
             set_on_icecandidate(&pc, friend_id, client_id, ws);
             set_on_track(&pc).unwrap_throw();
@@ -214,6 +249,8 @@ impl WebRtcState {
             v.pc = Some(pc.clone());
         });
 
+        self.start_stats();
+
         spawn_local(async move {
             set_on_icecandidate(&pc, friend_id, client_id, ws);
             set_on_track(&pc).unwrap_throw();
@@ -247,12 +284,77 @@ impl WebRtcState {
             })
         }
     }
+
+    /// Poll the statistics of the peer connection every two seconds
+    ///
+    #[cfg(feature = "hydrate")]
+    fn start_stats(&self) {
+        let rtc_ref = self.0;
+        let stats = self.stats();
+
+        let interval = set_interval_with_handle(
+            move || {
+                let Some((pc, prev)) =
+                    rtc_ref.with_value(|v| v.pc.clone().map(|pc| (pc, v.sampler.last_bytes)))
+                else {
+                    return;
+                };
+                spawn_local(async move {
+                    let Ok((sample, last_bytes)) = sample_stats(&pc, prev).await else {
+                        return;
+                    };
+                    // no candidate pair is selected before connected
+                    if sample.candidate_type.is_empty() {
+                        return;
+                    }
+                    rtc_ref.update_value(|v| {
+                        v.sampler.last_bytes = last_bytes;
+                        v.sampler.samples.push(sample.clone());
+                    });
+                    stats.set(Some(sample));
+                });
+            },
+            Duration::from_secs(2),
+        )
+        .ok();
+
+        rtc_ref.update_value(|v| {
+            if let Some(h) = v.sampler.interval.take() {
+                h.clear();
+            }
+            v.sampler = StatsSampler {
+                interval,
+                started_at: DateTime::now().timestamp,
+                ..Default::default()
+            };
+        });
+    }
+
+    /// Stop polling and returns the summary of the call if any sample was taken
+    ///
+    #[cfg(feature = "hydrate")]
+    fn stop_stats(&self, friend_id: i64) -> Option<InsertCallReportArg> {
+        let sampler = self
+            .0
+            .try_update_value(|v| std::mem::take(&mut v.sampler))?;
+        if let Some(h) = sampler.interval {
+            h.clear();
+        }
+        self.stats().set(None);
+
+        let stats = CallStats::average(&sampler.samples)?;
+        Some(InsertCallReportArg {
+            friend_id,
+            duration: DateTime::now().timestamp - sampler.started_at,
+            stats,
+        })
+    }
 }
 
 /// Create a rtc peer connection
 ///
 #[cfg(feature = "hydrate")]
-fn create_plain_connection() -> Result<RtcPeerConnection, JsValue> {
+pub(super) fn create_plain_connection() -> Result<RtcPeerConnection, JsValue> {
     RtcPeerConnection::new()
 }
 
@@ -351,7 +453,7 @@ async fn add_local_stream(pc: &RtcPeerConnection) -> Result<(), JsValue> {
 /// Create sdp offer
 ///
 #[cfg(feature = "hydrate")]
-async fn create_sdp_offer(pc: &RtcPeerConnection) -> Result<String, JsValue> {
+pub(super) async fn create_sdp_offer(pc: &RtcPeerConnection) -> Result<String, JsValue> {
     let options = RtcOfferOptions::new();
     options.set_offer_to_receive_audio(true);
 
@@ -373,7 +475,10 @@ async fn create_sdp_offer(pc: &RtcPeerConnection) -> Result<String, JsValue> {
 /// Create sdp answer
 ///
 #[cfg(feature = "hydrate")]
-async fn create_sdp_answer(pc: &RtcPeerConnection, offer: String) -> Result<String, JsValue> {
+pub(super) async fn create_sdp_answer(
+    pc: &RtcPeerConnection,
+    offer: String,
+) -> Result<String, JsValue> {
     let offer_obj = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
     offer_obj.set_sdp(&offer);
     let srd_promise = pc.set_remote_description(&offer_obj);
@@ -397,7 +502,10 @@ async fn create_sdp_answer(pc: &RtcPeerConnection, offer: String) -> Result<Stri
 /// Store remote answer desc
 ///
 #[cfg(feature = "hydrate")]
-async fn store_sdp_answer(pc: &RtcPeerConnection, answer: String) -> Result<(), JsValue> {
+pub(super) async fn store_sdp_answer(
+    pc: &RtcPeerConnection,
+    answer: String,
+) -> Result<(), JsValue> {
     // setting remote description
     let answer_obj = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
     answer_obj.set_sdp(&answer);
@@ -409,7 +517,10 @@ async fn store_sdp_answer(pc: &RtcPeerConnection, answer: String) -> Result<(),
 /// Add ice candidate with opt
 ///
 #[cfg(feature = "hydrate")]
-async fn add_ice_candidate(pc: &RtcPeerConnection, candidate: IceCandidate) -> Result<(), JsValue> {
+pub(super) async fn add_ice_candidate(
+    pc: &RtcPeerConnection,
+    candidate: IceCandidate,
+) -> Result<(), JsValue> {
     // setting remote description
     let candidate_init_dict = RtcIceCandidateInit::new("");
     candidate_init_dict.set_candidate(&candidate.candidate);
@@ -1,7 +1,7 @@
 use leptos::*;
 use web_sys::WebSocket;
 
-use super::WebRtcState;
+use super::{RoomRtcState, WebRtcState};
 use crate::home::ChatsState;
 use common::Event;
 
@@ -66,16 +66,17 @@ impl WebSocketState {
 
 // ==================== // provide_websocket // ==================== //
 
-pub fn provide_websocket(chats: ChatsState, webrtc: WebRtcState) {
+pub fn provide_websocket(chats: ChatsState, webrtc: WebRtcState, roomrtc: RoomRtcState) {
     #[cfg(feature = "ssr")]
     {
         let _ = chats;
         let _ = webrtc;
+        let _ = roomrtc;
         provide_websocket_0();
     }
 
     #[cfg(feature = "hydrate")]
-    provide_websocket_1(chats, webrtc);
+    provide_websocket_1(chats, webrtc, roomrtc);
 }
 
 #[cfg(feature = "ssr")]
@@ -84,7 +85,7 @@ fn provide_websocket_0() {
 }
 
 #[cfg(feature = "hydrate")]
-fn provide_websocket_1(chats: ChatsState, webrtc: WebRtcState) {
+fn provide_websocket_1(chats: ChatsState, webrtc: WebRtcState, roomrtc: RoomRtcState) {
     use std::rc::Rc;
 
     use leptos_router::use_location;
@@ -93,7 +94,7 @@ fn provide_websocket_1(chats: ChatsState, webrtc: WebRtcState) {
 
     use super::RtcStatus;
     use crate::components::Toast;
-    use crate::CHATS_PATH;
+    use crate::{CHATS_PATH, LOGIN_PATH};
     use common::{FriendStatus, HungUpReson};
 
     let ws_state = WebSocketState::new();
@@ -260,6 +261,18 @@ fn provide_websocket_1(chats: ChatsState, webrtc: WebRtcState) {
                 Event::ReceiveOffer(offser) => webrtc.send_answer(offser, ws_state),
                 Event::ReceiveAnswer(answer) => webrtc.receive_answer(answer),
                 Event::ReceiveCandidate(candidate) => webrtc.receive_candidate(candidate),
+                Event::ReceiveRoomCall(room_id, peers) => {
+                    roomrtc.receive_room_call(room_id, peers, ws_state)
+                }
+                Event::ReceiveCallMembers(room_id, members) => {
+                    roomrtc.receive_members(room_id, members)
+                }
+                Event::ReceiveSignal(signal) => roomrtc.receive_signal(signal, ws_state),
+                Event::ReceiveKick(reason) => {
+                    // the server closes the socket, leave before it is reconnected
+                    toast.error(reason);
+                    let _ = window().location().set_href(LOGIN_PATH);
+                }
                 _ => {}
             }
         });
@@ -277,6 +290,8 @@ fn provide_websocket_1(chats: ChatsState, webrtc: WebRtcState) {
         // onclose handler
         let onclose_callback = Closure::<dyn FnMut(_)>::new(move |_: WsEvent| {
             status.set(SocketStatus::Closed);
+            roomrtc.reset();
+            roomrtc.calls().set(Default::default());
         });
         ws.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
         onclose_callback.forget();
@@ -298,6 +313,7 @@ fn provide_websocket_1(chats: ChatsState, webrtc: WebRtcState) {
             });
             v.ws = None;
         });
+        roomrtc.reset();
         status.set(SocketStatus::Closed);
     });
 
@@ -1,10 +1,12 @@
 use leptos::*;
 use leptos_router::ActionForm;
 
-use crate::components::icons::{DeleteTrash, RefreshArrow, SpinCircle};
+use crate::components::icons::{LockOpen, PlaySolid, RefreshArrow, ShieldCheck, SpinCircle};
 use crate::components::{Avatar, Toast, UserRoleBadge};
 use crate::home::DateTimeState;
-use common::{Error, FeedData, FnError, HubData, User};
+use common::{
+    CallReports, Error, FeedData, FnError, HubData, JobKind, JobStatus, LockKind, LockedLogin, User,
+};
 
 #[server]
 async fn get_hub_data() -> Result<HubData, ServerFnError<Error>> {
@@ -16,10 +18,54 @@ async fn get_hub_data() -> Result<HubData, ServerFnError<Error>> {
     HubManager::get_data(&store).await
 }
 
+#[server]
+async fn list_call_reports() -> Result<CallReports, ServerFnError<Error>> {
+    use common::{AuthExtractor, CallReport, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    CallReport::list(5, &store).await.map_err(|e| e.into())
+}
+
 #[component]
 pub fn DashboardPage() -> impl IntoView {
     let refresh = create_rw_signal(0);
     let rsc = create_resource(move || refresh.get(), move |_| get_hub_data());
+    let calls = create_resource(move || refresh.get(), move |_| list_call_reports());
+
+    // the panel is rendered again after each run, so the result is shown here
+    let toast = expect_context::<Toast>();
+    let run_action = create_server_action::<RunJob>();
+    let jobs = create_resource(
+        move || (refresh.get(), run_action.version().get()),
+        move |_| list_jobs(),
+    );
+    let unlock_action = create_server_action::<UnlockLogin>();
+    let locked = create_resource(
+        move || (refresh.get(), unlock_action.version().get()),
+        move |_| list_locked_logins(),
+    );
+
+    let run_value = run_action.value();
+    create_effect(move |_| {
+        run_value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(status)) if status.success => {
+                toast.success(format!("{}: {}", status.kind.label(), status.result));
+            }
+            Some(Ok(status)) => toast.error(format!("{}: {}", status.kind.label(), status.result)),
+            _ => {}
+        });
+    });
+    let unlock_value = unlock_action.value();
+    create_effect(move |_| {
+        unlock_value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => toast.success(String::from("The lockout is cleared")),
+            _ => {}
+        });
+    });
 
     view! {
         <div class="p-6 grow h-full w-full">
@@ -49,6 +95,27 @@ pub fn DashboardPage() -> impl IntoView {
                         ().into_view()
                     }
                 }}
+                {move || {
+                    if let Some(Ok(rsp)) = jobs.get() {
+                        view! { <JobsPanel jobs=rsp action=run_action /> }
+                    } else {
+                        ().into_view()
+                    }
+                }}
+                {move || {
+                    if let Some(Ok(rsp)) = locked.get() {
+                        view! { <LockedPanel locked=rsp action=unlock_action /> }
+                    } else {
+                        ().into_view()
+                    }
+                }}
+                {move || {
+                    if let Some(Ok(rsp)) = calls.get() {
+                        view! { <CallQuality data=rsp /> }
+                    } else {
+                        ().into_view()
+                    }
+                }}
 
             </Transition>
         </div>
@@ -58,16 +125,13 @@ pub fn DashboardPage() -> impl IntoView {
 // ==================== // Overview // ==================== //
 
 #[server]
-async fn clean_shared_files() -> Result<String, ServerFnError<Error>> {
-    use common::{AuthExtractor, ConfigExtractor, FileManager, StoreExtractor};
+async fn verify_shared_files() -> Result<Vec<String>, ServerFnError<Error>> {
+    use common::{AuthExtractor, Blob, StoreExtractor};
 
     let store = StoreExtractor::use_store()?;
     let _ = AuthExtractor::use_admin(false, &store).await?;
 
-    let config = ConfigExtractor::use_config()?;
-    FileManager::clean_outdated_files(config)
-        .await
-        .map_err(|err| err.into())
+    Blob::verify(&store).await.map_err(|err| err.into())
 }
 
 #[component]
@@ -82,13 +146,22 @@ fn Overview(data: HubData) -> impl IntoView {
     } = data;
 
     let toast = expect_context::<Toast>();
-    let action = create_server_action::<CleanSharedFiles>();
-    let value = action.value();
+    let verify_action = create_server_action::<VerifySharedFiles>();
+    let verify_value = verify_action.value();
     create_effect(move |_| {
-        value.with(|val| match val {
+        verify_value.with(|val| match val {
             Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
-            Some(Ok(deleted_size)) => {
-                toast.success(format!("{} has been clean", deleted_size));
+            Some(Ok(corrupted)) if corrupted.is_empty() => {
+                toast.success(String::from("All shared files are intact"));
+            }
+            Some(Ok(corrupted)) => {
+                toast.error(format!(
+                    "{} files are missing or corrupted",
+                    corrupted.len()
+                ));
+                for url in corrupted {
+                    logging::warn!("corrupted file: {}", url);
+                }
             }
             _ => {}
         });
@@ -111,11 +184,13 @@ fn Overview(data: HubData) -> impl IntoView {
             <div class="p-5 rounded-md border border-border shadow-sm">
                 <div class="flex items-center justify-between">
                     <h4 class="mb-2 text-sm font-medium">"Shared files"</h4>
-                    <ActionForm action>
-                        <button type="submit" class="text-muted hover:text-primary">
-                            <DeleteTrash class="size-4" />
-                        </button>
-                    </ActionForm>
+                    <div class="flex items-center gap-2">
+                        <ActionForm action=verify_action>
+                            <button type="submit" title="Verify files" class="text-muted hover:text-primary">
+                                <ShieldCheck class="size-4" />
+                            </button>
+                        </ActionForm>
+                    </div>
                 </div>
                 <p class="text-xl font-bold">{share_size}</p>
             </div>
@@ -138,6 +213,194 @@ fn Overview(data: HubData) -> impl IntoView {
     }
 }
 
+// ==================== // JobsPanel // ==================== //
+
+#[server]
+async fn list_jobs() -> Result<Vec<JobStatus>, ServerFnError<Error>> {
+    use common::{AuthExtractor, SchedulerExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    let scheduler = SchedulerExtractor::use_scheduler()?;
+    scheduler.list().await.map_err(|err| err.into())
+}
+
+#[server]
+async fn run_job(kind: JobKind) -> Result<JobStatus, ServerFnError<Error>> {
+    use common::{AuthExtractor, SchedulerExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    let scheduler = SchedulerExtractor::use_scheduler()?;
+    scheduler.run(kind).await.map_err(|err| err.into())
+}
+
+#[component]
+fn JobsPanel(
+    jobs: Vec<JobStatus>,
+    action: Action<RunJob, Result<JobStatus, ServerFnError<Error>>>,
+) -> impl IntoView {
+    let dts = expect_context::<DateTimeState>();
+    let pending = action.pending();
+    let jobs = store_value(jobs);
+
+    view! {
+        <div class="mt-12 p-4 rounded-md border border-border shadow-sm">
+            <h3 class="font-semibold">Jobs</h3>
+            <p class="text-sm font-medium text-muted">"Maintenance tasks run in the background"</p>
+            <table class="w-full mt-6 text-sm">
+                <thead>
+                    <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
+                        <th class="h-10 px-2 font-medium">"Job"</th>
+                        <th class="h-10 px-2 font-medium">"Schedule"</th>
+                        <th class="h-10 px-2 font-medium">"Last run"</th>
+                        <th class="h-10 px-2 font-medium">"Duration"</th>
+                        <th class="h-10 px-2 font-medium">"Result"</th>
+                        <th class="h-10 px-2 font-medium"></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=move || jobs.get_value()
+                        key=move |job| job.kind
+                        children=move |job| {
+                            let schedule = job.interval_label();
+                            let running = job.running;
+                            let last_run = if job.last_run > 0 {
+                                dts.fmt_sm(job.last_run)
+                            } else {
+                                String::from("Never")
+                            };
+                            let cresult = if job.success { "px-2 h-12" } else { "px-2 h-12 text-danger" };
+                            view! {
+                                <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
+                                    <td class="px-2 h-12">{job.kind.label()}</td>
+                                    <td class="px-2 h-12">{schedule}</td>
+                                    <td class="px-2 h-12">{last_run}</td>
+                                    <td class="px-2 h-12">{format!("{} ms", job.duration)}</td>
+                                    <td class=cresult>{job.result}</td>
+                                    <td class="px-2 h-12 text-right">
+                                        <ActionForm action>
+                                            <input type="hidden" name="kind" value=format!("{:?}", job.kind) />
+                                            <button
+                                                type="submit"
+                                                title="Run now"
+                                                disabled=move || running || pending.get()
+                                                class="text-muted hover:text-primary"
+                                            >
+                                                <PlaySolid class="size-4" />
+                                            </button>
+                                        </ActionForm>
+                                    </td>
+                                </tr>
+                            }
+                        }
+                    />
+
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+// ==================== // LockedPanel // ==================== //
+
+#[server]
+async fn list_locked_logins() -> Result<Vec<LockedLogin>, ServerFnError<Error>> {
+    use common::{AuthExtractor, ConfigExtractor, LoginThrottle, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    let config = ConfigExtractor::use_config()?;
+    LoginThrottle::list_locked(&config.login, &store)
+        .await
+        .map_err(|err| err.into())
+}
+
+#[server]
+async fn unlock_login(kind: LockKind, name: String) -> Result<(), ServerFnError<Error>> {
+    use common::{AuthExtractor, LoginThrottle, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    LoginThrottle::unlock(kind, &name, &store)
+        .await
+        .map_err(|err| err.into())
+}
+
+#[component]
+fn LockedPanel(
+    locked: Vec<LockedLogin>,
+    action: Action<UnlockLogin, Result<(), ServerFnError<Error>>>,
+) -> impl IntoView {
+    let pending = action.pending();
+    let empty = locked.is_empty();
+    let locked = store_value(locked);
+
+    view! {
+        <div class="mt-12 p-4 rounded-md border border-border shadow-sm">
+            <h3 class="font-semibold">Lockouts</h3>
+            <p class="text-sm font-medium text-muted">"Accounts and addresses locked after failed logins"</p>
+            <table class="w-full mt-6 text-sm">
+                <thead>
+                    <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
+                        <th class="h-10 px-2 font-medium">"Kind"</th>
+                        <th class="h-10 px-2 font-medium">"Name"</th>
+                        <th class="h-10 px-2 font-medium">"Failures"</th>
+                        <th class="h-10 px-2 font-medium">"Unlocks in"</th>
+                        <th class="h-10 px-2 font-medium"></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=move || locked.get_value()
+                        key=move |item| (item.kind, item.name.clone())
+                        children=move |item| {
+                            let expire_in = item.expire_in.max(0);
+                            view! {
+                                <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
+                                    <td class="px-2 h-12">{item.kind.label()}</td>
+                                    <td class="px-2 h-12">{item.name.clone()}</td>
+                                    <td class="px-2 h-12">{item.failures}</td>
+                                    <td class="px-2 h-12">
+                                        {format!("{}:{:02}", expire_in / 60, expire_in % 60)}
+                                    </td>
+                                    <td class="px-2 h-12 text-right">
+                                        <ActionForm action>
+                                            <input type="hidden" name="kind" value=format!("{:?}", item.kind) />
+                                            <input type="hidden" name="name" value=item.name />
+                                            <button
+                                                type="submit"
+                                                title="Unlock"
+                                                disabled=pending
+                                                class="text-muted hover:text-primary"
+                                            >
+                                                <LockOpen class="size-4" />
+                                            </button>
+                                        </ActionForm>
+                                    </td>
+                                </tr>
+                            }
+                        }
+                    />
+
+                    <Show when=move || empty>
+                        <tr class="hover:bg-accent/50">
+                            <td class="px-2 h-12 w-full text-center text-muted" colspan="5">
+                                "No locked accounts"
+                            </td>
+                        </tr>
+                    </Show>
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
 // ==================== // FeedsTable // ==================== //
 
 #[component]
@@ -216,3 +479,94 @@ fn UsersSection(users: Vec<User>) -> impl IntoView {
         </div>
     }
 }
+
+// ==================== // CallQuality // ==================== //
+
+#[component]
+fn CallQuality(data: CallReports) -> impl IntoView {
+    let CallReports {
+        num_calls,
+        avg_rtt,
+        avg_packet_loss,
+        reports,
+    } = data;
+
+    let len = reports.len() as i32;
+    let reports = store_value(reports);
+    let dts = expect_context::<DateTimeState>();
+
+    view! {
+        <div class="mt-12 p-4 rounded-md border border-border shadow-sm">
+            <div class="flex items-start justify-between">
+                <div>
+                    <h3 class="font-semibold">Calls</h3>
+                    <p class="text-sm font-medium text-muted">"Quality of the latest calls"</p>
+                </div>
+                <div class="flex gap-6 text-sm">
+                    <p>
+                        <span class="text-muted">"Calls "</span>
+                        <span class="font-semibold">{num_calls}</span>
+                    </p>
+                    <p>
+                        <span class="text-muted">"Avg RTT "</span>
+                        <span class="font-semibold">{format!("{:.0} ms", avg_rtt)}</span>
+                    </p>
+                    <p>
+                        <span class="text-muted">"Avg loss "</span>
+                        <span class="font-semibold">{format!("{:.1} %", avg_packet_loss)}</span>
+                    </p>
+                </div>
+            </div>
+            <table class="w-full mt-6 text-sm">
+                <thead>
+                    <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
+                        <th class="h-10 px-2 font-medium">"Users"</th>
+                        <th class="h-10 px-2 font-medium">"Duration"</th>
+                        <th class="h-10 px-2 font-medium">"RTT"</th>
+                        <th class="h-10 px-2 font-medium">"Jitter"</th>
+                        <th class="h-10 px-2 font-medium">"Loss"</th>
+                        <th class="h-10 px-2 font-medium">"Bitrate"</th>
+                        <th class="h-10 px-2 font-medium">"Candidate"</th>
+                        <th class="h-10 px-2 font-medium">"Ended"</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=move || reports.get_value()
+                        key=move |report| report.id
+                        children=move |report| {
+                            view! {
+                                <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
+                                    <td class="px-2 h-12">
+                                        {format!("{} - {}", report.nickname, report.friend_nickname)}
+                                    </td>
+                                    <td class="px-2 h-12">
+                                        {format!("{}:{:02}", report.duration / 60, report.duration % 60)}
+                                    </td>
+                                    <td class="px-2 h-12">{format!("{:.0} ms", report.rtt)}</td>
+                                    <td class="px-2 h-12">{format!("{:.1} ms", report.jitter)}</td>
+                                    <td class="px-2 h-12">{format!("{:.1} %", report.packet_loss)}</td>
+                                    <td class="px-2 h-12">{format!("{:.1} kbps", report.bitrate)}</td>
+                                    <td class="px-2 h-12">{report.candidate_type}</td>
+                                    <td class="px-2 h-12">{dts.fmt_sm(report.create_at)}</td>
+                                </tr>
+                            }
+                        }
+                    />
+
+                    {(len..5)
+                        .into_iter()
+                        .map(|_| {
+                            view! {
+                                <tr class="hover:bg-accent/50">
+                                    <td class="px-2 h-12 w-full" colspan="8"></td>
+                                </tr>
+                            }
+                        })
+                        .collect_view()}
+
+                </tbody>
+            </table>
+        </div>
+    }
+}
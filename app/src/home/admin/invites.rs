@@ -0,0 +1,151 @@
+use leptos::*;
+
+use super::invites_ops::{CreateInviteButton, DeleteInviteButton, InviteQrButton};
+use crate::components::icons::SpinCircle;
+use crate::components::{BlankTable, BlankTableItem, SelectLabel};
+use crate::home::DateTimeState;
+use common::{DateTime, Error, Invite};
+
+#[server]
+async fn list_invites() -> Result<Vec<Invite>, ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    let rsp = Invite::list(&store).await?;
+    Ok(rsp)
+}
+
+#[component]
+pub fn InvitesPage() -> impl IntoView {
+    let refresh = create_rw_signal(0);
+    let image = create_rw_signal(String::new());
+
+    let invites_resource = create_resource(move || refresh.get(), move |_| list_invites());
+
+    let tab_content = move || {
+        invites_resource
+            .get()
+            .map(|res: Result<Vec<Invite>, ServerFnError<Error>>| match res {
+                Ok(invites) => {
+                    let mut vlist = invites
+                        .into_iter()
+                        .map(|invite| view! { <InvitesTableItem invite refresh image /> })
+                        .collect::<Vec<_>>();
+                    let start = vlist.len() as i32;
+                    for _ in start..5 {
+                        vlist.push(view! { <BlankTableItem cols=6 /> });
+                    }
+                    vlist.into_view()
+                }
+                Err(e) => view! { <BlankTable rows=5 cols=6 msg=e.to_string() /> },
+            })
+            .unwrap_or_default()
+    };
+
+    view! {
+        <div class="p-6 grow h-full w-full">
+            <h2 class="text-xl font-semibold">Invites</h2>
+            <div class="my-1 flex justify-between">
+                <p class="text-sm text-muted">"Manage invite codes for self-service registration."</p>
+                <CreateInviteButton refresh />
+            </div>
+
+            <div class="w-full mt-8 overflow-auto rounded-md border border-border">
+                <table class="w-full text-sm">
+                    <thead>
+                        <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
+                            <th class="h-10 px-4 font-medium">Code</th>
+                            <th class="h-10 px-2 font-medium">Status</th>
+                            <th class="h-10 px-2 font-medium">Uses</th>
+                            <th class="h-10 px-2 font-medium">Role</th>
+                            <th class="h-10 px-2 font-medium">Creator</th>
+                            <th class="h-10 px-2"></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <Transition fallback=|| {
+                            view! {
+                                <div class="w-full h-56 flex items-center justify-center">
+                                    <SpinCircle class="animate-spin size-10" />
+                                </div>
+                            }
+                        }>{tab_content}</Transition>
+                    </tbody>
+                </table>
+            </div>
+        </div>
+
+        <Show when=move || !image.with(String::is_empty)>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <div
+                    on:click=move |_| image.set(String::new())
+                    class="fixed inset-0 z-40 h-screen flex flex-col items-center justify-center gap-4 bg-container/95"
+                >
+                    <div class="bg-accent rounded-lg shadow-sm border border-border overflow-hidden">
+                        <img src=image alt="Invite QR code" />
+                    </div>
+                    <a
+                        href=image
+                        download="invite.png"
+                        on:click=|ev| ev.stop_propagation()
+                        class="btn-primary h-8 px-5 flex items-center"
+                    >
+                        "Download"
+                    </a>
+                </div>
+            </Portal>
+        </Show>
+    }
+}
+
+#[component]
+fn InvitesTableItem(
+    invite: Invite,
+    refresh: RwSignal<i32>,
+    image: RwSignal<String>,
+) -> impl IntoView {
+    let dts = expect_context::<DateTimeState>();
+
+    let status = invite.status(DateTime::now().timestamp);
+    let status_class = if status == "Active" {
+        "text-success"
+    } else {
+        "text-danger"
+    };
+    let expire = match invite.expire_at {
+        0 => String::from("Never expires"),
+        ts => format!("Until {}", dts.fmt_date(ts - 1)),
+    };
+    let uses = match invite.max_uses {
+        0 => invite.uses.to_string(),
+        max => format!("{} / {}", invite.uses, max),
+    };
+    let friend = if invite.auto_friend { " · Friend" } else { "" };
+
+    view! {
+        <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
+            <td class="px-4 h-12 font-mono">{invite.code}</td>
+            <td class="px-2 h-12">
+                <p class=status_class>{status}</p>
+                <p class="text-xs text-muted">{expire}</p>
+            </td>
+            <td class="px-2 h-12">{uses}</td>
+            <td class="px-2 h-12">
+                {invite.role.label()}
+                <span class="text-muted">{friend}</span>
+            </td>
+            <td class="px-2 h-12">
+                <p>{invite.creator}</p>
+                <p class="text-xs text-muted">{dts.fmt_sm(invite.create_at)}</p>
+            </td>
+            <td class="pr-6 h-12">
+                <div class="flex items-center justify-center gap-3">
+                    <InviteQrButton invite_id=invite.id image />
+                    <DeleteInviteButton invite_id=invite.id refresh />
+                </div>
+            </td>
+        </tr>
+    }
+}
@@ -0,0 +1,237 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::components::icons::{DeleteTrash, PlusCircle, QrCode, WarnTriangle};
+use crate::components::{ModalWrapper, SelectLabel, Selector, Toast};
+use crate::home::DateTimeState;
+use common::{Error, FnError, InsertInviteArg, UserRole};
+
+// ==================== // CreateInviteButton // ==================== //
+
+#[server]
+async fn create_invite(arg: InsertInviteArg) -> Result<String, ServerFnError<Error>> {
+    use common::{ArgsValidator, AuthExtractor, StoreExtractor};
+
+    let arg = ArgsValidator::validate(arg)?;
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_admin(false, &store).await?;
+
+    let code = arg.call(user.id, &store).await?;
+    Ok(code)
+}
+
+#[component]
+pub fn CreateInviteButton(refresh: RwSignal<i32>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let dts = expect_context::<DateTimeState>();
+    let show_modal = create_rw_signal(false);
+
+    let action = create_server_action::<CreateInvite>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(code)) => {
+                toast.success(format!("Invite code {} is created", code));
+                refresh.update(|x| *x += 1);
+                show_modal.set(false)
+            }
+            _ => {}
+        });
+    });
+
+    let role = create_rw_signal(UserRole::User);
+    let role_options = store_value(vec![UserRole::User, UserRole::Admin]);
+
+    let auto_friend = create_rw_signal(true);
+    let bool_options = store_value(vec![true, false]);
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true) class="h-8 px-4 gap-1 btn-primary">
+            <PlusCircle class="size-4 stroke-2" />
+            <span class="text-sm font-medium">"New Invite"</span>
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <h3 class="text-xl font-semibold tracking-tight">"New Invite"</h3>
+                    <p class="text-sm text-muted">"Generate a code for self-service registration"</p>
+
+                    <ActionForm class="w-full flex flex-col gap-6 mt-6 mb-4" action>
+                        <input type="hidden" name="arg[offset]" value=dts.offset() />
+                        <div class="grid gap-2">
+                            <label for="max_uses" class="text-sm font-medium leading-none">
+                                "Max uses"
+                            </label>
+                            <input
+                                id="max_uses"
+                                type="number"
+                                min="0"
+                                name="arg[max_uses]"
+                                value="1"
+                                disabled=pending
+                                autocomplete="off"
+                                placeholder="0 for unlimited"
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid gap-2">
+                            <label for="expire_date" class="text-sm font-medium leading-none">
+                                "Expiry date"
+                            </label>
+                            <input
+                                id="expire_date"
+                                type="date"
+                                name="arg[expire_date]"
+                                disabled=pending
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid grid-cols-2 gap-6 mb-3">
+                            <div class="grid gap-2">
+                                <label for="role" class="text-sm font-medium leading-none">
+                                    Role
+                                </label>
+                                <input
+                                    id="role"
+                                    type="hidden"
+                                    name="arg[role]"
+                                    prop:value=move || role.with(|v| v.label())
+                                />
+                                <Selector value=role options=role_options />
+                            </div>
+                            <div class="grid gap-2">
+                                <label for="auto_friend" class="text-sm font-medium leading-none">
+                                    "Add as friend"
+                                </label>
+                                <input
+                                    id="auto_friend"
+                                    type="hidden"
+                                    name="arg[auto_friend]"
+                                    prop:value=move || auto_friend.get().to_string()
+                                />
+                                <Selector value=auto_friend options=bool_options />
+                            </div>
+                        </div>
+
+                        <div class="flex items-center justify-between space-x-2">
+                            <button type="button" on:click=move |_| show_modal.set(false) class="h-9 px-5 btn-ghost">
+                                Cancel
+                            </button>
+                            <button type="submit" disabled=pending class="h-9 px-5 btn-primary">
+                                "Submit"
+                            </button>
+                        </div>
+                    </ActionForm>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
+
+// ==================== // InviteQrButton // ==================== //
+
+#[server]
+async fn get_invite_qrcode(invite_id: i64) -> Result<String, ServerFnError<Error>> {
+    use common::{AuthExtractor, HostExtractor, Invite, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    let host = HostExtractor::use_host().await?;
+    let qrcode = Invite::qrcode(invite_id, &host, &store).await?;
+    Ok(qrcode)
+}
+
+#[component]
+pub fn InviteQrButton(invite_id: i64, image: RwSignal<String>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+
+    let action = create_server_action::<GetInviteQrcode>();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(qrcode)) => image.set(qrcode.clone()),
+            _ => {}
+        });
+    });
+
+    view! {
+        <ActionForm action>
+            <input type="hidden" name="invite_id" value=invite_id />
+            <button type="submit" title="Show QR code" class="flex">
+                <QrCode class="size-4 hover:stroke-success" />
+            </button>
+        </ActionForm>
+    }
+}
+
+// ==================== // DeleteInviteButton // ==================== //
+
+#[server]
+async fn delete_invite(invite_id: i64) -> Result<(), ServerFnError<Error>> {
+    use common::{AuthExtractor, Invite, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    Invite::delete(invite_id, &store).await?;
+    Ok(())
+}
+
+#[component]
+pub fn DeleteInviteButton(invite_id: i64, refresh: RwSignal<i32>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let show_modal = create_rw_signal(false);
+
+    let action = create_server_action::<DeleteInvite>();
+    let value = action.value();
+    let pending = action.pending();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => {
+                toast.success(String::from("Invite code has been deleted"));
+                refresh.update(|v| *v += 1);
+                show_modal.set(false);
+            }
+            _ => {}
+        });
+    });
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true)>
+            <DeleteTrash class="size-4 hover:stroke-danger" />
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <div class="my-6 size-16 flex items-center justify-center rounded-full bg-danger/20">
+                        <WarnTriangle class="size-10 stroke-danger" />
+                    </div>
+                    <h3 class="my-1 text-center text-xl font-semibold">"Delete invite code"</h3>
+                    <p class="mb-4 text-center text-muted">"The code can no longer be used to register."</p>
+                    <ActionForm class="w-full my-5" action>
+                        <input type="hidden" name="invite_id" value=invite_id />
+                        <button type="submit" disabled=pending class="w-full h-9 px-3 btn-primary">
+                            "Confirm"
+                        </button>
+                    </ActionForm>
+                    <button type="button" on:click=move |_| show_modal.set(false) class="w-full h-9 px-3 btn-outline">
+                        Cancel
+                    </button>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
@@ -4,14 +4,17 @@ use leptos_router::{use_location, use_navigate, Outlet, Redirect, Route};
 
 use super::state::{DateTimeState, UserState};
 use super::ADMIN_PATH;
-use crate::components::icons::{HomeHouse, LockClosed, QrCode, UserGroup};
+use crate::components::icons::{HomeHouse, InviteTicket, LockClosed, QrCode, UserGroup};
 use crate::components::MenuListItem;
 use crate::CHATS_PATH;
 use dashboard::DashboardPage;
+use invites::InvitesPage;
 use qrlink::QrLinkPage;
 use users::UsersPage;
 
 mod dashboard;
+mod invites;
+mod invites_ops;
 mod qrlink;
 mod qrlink_ops;
 mod users;
@@ -23,6 +26,8 @@ const USERS_NAME: &str = "/users";
 const USERS_PATH: &str = "/admin/users";
 const QRLINK_NAME: &str = "/qrlink";
 const QRLINK_PATH: &str = "/admin/qrlink";
+const INVITES_NAME: &str = "/invites";
+const INVITES_PATH: &str = "/admin/invites";
 
 #[component(transparent)]
 pub fn AdminRoutes() -> impl IntoView {
@@ -32,6 +37,7 @@ pub fn AdminRoutes() -> impl IntoView {
             <Route path=DASHBOARD_NAME view=DashboardPage />
             <Route path=USERS_NAME view=UsersPage />
             <Route path=QRLINK_NAME view=QrLinkPage />
+            <Route path=INVITES_NAME view=InvitesPage />
         </Route>
     }
 }
@@ -101,6 +107,13 @@ fn SideBar() -> impl IntoView {
                     <QrCode class="size-5" />
                     <h3>"Qrlink"</h3>
                 </MenuListItem>
+                <MenuListItem
+                    active=Signal::derive(move || pathname.with(|v| v.as_str() == INVITES_PATH))
+                    on:click=move |_| nav.call(INVITES_PATH)
+                >
+                    <InviteTicket class="size-5" />
+                    <h3>"Invites"</h3>
+                </MenuListItem>
             </ul>
         </div>
     }
@@ -2,10 +2,11 @@ use leptos::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlButtonElement, MouseEvent};
 
-use super::qrlink_ops::{DeleteLinkButton, UploadFileButton};
+use super::qrlink_ops::{DeleteLinkButton, EditLinkButton, UploadFileButton};
 use crate::components::icons::SpinCircle;
 use crate::components::{BlankTable, BlankTableItem};
-use common::{Error, FileLink, FileLinks};
+use crate::home::DateTimeState;
+use common::{DateTime, Error, FileLink, FileLinks};
 
 #[server]
 async fn list_links(page_id: i32) -> Result<FileLinks, ServerFnError<Error>> {
@@ -46,11 +47,11 @@ pub fn QrLinkPage() -> impl IntoView {
                         .collect::<Vec<_>>();
                     let start = vlist.len() as i32;
                     for _ in start..5 {
-                        vlist.push(view! { <BlankTableItem cols=4 /> });
+                        vlist.push(view! { <BlankTableItem cols=6 /> });
                     }
                     vlist.into_view()
                 }
-                Err(e) => view! { <BlankTable rows=5 cols=4 msg=e.to_string() /> },
+                Err(e) => view! { <BlankTable rows=5 cols=6 msg=e.to_string() /> },
             })
             .unwrap_or_default()
     };
@@ -60,7 +61,12 @@ pub fn QrLinkPage() -> impl IntoView {
             <h2 class="text-xl font-semibold">Qrlink</h2>
             <div class="my-1 flex justify-between">
                 <p class="text-sm text-muted">"Manage QR links for file download."</p>
-                <UploadFileButton refresh />
+                <div class="flex items-center gap-3">
+                    <a href="/links/downloads.csv" download="link-downloads.csv" class="btn-outline h-7 px-4 flex items-center">
+                        "Export CSV"
+                    </a>
+                    <UploadFileButton refresh />
+                </div>
             </div>
 
             <div class="w-full mt-8 overflow-auto rounded-md border border-border">
@@ -68,6 +74,8 @@ pub fn QrLinkPage() -> impl IntoView {
                     <thead>
                         <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
                             <th class="h-10 px-4 font-medium">Name</th>
+                            <th class="h-10 px-2 font-medium">Status</th>
+                            <th class="h-10 px-2 font-medium">Downloads</th>
                             <th class="h-10 px-2 font-medium">File</th>
                             <th class="h-10 px-3 font-medium">QR</th>
                             <th class="h-10 px-2"></th>
@@ -107,8 +115,24 @@ fn LinksTableItem(
     refresh: RwSignal<i32>,
     image: RwSignal<String>,
 ) -> impl IntoView {
+    let dts = expect_context::<DateTimeState>();
     let filelink = store_value(filelink);
 
+    let status = filelink.with_value(|v| v.status(DateTime::now().timestamp));
+    let status_class = if status == "Active" {
+        "text-success"
+    } else {
+        "text-danger"
+    };
+    let expire = filelink.with_value(|v| match v.expire_at {
+        0 => String::from("Never expires"),
+        ts => format!("Until {}", dts.fmt_date(ts - 1)),
+    });
+    let downloads = filelink.with_value(|v| match v.max_downloads {
+        0 => v.downloads.to_string(),
+        max => format!("{} / {}", v.downloads, max),
+    });
+
     let on_click_view = move |ev: MouseEvent| {
         ev.prevent_default();
         let target = ev.target().unwrap().unchecked_into::<HtmlButtonElement>();
@@ -118,6 +142,28 @@ fn LinksTableItem(
     view! {
         <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
             <td class="px-4 h-12">{filelink.with_value(|v| v.name.clone())}</td>
+            <td class="px-2 h-12">
+                <p class=status_class>
+                    {status}
+                    <Show when=move || filelink.with_value(|v| v.has_password)>
+                        <span class="text-muted">" · Password"</span>
+                    </Show>
+                </p>
+                <p class="text-xs text-muted">{expire}</p>
+            </td>
+            <td class="px-2 h-12">
+                <div class="flex items-center gap-3">
+                    <DownloadHistory daily=filelink.with_value(|v| v.daily.clone()) />
+                    <div>
+                        <p>{downloads}</p>
+                        <Show when=move || filelink.with_value(|v| v.failed > 0)>
+                            <p class="text-xs text-danger">
+                                {filelink.with_value(|v| format!("{} failed", v.failed))}
+                            </p>
+                        </Show>
+                    </div>
+                </div>
+            </td>
             <td class="px-2 h-12">
                 <a
                     href=filelink.with_value(|v| v.link.clone())
@@ -138,10 +184,36 @@ fn LinksTableItem(
                 </button>
             </td>
             <td class="pr-6 h-12">
-                <div class="flex items-center justify-center">
+                <div class="flex items-center justify-center gap-3">
+                    <EditLinkButton filelink refresh />
                     <DeleteLinkButton link_id=filelink.with_value(|v| v.id) refresh />
                 </div>
             </td>
         </tr>
     }
 }
+
+/// Bars of the daily downloads, the latest day on the right
+///
+#[component]
+fn DownloadHistory(daily: Vec<i64>) -> impl IntoView {
+    let max = daily.iter().copied().max().unwrap_or_default().max(1);
+    let title = format!(
+        "{} downloads in the last {} days",
+        daily.iter().sum::<i64>(),
+        daily.len()
+    );
+
+    view! {
+        <div title=title class="h-6 flex items-end gap-px">
+            {daily
+                .into_iter()
+                .map(|count| {
+                    let height = format!("height: {}%", (count * 100 / max).max(8));
+                    let class = if count > 0 { "w-1 rounded-sm bg-success" } else { "w-1 rounded-sm bg-accent" };
+                    view! { <div class=class style=height></div> }
+                })
+                .collect_view()}
+        </div>
+    }
+}
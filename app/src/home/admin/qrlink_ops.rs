@@ -2,9 +2,10 @@ use leptos::*;
 use leptos_router::ActionForm;
 use web_sys::{Event, FormData};
 
-use crate::components::icons::{DeleteTrash, WarnTriangle};
-use crate::components::{ModalWrapper, Toast};
-use common::{Error, FnError};
+use crate::components::icons::{DeleteTrash, EditPencil, WarnTriangle};
+use crate::components::{ModalWrapper, SelectLabel, Selector, Toast};
+use crate::home::DateTimeState;
+use common::{Error, FileLink, FnError, UpdateLinkArg};
 use server_fn::codec::{MultipartData, MultipartFormData};
 
 // ==================== // UploadFileButton // ==================== //
@@ -14,7 +15,7 @@ async fn save_file_link(data: MultipartData) -> Result<(), ServerFnError<Error>>
     use common::{AuthExtractor, ConfigExtractor, FileLink, HostExtractor, StoreExtractor};
 
     let store = StoreExtractor::use_store()?;
-    let _ = AuthExtractor::use_admin(false, &store).await?;
+    let user = AuthExtractor::use_admin(false, &store).await?;
 
     // `.into_inner()` returns the inner `multer` stream, it is `None` if we call this on the client,
     // but always `Some(_)` on the server, so is safe to unwrap
@@ -23,7 +24,7 @@ async fn save_file_link(data: MultipartData) -> Result<(), ServerFnError<Error>>
     if let Ok(Some(field)) = data.next_field().await {
         let config = ConfigExtractor::use_config()?;
         let host = HostExtractor::use_host().await?;
-        FileLink::save(field.into(), host, &store, config).await?;
+        FileLink::save(user.id, field.into(), host, &store, config).await?;
         Ok(())
     } else {
         Err(Error::BadRequest(String::from("No field found in multipart data")).into())
@@ -70,17 +71,168 @@ pub fn UploadFileButton(refresh: RwSignal<i32>) -> impl IntoView {
     }
 }
 
+// ==================== // EditLinkButton // ==================== //
+
+#[server]
+async fn update_link(arg: UpdateLinkArg) -> Result<(), ServerFnError<Error>> {
+    use common::{ArgsValidator, AuthExtractor, StoreExtractor};
+
+    let arg = ArgsValidator::validate(arg)?;
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    arg.call(&store).await?;
+    Ok(())
+}
+
+#[component]
+pub fn EditLinkButton(filelink: StoredValue<FileLink>, refresh: RwSignal<i32>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let dts = expect_context::<DateTimeState>();
+    let show_modal = create_rw_signal(false);
+
+    let action = create_server_action::<UpdateLink>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => {
+                toast.success(String::from("Update link successfully"));
+                refresh.update(|x| *x += 1);
+                show_modal.set(false)
+            }
+            _ => {}
+        });
+    });
+
+    // the link expires at the end of the chosen day
+    let expire_date = filelink.with_value(|v| match v.expire_at {
+        0 => String::new(),
+        ts => dts.fmt_date(ts - 1),
+    });
+    let clear_password = create_rw_signal(false);
+    let revoked = create_rw_signal(filelink.with_value(|v| v.revoked));
+    let bool_options = store_value(vec![false, true]);
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true) class="shrink-0">
+            <EditPencil class="size-4 hover:stroke-success" />
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <h3 class="text-xl font-semibold tracking-tight">"Update Link"</h3>
+                    <p class="text-sm text-muted">{filelink.with_value(|v| v.name.clone())}</p>
+
+                    <ActionForm class="w-full flex flex-col gap-6 mt-6 mb-4" action>
+                        <input type="hidden" name="arg[link_id]" value=filelink.with_value(|v| v.id) />
+                        <input type="hidden" name="arg[offset]" value=dts.offset() />
+                        <div class="grid gap-2">
+                            <label for="expire_date" class="text-sm font-medium leading-none">
+                                "Expiry date"
+                            </label>
+                            <input
+                                id="expire_date"
+                                type="date"
+                                name="arg[expire_date]"
+                                value=expire_date.clone()
+                                disabled=pending
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid gap-2">
+                            <label for="max_downloads" class="text-sm font-medium leading-none">
+                                "Max downloads"
+                            </label>
+                            <input
+                                id="max_downloads"
+                                type="number"
+                                min="0"
+                                name="arg[max_downloads]"
+                                value=filelink.with_value(|v| v.max_downloads)
+                                disabled=pending
+                                autocomplete="off"
+                                placeholder="0 for unlimited"
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid gap-2">
+                            <label for="link_password" class="text-sm font-medium leading-none">
+                                "Password: "
+                                <span class="text-muted">
+                                    {filelink.with_value(|v| if v.has_password { "set" } else { "none" })}
+                                </span>
+                            </label>
+                            <input
+                                id="link_password"
+                                type="password"
+                                name="arg[password]"
+                                disabled=pending
+                                autocomplete="new-password"
+                                placeholder="Set a new password"
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid grid-cols-2 gap-6 mb-3">
+                            <div class="grid gap-2">
+                                <label for="clear_password" class="text-sm font-medium leading-none">
+                                    "Remove password"
+                                </label>
+                                <input
+                                    id="clear_password"
+                                    type="hidden"
+                                    name="arg[clear_password]"
+                                    prop:value=move || clear_password.get().to_string()
+                                />
+                                <Selector value=clear_password options=bool_options />
+                            </div>
+                            <div class="grid gap-2">
+                                <label for="revoked" class="text-sm font-medium leading-none">
+                                    "Revoked: "
+                                    <span class="text-muted">{filelink.with_value(|v| v.revoked.label())}</span>
+                                </label>
+                                <input
+                                    id="revoked"
+                                    type="hidden"
+                                    name="arg[revoked]"
+                                    prop:value=move || revoked.get().to_string()
+                                />
+                                <Selector value=revoked options=bool_options />
+                            </div>
+                        </div>
+
+                        <div class="flex items-center justify-between space-x-2">
+                            <button type="button" on:click=move |_| show_modal.set(false) class="h-9 px-5 btn-ghost">
+                                Cancel
+                            </button>
+                            <button type="submit" disabled=pending class="h-9 px-5 btn-primary">
+                                "Submit"
+                            </button>
+                        </div>
+                    </ActionForm>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
+
 // ==================== // DeleteLinkButton // ==================== //
 
 #[server]
 async fn delete_link(link_id: i64) -> Result<(), ServerFnError<Error>> {
-    use common::{AuthExtractor, ConfigExtractor, FileLink, StoreExtractor};
+    use common::{AuthExtractor, FileLink, StoreExtractor};
 
     let store = StoreExtractor::use_store()?;
     let _ = AuthExtractor::use_admin(false, &store).await?;
 
-    let config = ConfigExtractor::use_config()?;
-    FileLink::delete(link_id, &store, config).await?;
+    FileLink::delete(link_id, &store).await?;
     Ok(())
 }
 
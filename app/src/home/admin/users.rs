@@ -1,6 +1,6 @@
 use leptos::*;
 
-use super::users_ops::{AddUserButton, DeleteUserButton, UpdateUserButton};
+use super::users_ops::{AddUserButton, DeleteUserButton, ResetTwoFactorButton, UpdateUserButton};
 use crate::components::{Avatar, BlankTable, BlankTableItem, UserActiveBadge, UserRoleBadge};
 use common::{Error, ListUsersArg, ListUsersRsp, User, UserRole};
 
@@ -182,6 +182,7 @@ fn UsersTableItem(user: User, refresh: RwSignal<i32>) -> impl IntoView {
             <td class="pr-6 h-14">
                 <div class="flex items-center justify-end space-x-6 text-xs text-primary">
                     <DeleteUserButton id=user.with_value(|v| v.id) refresh />
+                    <ResetTwoFactorButton id=user.with_value(|v| v.id) />
                     <UpdateUserButton user refresh />
                 </div>
             </td>
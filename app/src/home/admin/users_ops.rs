@@ -1,7 +1,7 @@
 use leptos::*;
 use leptos_router::ActionForm;
 
-use crate::components::icons::{DeleteTrash, EditPencil, PlusCircle, WarnTriangle};
+use crate::components::icons::{DeleteTrash, EditPencil, PlusCircle, ShieldCheck, WarnTriangle};
 use crate::components::{ModalWrapper, SelectLabel, Selector, Toast};
 use common::{Error, FnError, InsertUserArg, UpdateUserArg, User, UserRole};
 
@@ -16,6 +16,11 @@ async fn add_user(arg: InsertUserArg) -> Result<(), ServerFnError<Error>> {
     let store = StoreExtractor::use_store()?;
     let _ = AuthExtractor::use_admin(false, &store).await?;
 
+    // the password typed by the admin is only for the first login
+    let arg = InsertUserArg {
+        must_change_password: true,
+        ..arg
+    };
     arg.insert(&store).await?;
     Ok(())
 }
@@ -132,7 +137,7 @@ pub fn AddUserButton(refresh: RwSignal<i32>) -> impl IntoView {
 
 #[server]
 async fn update_user(arg: UpdateUserArg) -> Result<(), ServerFnError<Error>> {
-    use common::{ArgsValidator, AuthExtractor, HubManager, StoreExtractor};
+    use common::{ApiToken, ArgsValidator, AuthExtractor, HubManager, Session, StoreExtractor};
 
     let arg = ArgsValidator::validate(arg)?;
 
@@ -140,10 +145,15 @@ async fn update_user(arg: UpdateUserArg) -> Result<(), ServerFnError<Error>> {
     let _ = AuthExtractor::use_admin(false, &store).await?;
 
     arg.call(&store).await?;
-    if let Some(active) = arg.active {
-        if !active {
-            HubManager::remove_user(arg.id)?;
-        }
+    if arg.active == Some(false) {
+        // the sockets of the tokens are closed with the others
+        Session::delete_all(arg.id, &store).await?;
+        ApiToken::revoke_all(arg.id, &store).await?;
+        HubManager::kick_user(arg.id, "Your account has been deactivated")?;
+    } else if arg.password.is_some() {
+        // the user signs in again with the new password
+        Session::delete_all(arg.id, &store).await?;
+        HubManager::kick_user(arg.id, "Your password has been reset by an admin")?;
     }
     Ok(())
 }
@@ -219,6 +229,22 @@ pub fn UpdateUserButton(user: StoredValue<User>, refresh: RwSignal<i32>) -> impl
                             />
                         </div>
 
+                        <div class="grid gap-2">
+                            <label for="quota" class="text-sm font-medium leading-none">
+                                Quota
+                            </label>
+                            <input
+                                id="quota"
+                                type="number"
+                                min="0"
+                                name="arg[quota]"
+                                disabled=pending
+                                autocomplete="off"
+                                placeholder="Quota in MB, 0 for default"
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
                         <div class="grid grid-cols-2 gap-6 mb-3">
                             <div class="grid gap-2">
                                 <label for="role" class="text-sm font-medium leading-none">
@@ -259,6 +285,72 @@ pub fn UpdateUserButton(user: StoredValue<User>, refresh: RwSignal<i32>) -> impl
     }
 }
 
+// ==================== // ResetTwoFactorButton // ==================== //
+
+#[server]
+async fn reset_two_factor(user_id: i64) -> Result<(), ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor, TwoFactor};
+
+    let store = StoreExtractor::use_store()?;
+    let _ = AuthExtractor::use_admin(false, &store).await?;
+
+    if user_id < 1 {
+        return Err(Error::BadRequest(String::from("Invalid user id")).into());
+    }
+    TwoFactor::reset(user_id, &store).await?;
+    Ok(())
+}
+
+#[component]
+pub fn ResetTwoFactorButton(id: i64) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let show_modal = create_rw_signal(false);
+
+    let action = create_server_action::<ResetTwoFactor>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => {
+                toast.success(String::from("Two-factor authentication has been reset"));
+                show_modal.set(false)
+            }
+            _ => {}
+        });
+    });
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true) title="Reset 2FA">
+            <ShieldCheck class="size-4 hover:stroke-danger" />
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <div class="my-6 size-16 flex items-center justify-center rounded-full bg-danger/20">
+                        <WarnTriangle class="size-10 stroke-danger" />
+                    </div>
+                    <h3 class="my-1 text-center text-xl font-semibold">"Reset 2FA"</h3>
+                    <p class="mb-4 text-center text-muted">
+                        "The user will sign in with only the password until 2FA is enabled again."
+                    </p>
+                    <ActionForm class="w-full my-5" action>
+                        <input type="hidden" name="user_id" value=id />
+                        <button type="submit" disabled=pending class="w-full h-9 px-3 btn-primary">
+                            "Confirm"
+                        </button>
+                    </ActionForm>
+                    <button type="button" on:click=move |_| show_modal.set(false) class="w-full h-9 px-3 btn-outline">
+                        Cancel
+                    </button>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
+
 // ==================== // DeleteUserButton // ==================== //
 
 #[server]
@@ -272,7 +364,7 @@ async fn delete_user(user_id: i64) -> Result<(), ServerFnError<Error>> {
         return Err(Error::BadRequest(String::from("Invalid user id")).into());
     }
     User::delete(user_id, &store).await?;
-    HubManager::remove_user(user_id)?;
+    HubManager::kick_user(user_id, "Your account has been deleted")?;
     Ok(())
 }
 
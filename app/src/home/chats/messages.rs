@@ -3,10 +3,10 @@ use wasm_bindgen::JsCast;
 use web_sys::{ErrorEvent, Event, HtmlImageElement, MouseEvent};
 
 use super::DateTimeState;
-use crate::components::icons::FileDownload;
+use crate::components::icons::{FileDownload, PauseSolid, PlaySolid};
 use crate::components::Avatar;
 use crate::home::{ChatsState, UserState};
-use common::{Message, MessageKind};
+use common::{content_type, FileMeta, ImageMeta, Message, MessageKind};
 
 #[component]
 pub fn Messages() -> impl IntoView {
@@ -71,6 +71,9 @@ where
         content,
         url,
         kind,
+        duration,
+        image: meta,
+        files,
         divide,
         sender,
         send_at,
@@ -96,10 +99,11 @@ where
         target.set_src("/default/fallback.png");
     };
 
+    // the viewer always loads the full image instead of the thumbnail
+    let full_url = url.clone();
     let on_click_view = move |ev: MouseEvent| {
         ev.prevent_default();
-        let target = ev.target().unwrap().unchecked_into::<HtmlImageElement>();
-        image.set(target.src());
+        image.set(full_url.clone());
     };
 
     view! {
@@ -120,20 +124,50 @@ where
                             "max-w-lg px-3 py-2 rounded-md shadow-sm bg-primary text-primary-on whitespace-pre-wrap"
                         }
                     };
-                    view! { <div class=ctext>{content}</div> }
+                    view! { <div class=ctext>{content}</div> }.into_view()
                 }
                 MessageKind::Image => {
-                    view! {
-                        <div class="max-w-72 rounded-md overflow-hidden bg-accent cursor-zoom-in">
-                            <img
-                                src=url
-                                alt=content
-                                on:load=on_load
-                                on:error=on_error
-                                on:click=on_click_view
-                                class="object-cover object-center"
-                            />
-                        </div>
+                    match meta {
+                        Some(ImageMeta { thumb, width, height, color }) => {
+                            // reserve the layout space and show the dominant color until loaded
+                            let color = if is_hex_color(&color) { color } else { String::new() };
+                            let style = format!(
+                                "width: min(18rem, {}px); aspect-ratio: {} / {}; background-color: {}",
+                                width.max(1),
+                                width.max(1),
+                                height.max(1),
+                                color,
+                            );
+                            view! {
+                                <div class="max-w-72 rounded-md overflow-hidden bg-accent cursor-zoom-in" style=style>
+                                    <img
+                                        src=thumb
+                                        alt=content
+                                        loading="lazy"
+                                        on:load=on_load
+                                        on:error=on_error
+                                        on:click=on_click_view
+                                        class="size-full object-cover object-center"
+                                    />
+                                </div>
+                            }
+                                .into_view()
+                        }
+                        None => {
+                            view! {
+                                <div class="max-w-72 rounded-md overflow-hidden bg-accent cursor-zoom-in">
+                                    <img
+                                        src=url
+                                        alt=content
+                                        on:load=on_load
+                                        on:error=on_error
+                                        on:click=on_click_view
+                                        class="object-cover object-center"
+                                    />
+                                </div>
+                            }
+                                .into_view()
+                        }
                     }
                 }
                 MessageKind::File => {
@@ -145,9 +179,197 @@ where
                             </a>
                         </div>
                     }
+                        .into_view()
                 }
+                MessageKind::Voice => {
+                    let incoming = user.with_untracked(|v| v.id != sender.id);
+                    view! { <VoicePlayer url duration incoming /> }.into_view()
+                }
+                MessageKind::Media => view! { <MediaPlayer url name=content /> }.into_view(),
+                MessageKind::Album => view! { <AlbumView files image /> }.into_view(),
             }}
 
         </li>
     }
 }
+
+/// Check the placeholder color since messages come from other clients
+///
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// ==================== // AlbumView // ==================== //
+
+/// Show the images of an album in a grid followed by the other files
+///
+#[component]
+fn AlbumView(files: Vec<FileMeta>, image: RwSignal<String>) -> impl IntoView {
+    let (images, others): (Vec<FileMeta>, Vec<FileMeta>) = files.into_iter().partition(|v| v.img);
+    let cols = if images.len() == 1 {
+        "grid-cols-1"
+    } else {
+        "grid-cols-2"
+    };
+
+    let images = images
+        .into_iter()
+        .map(|file| {
+            let src = file
+                .image
+                .map(|v| v.thumb)
+                .unwrap_or_else(|| file.url.clone());
+            let full_url = file.url;
+            view! {
+                <img
+                    src=src
+                    alt=file.name
+                    loading="lazy"
+                    on:click=move |_| image.set(full_url.clone())
+                    class="aspect-square w-full object-cover object-center bg-accent cursor-zoom-in"
+                />
+            }
+        })
+        .collect_view();
+
+    let others = others
+        .into_iter()
+        .map(|file| {
+            if file.media {
+                view! { <MediaPlayer url=file.url name=file.name /> }.into_view()
+            } else {
+                view! {
+                    <div class="w-72 rounded-md bg-accent px-4 py-3">
+                        <a href=file.url download=file.name.clone() class="group space-x-2">
+                            <span class="text-sm text-accent-on group-hover:text-success">{file.name}</span>
+                            <FileDownload class="inline-block size-6 fill-accent-on group-hover:fill-success" />
+                        </a>
+                    </div>
+                }
+                    .into_view()
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="flex flex-col gap-2">
+            <div class=format!("w-72 grid {} gap-1 rounded-md overflow-hidden", cols)>{images}</div>
+            {others}
+        </div>
+    }
+}
+
+// ==================== // MediaPlayer // ==================== //
+
+/// Play a shared audio or video inline, the player seeks with range requests
+///
+#[component]
+fn MediaPlayer(url: String, name: String) -> impl IntoView {
+    let video = content_type(&url).starts_with("video/");
+    let player = if video {
+        view! {
+            <video
+                src=url.clone()
+                controls=true
+                preload="metadata"
+                class="w-full max-h-96 bg-black"
+            ></video>
+        }
+        .into_view()
+    } else {
+        view! { <audio src=url.clone() controls=true preload="metadata" class="w-full"></audio> }
+            .into_view()
+    };
+
+    view! {
+        <div class="w-72 max-w-full rounded-md overflow-hidden bg-accent">
+            {player}
+            <a href=url download=name.clone() class="group px-3 py-2 flex items-center gap-2">
+                <span class="grow truncate text-sm text-accent-on group-hover:text-success">
+                    {name}
+                </span>
+                <FileDownload class="shrink-0 size-5 fill-accent-on group-hover:fill-success" />
+            </a>
+        </div>
+    }
+}
+
+// ==================== // VoicePlayer // ==================== //
+
+#[component]
+fn VoicePlayer(url: String, duration: u32, incoming: bool) -> impl IntoView {
+    let audio_ref = create_node_ref::<html::Audio>();
+    let playing = create_rw_signal(false);
+    let current = create_rw_signal(0.0_f64);
+
+    let total = duration.max(1) as f64;
+    let percent = move || format!("width: {:.1}%", (current.get() / total * 100.0).min(100.0));
+
+    let label = move || {
+        let secs = if playing.get() || current.get() > 0.0 {
+            current.get() as u32
+        } else {
+            duration
+        };
+        format!("{}:{:02}", secs / 60, secs % 60)
+    };
+
+    let on_toggle = move |_| {
+        if let Some(audio) = audio_ref.get_untracked() {
+            if audio.paused() {
+                let _ = audio.play();
+            } else {
+                let _ = audio.pause();
+            }
+        }
+    };
+
+    let on_seek = move |ev: MouseEvent| {
+        let target = ev
+            .current_target()
+            .unwrap()
+            .unchecked_into::<web_sys::HtmlElement>();
+        let width = target.client_width() as f64;
+        if let Some(audio) = audio_ref.get_untracked() {
+            if width > 0.0 {
+                audio.set_current_time(ev.offset_x() as f64 / width * total);
+            }
+        }
+    };
+
+    let on_timeupdate = move |_| {
+        if let Some(audio) = audio_ref.get_untracked() {
+            current.set(audio.current_time());
+        }
+    };
+
+    let cbubble = if incoming {
+        "w-64 px-3 py-2 flex items-center gap-3 rounded-md shadow-sm bg-accent text-accent-on"
+    } else {
+        "w-64 px-3 py-2 flex items-center gap-3 rounded-md shadow-sm bg-primary text-primary-on"
+    };
+
+    view! {
+        <div class=cbubble>
+            <audio
+                src=url
+                preload="metadata"
+                node_ref=audio_ref
+                on:play=move |_| playing.set(true)
+                on:pause=move |_| playing.set(false)
+                on:ended=move |_| current.set(0.0)
+                on:timeupdate=on_timeupdate
+            ></audio>
+            <button type="button" on:click=on_toggle class="shrink-0">
+                <Show when=move || playing.get() fallback=|| view! { <PlaySolid class="size-5" /> }>
+                    <PauseSolid class="size-5" />
+                </Show>
+            </button>
+            <div on:click=on_seek class="relative grow h-1.5 rounded-full cursor-pointer overflow-hidden">
+                <div class="absolute inset-0 bg-current opacity-25 pointer-events-none"></div>
+                <div class="relative h-full rounded-full bg-current pointer-events-none" style=percent></div>
+            </div>
+            <span class="shrink-0 text-xs tabular-nums">{label}</span>
+        </div>
+    }
+}
@@ -9,6 +9,8 @@ mod emoji;
 mod messages;
 mod room;
 mod rooms;
+mod upload;
+mod voice;
 
 #[component]
 pub fn ChatsPage() -> impl IntoView {
@@ -1,16 +1,20 @@
 use leptos::*;
 use server_fn::codec::{MultipartData, MultipartFormData};
-use wasm_bindgen::JsCast;
-use web_sys::{File, FormData, HtmlFormElement};
+use uuid::Uuid;
+use web_sys::{FileList, FormData};
 
-use super::{emoji::EmojiButton, messages::Messages};
+#[cfg(feature = "hydrate")]
+use super::upload::upload_all;
+use super::upload::PendingFile;
+use super::{emoji::EmojiButton, messages::Messages, voice::VoiceButton};
 use crate::components::icons::{
-    AirPlane, CallPhone, ChatBubble, DeleteTrash, FileUpload, PlusCircle, UploadArrow,
+    AirPlane, CallPhone, ChatBubble, CloseXmark, DeleteTrash, FileUpload, PlusCircle, RefreshArrow,
+    UploadArrow, UserGroup,
 };
 use crate::components::Toast;
-use crate::connection::{RtcStatus, WebRtcState, WebSocketState};
+use crate::connection::{RoomRtcState, RtcStatus, WebRtcState, WebSocketState};
 use crate::home::{ChatsState, UserState};
-use common::{Error, Event, FileInfo, FileMeta, FnError, Message};
+use common::{Error, Event, FileInfo, FileMeta, FnError, Message, MAX_ALBUM_FILES};
 
 #[component]
 pub fn RoomPage() -> impl IntoView {
@@ -21,6 +25,8 @@ pub fn RoomPage() -> impl IntoView {
     let ws = expect_context::<WebSocketState>();
     let rtc = expect_context::<WebRtcState>();
     let status = rtc.status();
+    let roomrtc = expect_context::<RoomRtcState>();
+    let call_room_id = roomrtc.room_id();
 
     let room_pos = create_memo(move |_| {
         with!(|room_id, rooms| { rooms.iter().position(|v| v.id.as_str() == room_id) })
@@ -59,6 +65,23 @@ pub fn RoomPage() -> impl IntoView {
         }
     };
 
+    // handle the voice call of the room
+    let num_members = move || {
+        with!(|room_id| roomrtc
+            .calls()
+            .with(|calls| calls.get(room_id).map(Vec::len).unwrap_or(0)))
+    };
+    let in_room_call =
+        move || with!(|room_id, call_room_id| call_room_id.as_ref() == Some(room_id));
+
+    let on_click_room_call = move |_| {
+        if in_room_call() {
+            roomrtc.leave(ws);
+        } else {
+            roomrtc.join(room_id.get_untracked(), ws);
+        }
+    };
+
     view! {
         <Show
             when=move || room_name.with(Option::is_some)
@@ -74,13 +97,32 @@ pub fn RoomPage() -> impl IntoView {
             <div class="h-full w-full flex flex-col">
                 <div class="shrink-0 px-6 py-4 border-b border-border flex items-center justify-between">
                     <p class="font-medium text-lg">{move || room_name.get().unwrap_or(String::new())}</p>
-                    <button
-                        on:click=on_click
-                        disabled=move || status.get() != RtcStatus::Idle
-                        class="text-muted hover:text-primary disabled:text-muted"
-                    >
-                        <CallPhone class="size-5" />
-                    </button>
+                    <div class="flex items-center gap-4">
+                        <button
+                            on:click=on_click_room_call
+                            disabled=move || {
+                                status.get() != RtcStatus::Idle
+                                    || (call_room_id.with(Option::is_some) && !in_room_call())
+                            }
+                            class="relative text-muted hover:text-primary disabled:text-muted"
+                            class=("text-success", in_room_call)
+                        >
+                            <UserGroup class="size-5" />
+                            <span
+                                class="absolute -top-2 -right-3 h-4 rounded-xl bg-success text-success-on px-1.5 text-xs leading-tight"
+                                class=("hidden", move || num_members() == 0)
+                            >
+                                {num_members}
+                            </span>
+                        </button>
+                        <button
+                            on:click=on_click
+                            disabled=move || status.get() != RtcStatus::Idle || call_room_id.with(Option::is_some)
+                            class="text-muted hover:text-primary disabled:text-muted"
+                        >
+                            <CallPhone class="size-5" />
+                        </button>
+                    </div>
                 </div>
                 <Messages />
                 <ChatBar />
@@ -96,19 +138,34 @@ pub async fn upload_file(data: MultipartData) -> Result<FileMeta, ServerFnError<
     use common::{AuthExtractor, ConfigExtractor, FileManager, StoreExtractor};
 
     let store = StoreExtractor::use_store()?;
-    let _ = AuthExtractor::use_auth(false, &store).await?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
 
     // `.into_inner()` returns the inner `multer` stream, it is `None` if we call this on the client,
     // but always `Some(_)` on the server, so is safe to unwrap
     let mut data = data.into_inner().unwrap();
 
-    if let Ok(Some(field)) = data.next_field().await {
+    // a voice message sends its duration before the audio file
+    let mut duration = None;
+    while let Ok(Some(field)) = data.next_field().await {
+        if field.name() == Some("duration") {
+            let text = field.text().await.unwrap_or_default();
+            let secs = text
+                .parse::<u32>()
+                .map_err(|_| Error::BadRequest(String::from("Invalid voice duration")))?;
+            duration = Some(secs);
+            continue;
+        }
+
         let config = ConfigExtractor::use_config()?;
-        let rsp = FileManager::save_shared_file(field.into(), config).await?;
-        Ok(rsp)
-    } else {
-        Err(Error::BadRequest(String::from("No field found in multipart data")).into())
+        let rsp = match duration {
+            Some(secs) => {
+                FileManager::save_voice(user.id, field.into(), secs, &store, config).await?
+            }
+            None => FileManager::save_shared_file(user.id, field.into(), &store, config).await?,
+        };
+        return Ok(rsp);
     }
+    Err(Error::BadRequest(String::from("No field found in multipart data")).into())
 }
 
 #[component]
@@ -149,27 +206,84 @@ fn ChatBar() -> impl IntoView {
         }
     };
 
-    // handle file info
+    // handle the files in the tray
     let input_ref = create_node_ref::<html::Input>();
-    let has_file = create_rw_signal(false);
-    let file_info = create_rw_signal(FileInfo::default());
+    let files = create_rw_signal(Vec::<PendingFile>::new());
+    let has_file = create_memo(move |_| files.with(|v| !v.is_empty()));
 
-    let add_file = move |file: File| {
-        file_info.set(FileInfo::new(file.name(), file.size()));
-        has_file.set(true);
+    // the upload is paused after a failure and resumed by submitting again
+    let uploading = create_rw_signal(false);
+    let paused = create_rw_signal(false);
+
+    // overall progress in percent weighted by the file sizes
+    let progress = move || {
+        files.with(|v| {
+            let total: f64 = v.iter().map(|f| f.file.size()).sum();
+            let done: f64 = v.iter().map(|f| f.progress.get() * f.file.size()).sum();
+            if total > 0.0 {
+                done / total
+            } else {
+                0.0
+            }
+        })
     };
-    let del_file = move || {
-        file_info.set(FileInfo::default());
-        has_file.set(false);
+    let summary = move || {
+        files.with(|v| {
+            let size: f64 = v.iter().map(|f| f.file.size()).sum();
+            let info = FileInfo::new(String::new(), size);
+            match v.len() {
+                1 => format!("1 file, {}", info.size),
+                n => format!("{} files, {}", n, info.size),
+            }
+        })
+    };
+
+    let add_files = move |list: FileList| {
+        let mut skipped = false;
+        files.update(|v| {
+            for i in 0..list.length() {
+                let Some(file) = list.item(i) else {
+                    continue;
+                };
+                // a dropped folder shows up as an empty file
+                if file.size() == 0.0 {
+                    skipped = true;
+                    continue;
+                }
+                if v.len() >= MAX_ALBUM_FILES {
+                    toast.error(format!(
+                        "Up to {} files can be sent at once",
+                        MAX_ALBUM_FILES
+                    ));
+                    break;
+                }
+                v.push(PendingFile::new(file));
+            }
+        });
+        if skipped {
+            toast.error(String::from("Folders and empty files are skipped"));
+        }
+        paused.set(false);
+    };
+    let del_file = move |key: Uuid| {
+        files.update(|v| {
+            if let Some(pos) = v.iter().position(|f| f.key == key) {
+                v.remove(pos).release();
+            }
+        });
+    };
+    let clear_files = move || {
+        files.update(|v| v.drain(..).for_each(|f| f.release()));
+        paused.set(false);
     };
 
     let on_change = move |_| {
         if let Some(input_ref) = input_ref.get_untracked() {
-            if let Some(files) = input_ref.files() {
-                if let Some(file) = files.item(0) {
-                    add_file(file);
-                }
+            if let Some(list) = input_ref.files() {
+                add_files(list);
             }
+            // the same file can be selected again after removing it
+            input_ref.set_value("");
         }
     };
 
@@ -178,16 +292,9 @@ fn ChatBar() -> impl IntoView {
 
     let on_drop = move |ev: ev::DragEvent| {
         ev.prevent_default();
-        if let Some(data) = ev.data_transfer() {
-            if let Some(ref files) = data.files() {
-                if let Some(input_ref) = input_ref.get_untracked() {
-                    input_ref.set_files(Some(files));
-                    if let Some(file) = files.item(0) {
-                        add_file(file);
-                        has_drag.set(false);
-                    }
-                }
-            }
+        has_drag.set(false);
+        if let Some(list) = ev.data_transfer().and_then(|data| data.files()) {
+            add_files(list);
         }
     };
     let on_dragover = move |ev: ev::DragEvent| {
@@ -209,16 +316,54 @@ fn ChatBar() -> impl IntoView {
     });
     let pending = action.pending();
 
+    // upload the files in chunks and send them as an album once all are uploaded
     let on_submit = move |ev: ev::SubmitEvent| {
         ev.prevent_default();
-        let target = ev.target().unwrap().unchecked_into::<HtmlFormElement>();
-        let form_data = FormData::new_with_form(&target).unwrap();
-        action.dispatch(form_data);
-        target.reset();
-        del_file();
+        if uploading.get_untracked() || !has_file.get_untracked() {
+            return;
+        }
+        uploading.set(true);
+        paused.set(false);
+
+        #[cfg(feature = "hydrate")]
+        spawn_local(async move {
+            let pending_files = files.get_untracked();
+            match upload_all(&pending_files).await {
+                Ok(()) => {
+                    let room_id = chats.room_id().get_untracked();
+                    let mut metas: Vec<FileMeta> = pending_files
+                        .iter()
+                        .filter_map(|f| f.meta.get_untracked())
+                        .collect();
+
+                    let msg = if metas.len() == 1 {
+                        Message::file(room_id, user.get_untracked(), metas.remove(0))
+                    } else {
+                        Message::album(room_id, user.get_untracked(), metas)
+                    };
+                    ws.send(Event::Send(msg));
+                    clear_files();
+                }
+                Err(FnError::WrappedServerError(e)) => {
+                    toast.error(e.to_string());
+                    paused.set(true);
+                }
+                Err(_) => {
+                    toast.error(String::from("Upload interrupted, submit again to resume"));
+                    paused.set(true);
+                }
+            }
+            uploading.set(false);
+        });
     };
 
-    let on_reset = move |_| del_file();
+    let on_reset = move |ev: ev::Event| {
+        ev.prevent_default();
+        if !uploading.get_untracked() {
+            clear_files();
+        }
+    };
+    let on_record = move |form_data: FormData| action.dispatch(form_data);
 
     // automatically send ws message when uploading successfully
     create_effect(move |_| {
@@ -237,6 +382,18 @@ fn ChatBar() -> impl IntoView {
     });
 
     view! {
+        <Show when=move || has_file.get()>
+            <ul class="shrink-0 px-3 pt-2 flex gap-2 overflow-x-auto scrollbar">
+                <For
+                    each=move || files.get()
+                    key=|pending| pending.key
+                    children=move |pending| {
+                        let key = pending.key;
+                        view! { <PendingItem pending disabled=uploading on_remove=move || del_file(key) /> }
+                    }
+                />
+            </ul>
+        </Show>
         <div
             on:drop=on_drop
             on:dragover=on_dragover
@@ -258,29 +415,46 @@ fn ChatBar() -> impl IntoView {
                     id="upload"
                     type="file"
                     name="file_to_upload"
+                    multiple=true
                     on:change=on_change
                     node_ref=input_ref
                     class="hidden"
                 />
                 <FileUpload class="fill-danger size-5" />
-                <p class="mx-2 max-w-md text-accent-on truncate">{move || file_info.with(|fl| fl.name.to_owned())}</p>
-                <p class="grow shrink-0 text-muted">{move || file_info.with(|fl| fl.size.to_owned())}</p>
+                <p class="mx-2 shrink-0 text-accent-on">{summary}</p>
+                <div class="grow mx-4 flex items-center gap-2">
+                    <Show when=move || uploading.get() || paused.get()>
+                        <div class="grow h-1.5 rounded-full bg-border overflow-hidden">
+                            <div
+                                class="h-full bg-primary"
+                                class=("bg-danger", paused)
+                                style=move || format!("width: {:.1}%", progress())
+                            ></div>
+                        </div>
+                        <span class="shrink-0 text-xs text-muted tabular-nums">
+                            {move || format!("{:.0}%", progress())}
+                        </span>
+                    </Show>
+                </div>
                 <button
                     type="reset"
-                    disabled=pending
-                    class="mx-4 shrink-0 text-muted hover:text-primary pointer-events-auto"
+                    disabled=move || pending.get() || uploading.get()
+                    class="mr-4 shrink-0 text-muted hover:text-primary pointer-events-auto"
                 >
                     <DeleteTrash class="size-5" />
                 </button>
                 <button
                     type="submit"
-                    disabled=pending
+                    disabled=move || pending.get() || uploading.get()
+                    title=move || if paused.get() { "Resume upload" } else { "Upload" }
                     class="shrink-0 text-muted hover:text-primary pointer-events-auto"
                 >
-                    <UploadArrow class="size-5" />
+                    <Show when=paused fallback=|| view! { <UploadArrow class="size-5" /> }>
+                        <RefreshArrow class="size-5" />
+                    </Show>
                 </button>
             </form>
-            <div class="grow flex items-center" class:hidden=has_file>
+            <div class="grow flex items-center" class:hidden=move || has_file.get()>
                 <div class="grow relative">
                     <textarea
                         rows="1"
@@ -297,6 +471,9 @@ fn ChatBar() -> impl IntoView {
                         <EmojiButton content />
                     </div>
                 </div>
+                <div class="ml-3">
+                    <VoiceButton on_record />
+                </div>
                 <button type="button" on:click=on_click_send class="rounded-full mx-3 p-1 flex">
                     <AirPlane class="size-6 -rotate-45 -translate-y-0.5 fill-primary hover:fill-success" />
                 </button>
@@ -304,3 +481,61 @@ fn ChatBar() -> impl IntoView {
         </div>
     }
 }
+
+// ==================== // PendingItem // ==================== //
+
+/// A file in the tray with its preview and upload progress
+///
+#[component]
+fn PendingItem<F>(pending: PendingFile, disabled: RwSignal<bool>, on_remove: F) -> impl IntoView
+where
+    F: Fn() + 'static,
+{
+    let PendingFile {
+        info,
+        preview,
+        progress,
+        meta,
+        ..
+    } = pending;
+    let uploaded = move || meta.with(Option::is_some);
+
+    view! {
+        <li class="relative shrink-0 w-28 rounded-md bg-accent border border-border overflow-hidden">
+            {match preview {
+                Some(src) => {
+                    view! { <img src=src alt=info.name.clone() class="h-16 w-full object-cover object-center" /> }
+                        .into_view()
+                }
+                None => {
+                    view! {
+                        <div class="h-16 flex items-center justify-center">
+                            <FileUpload class="fill-muted size-8" />
+                        </div>
+                    }
+                        .into_view()
+                }
+            }}
+            <div class="px-2 py-1">
+                <p class="text-xs text-accent-on truncate" title=info.name.clone()>{info.name.clone()}</p>
+                <p class="text-xs text-muted">{info.size}</p>
+            </div>
+            <div class="h-1 bg-border">
+                <div
+                    class="h-full bg-primary"
+                    class=("bg-success", uploaded)
+                    style=move || format!("width: {:.1}%", progress.get())
+                ></div>
+            </div>
+            <button
+                type="button"
+                title="Remove"
+                disabled=move || disabled.get()
+                on:click=move |_| on_remove()
+                class="absolute top-1 right-1 rounded-full p-0.5 bg-container/80 text-muted hover:text-danger"
+            >
+                <CloseXmark class="size-4" />
+            </button>
+        </li>
+    }
+}
@@ -0,0 +1,236 @@
+use leptos::*;
+use server_fn::codec::{MultipartData, MultipartFormData};
+use uuid::Uuid;
+use web_sys::{File, Url};
+
+use common::{Error, FileInfo, FileMeta, InitUploadArg, UploadSession};
+
+cfg_if::cfg_if! { if #[cfg(feature = "hydrate")] {
+    use futures_util::{stream, StreamExt};
+    use sha2::{Digest, Sha256};
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::js_sys::{Promise, Uint8Array};
+    use web_sys::{Blob, FormData};
+    use common::{FnError, UPLOAD_CHUNK_SIZE};
+}}
+
+/// Times to retry a chunk before the upload is paused
+#[cfg(feature = "hydrate")]
+const CHUNK_RETRIES: u32 = 3;
+
+/// Files uploaded at the same time
+#[cfg(feature = "hydrate")]
+const MAX_CONCURRENT_UPLOADS: usize = 3;
+
+// ==================== // PendingFile // ==================== //
+
+/// A selected file waiting in the tray to be sent
+///
+#[derive(Clone)]
+pub struct PendingFile {
+    pub key: Uuid,
+    pub file: File,
+    pub info: FileInfo,
+    /// Object url to preview an image, revoked when the file leaves the tray
+    pub preview: Option<String>,
+    pub progress: RwSignal<f64>,
+    /// Set once the file is uploaded, so resuming skips it
+    pub meta: RwSignal<Option<FileMeta>>,
+}
+
+impl PendingFile {
+    pub fn new(file: File) -> Self {
+        let preview = if file.type_().starts_with("image/") {
+            Url::create_object_url_with_blob(&file).ok()
+        } else {
+            None
+        };
+
+        Self {
+            key: Uuid::new_v4(),
+            info: FileInfo::new(file.name(), file.size()),
+            file,
+            preview,
+            progress: create_rw_signal(0.0),
+            meta: create_rw_signal(None),
+        }
+    }
+
+    /// Release the preview of the file
+    ///
+    pub fn release(&self) {
+        if let Some(url) = &self.preview {
+            let _ = Url::revoke_object_url(url);
+        }
+    }
+}
+
+/// Upload the files not uploaded yet with limited concurrency, returns
+/// the first error after all uploads settled
+///
+#[cfg(feature = "hydrate")]
+pub async fn upload_all(files: &[PendingFile]) -> Result<(), FnError> {
+    let results: Vec<Result<(), FnError>> = stream::iter(files)
+        .filter(|pending| std::future::ready(pending.meta.get_untracked().is_none()))
+        .map(|pending| async move {
+            let file_meta = upload_in_chunks(&pending.file, pending.progress).await?;
+            pending.meta.set(Some(file_meta));
+            Ok(())
+        })
+        .buffer_unordered(MAX_CONCURRENT_UPLOADS)
+        .collect()
+        .await;
+
+    results.into_iter().collect()
+}
+
+#[server]
+pub async fn init_upload(arg: InitUploadArg) -> Result<UploadSession, ServerFnError<Error>> {
+    use common::{ArgsValidator, AuthExtractor, ConfigExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let arg = ArgsValidator::validate(arg)?;
+    let config = ConfigExtractor::use_config()?;
+    let session = arg.init(user.id, &store, config).await?;
+    Ok(session)
+}
+
+#[server(input = MultipartFormData)]
+pub async fn upload_chunk(data: MultipartData) -> Result<i64, ServerFnError<Error>> {
+    use axum::body::Bytes;
+    use common::{AuthExtractor, StoreExtractor, UPLOAD_CHUNK_SIZE};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    // `.into_inner()` returns the inner `multer` stream, it is `None` if we call this on the client,
+    // but always `Some(_)` on the server, so is safe to unwrap
+    let mut data = data.into_inner().unwrap();
+
+    // the id and offset are sent before the chunk
+    let (mut id, mut offset) = (String::new(), None);
+    while let Ok(Some(mut field)) = data.next_field().await {
+        match field.name().unwrap_or_default().to_owned().as_str() {
+            "id" => id = field.text().await.unwrap_or_default(),
+            "offset" => offset = field.text().await.ok().and_then(|v| v.parse::<i64>().ok()),
+            "chunk" => {
+                let offset =
+                    offset.ok_or(Error::BadRequest(String::from("Invalid chunk offset")))?;
+
+                let mut chunk = Vec::new();
+                while let Some(bytes) = field
+                    .chunk()
+                    .await
+                    .map_err(|_| Error::BadRequest(String::from("Failed to receive the chunk")))?
+                {
+                    if (chunk.len() + bytes.len()) as u64 > UPLOAD_CHUNK_SIZE {
+                        return Err(Error::BadRequest(String::from("Chunk is too large")).into());
+                    }
+                    chunk.extend_from_slice(&bytes);
+                }
+
+                let received =
+                    UploadSession::write_chunk(user.id, &id, offset, Bytes::from(chunk), &store)
+                        .await?;
+                return Ok(received);
+            }
+            _ => {}
+        }
+    }
+    Err(Error::BadRequest(String::from("No chunk found in multipart data")).into())
+}
+
+#[server]
+pub async fn finish_upload(id: String, checksum: String) -> Result<FileMeta, ServerFnError<Error>> {
+    use common::{AuthExtractor, ConfigExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let config = ConfigExtractor::use_config()?;
+    let file_meta = UploadSession::finish(user.id, &id, &checksum, &store, config).await?;
+    Ok(file_meta)
+}
+
+/// Upload the file in chunks and report the progress in percent. The chunks
+/// received by the server are skipped, so calling it again resumes the upload
+///
+#[cfg(feature = "hydrate")]
+pub async fn upload_in_chunks(file: &File, progress: RwSignal<f64>) -> Result<FileMeta, FnError> {
+    let size = file.size() as i64;
+    let arg = InitUploadArg {
+        key: format!("{}:{}:{}", file.name(), size, file.last_modified()),
+        name: file.name(),
+        mime: file.type_(),
+        size,
+    };
+    let session = init_upload(arg).await?;
+
+    let mut hasher = Sha256::new();
+    let mut offset = 0_i64;
+    while offset < size {
+        let end = (offset + UPLOAD_CHUNK_SIZE as i64).min(size);
+        let blob = file
+            .slice_with_f64_and_f64(offset as f64, end as f64)
+            .map_err(js_error)?;
+
+        // every chunk is hashed but only the missing ones are sent
+        let buffer = JsFuture::from(blob.array_buffer())
+            .await
+            .map_err(js_error)?;
+        hasher.update(Uint8Array::new(&buffer).to_vec());
+
+        if end > session.received {
+            send_chunk(&session.id, offset, &blob).await?;
+        }
+        offset = end;
+        progress.set(offset as f64 / size as f64 * 100.0);
+    }
+
+    let checksum = format!("{:x}", hasher.finalize());
+    finish_upload(session.id, checksum).await
+}
+
+/// Send a chunk and retry with a backoff on network errors
+///
+#[cfg(feature = "hydrate")]
+async fn send_chunk(id: &str, offset: i64, blob: &Blob) -> Result<(), FnError> {
+    let mut attempt = 0;
+    loop {
+        let form_data = FormData::new().map_err(js_error)?;
+        form_data.append_with_str("id", id).map_err(js_error)?;
+        form_data
+            .append_with_str("offset", &offset.to_string())
+            .map_err(js_error)?;
+        form_data
+            .append_with_blob_and_filename("chunk", blob, "chunk")
+            .map_err(js_error)?;
+
+        match upload_chunk(form_data.into()).await {
+            Ok(_) => return Ok(()),
+            // rejected by the server, retrying does not help
+            Err(err @ FnError::WrappedServerError(_)) => return Err(err),
+            Err(err) if attempt >= CHUNK_RETRIES => return Err(err),
+            Err(_) => {
+                attempt += 1;
+                sleep(500 * 2_i32.pow(attempt)).await;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "hydrate")]
+async fn sleep(millis: i32) {
+    let promise = Promise::new(&mut |resolve, _| {
+        let _ = window().set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis);
+    });
+    let _ = JsFuture::from(promise).await;
+}
+
+#[cfg(feature = "hydrate")]
+fn js_error(err: JsValue) -> FnError {
+    FnError::Request(format!("{:?}", err))
+}
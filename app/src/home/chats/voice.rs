@@ -0,0 +1,231 @@
+use leptos::*;
+use web_sys::FormData;
+
+use crate::components::icons::MicOn;
+use crate::components::Toast;
+
+cfg_if::cfg_if! { if #[cfg(feature = "hydrate")] {
+    use std::time::Duration;
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::js_sys::{Array, Date};
+    use web_sys::{
+        Blob, BlobEvent, BlobPropertyBag, MediaRecorder, MediaStream, MediaStreamConstraints,
+        MediaStreamTrack,
+    };
+    use common::MAX_VOICE_DURATION;
+}}
+
+#[derive(Clone, Default)]
+struct Recorder {
+    #[cfg(feature = "hydrate")]
+    recorder: Option<MediaRecorder>,
+    #[cfg(feature = "hydrate")]
+    chunks: Vec<Blob>,
+    #[cfg(feature = "hydrate")]
+    interval: Option<IntervalHandle>,
+    #[cfg(feature = "hydrate")]
+    started_at: f64,
+    #[cfg(feature = "hydrate")]
+    duration: u32,
+    holding: bool,
+}
+
+/// Hold to record a voice message, the form data with the duration
+/// and the audio file is passed to `on_record` after releasing
+///
+#[component]
+pub fn VoiceButton(#[prop(into)] on_record: Callback<FormData>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let state = store_value(Recorder::default());
+    let elapsed = create_rw_signal(None::<u32>);
+
+    #[cfg(not(feature = "hydrate"))]
+    let _ = (toast, on_record);
+
+    let on_start = move |ev: ev::PointerEvent| {
+        ev.prevent_default();
+        state.update_value(|v| v.holding = true);
+
+        #[cfg(feature = "hydrate")]
+        spawn_local(async move {
+            if start_recording(state, elapsed, on_record).await.is_err() {
+                state.update_value(|v| v.holding = false);
+                toast.error(String::from("No audio input found"));
+            }
+        });
+    };
+
+    let on_stop = move |_| {
+        if !state.with_value(|v| v.holding) {
+            return;
+        }
+        state.update_value(|v| v.holding = false);
+
+        #[cfg(feature = "hydrate")]
+        if stop_recording(state, elapsed) == Some(0) {
+            toast.info(String::from("Hold the button to record a voice message"));
+        }
+    };
+
+    view! {
+        <div class="flex items-center">
+            <Show when=move || elapsed.with(Option::is_some)>
+                <span class="mr-1 text-sm text-danger tabular-nums">
+                    {move || {
+                        let secs = elapsed.get().unwrap_or_default();
+                        format!("{}:{:02}", secs / 60, secs % 60)
+                    }}
+                </span>
+            </Show>
+            <button
+                type="button"
+                title="Hold to record"
+                on:pointerdown=on_start
+                on:pointerup=on_stop
+                on:pointerleave=on_stop
+                class="rounded-full p-1 flex touch-none text-muted hover:text-surface-on"
+                class=("text-danger", move || elapsed.with(Option::is_some))
+            >
+                <MicOn class="size-6" />
+            </button>
+        </div>
+    }
+}
+
+/// Open the microphone and start a media recorder
+///
+#[cfg(feature = "hydrate")]
+async fn start_recording(
+    state: StoredValue<Recorder>,
+    elapsed: RwSignal<Option<u32>>,
+    on_record: Callback<FormData>,
+) -> Result<(), JsValue> {
+    let stream = get_audio_stream().await?;
+
+    // the button may have been released while waiting for the microphone
+    if !state.with_value(|v| v.holding) {
+        stop_tracks(&stream);
+        return Ok(());
+    }
+
+    let recorder = MediaRecorder::new_with_media_stream(&stream)?;
+
+    let ondataavailable_callback = Closure::<dyn FnMut(_)>::new(move |ev: BlobEvent| {
+        if let Some(blob) = ev.data() {
+            state.update_value(|v| v.chunks.push(blob));
+        }
+    });
+    recorder.set_ondataavailable(Some(ondataavailable_callback.as_ref().unchecked_ref()));
+    ondataavailable_callback.forget();
+
+    let rec = recorder.clone();
+    let onstop_callback = Closure::<dyn FnMut()>::new(move || {
+        stop_tracks(&stream);
+
+        let (chunks, duration) = state
+            .try_update_value(|v| (std::mem::take(&mut v.chunks), v.duration))
+            .unwrap_or_default();
+        if duration == 0 || chunks.is_empty() {
+            return;
+        }
+
+        if let Ok(form_data) = create_form_data(&rec.mime_type(), chunks, duration) {
+            on_record.call(form_data);
+        }
+    });
+    recorder.set_onstop(Some(onstop_callback.as_ref().unchecked_ref()));
+    onstop_callback.forget();
+
+    recorder.start()?;
+
+    // show the elapsed time and stop at the max duration
+    elapsed.set(Some(0));
+    let started_at = Date::now();
+    let interval = set_interval_with_handle(
+        move || {
+            let secs = ((Date::now() - started_at) / 1000.0) as u32;
+            elapsed.set(Some(secs));
+            if secs >= MAX_VOICE_DURATION {
+                state.update_value(|v| v.holding = false);
+                stop_recording(state, elapsed);
+            }
+        },
+        Duration::from_millis(200),
+    )
+    .ok();
+
+    state.update_value(|v| {
+        v.recorder = Some(recorder);
+        v.interval = interval;
+        v.started_at = started_at;
+    });
+    Ok(())
+}
+
+/// Stop the media recorder and returns the duration in seconds
+///
+#[cfg(feature = "hydrate")]
+fn stop_recording(state: StoredValue<Recorder>, elapsed: RwSignal<Option<u32>>) -> Option<u32> {
+    elapsed.set(None);
+
+    let (recorder, interval, started_at) =
+        state.try_update_value(|v| (v.recorder.take(), v.interval.take(), v.started_at))?;
+    if let Some(h) = interval {
+        h.clear();
+    }
+
+    let recorder = recorder?;
+    let secs = ((Date::now() - started_at) / 1000.0).round() as u32;
+    let duration = secs.min(MAX_VOICE_DURATION);
+
+    state.update_value(|v| v.duration = duration);
+    let _ = recorder.stop();
+    Some(duration)
+}
+
+/// Create the form data of a voice message, the duration goes before the file
+///
+#[cfg(feature = "hydrate")]
+fn create_form_data(mime: &str, chunks: Vec<Blob>, duration: u32) -> Result<FormData, JsValue> {
+    let parts = chunks.into_iter().collect::<Array>();
+
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = Blob::new_with_blob_sequence_and_options(&parts, &options)?;
+
+    let ext = if mime.starts_with("audio/ogg") {
+        "ogg"
+    } else if mime.starts_with("audio/mp4") {
+        "m4a"
+    } else {
+        "webm"
+    };
+
+    let form_data = FormData::new()?;
+    form_data.append_with_str("duration", &duration.to_string())?;
+    form_data.append_with_blob_and_filename("file_to_upload", &blob, &format!("voice.{}", ext))?;
+    Ok(form_data)
+}
+
+/// Get the local audio stream from the microphone
+///
+#[cfg(feature = "hydrate")]
+async fn get_audio_stream() -> Result<MediaStream, JsValue> {
+    let media_devices = window().navigator().media_devices()?;
+
+    let constraints = MediaStreamConstraints::new();
+    constraints.set_audio(&JsValue::TRUE);
+    constraints.set_video(&JsValue::FALSE);
+
+    let stream_promise = media_devices.get_user_media_with_constraints(&constraints)?;
+    let ms = JsFuture::from(stream_promise).await?;
+    Ok(MediaStream::from(ms))
+}
+
+#[cfg(feature = "hydrate")]
+fn stop_tracks(stream: &MediaStream) {
+    for track in stream.get_tracks() {
+        MediaStreamTrack::from(track).stop();
+    }
+}
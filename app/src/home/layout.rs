@@ -1,13 +1,13 @@
 use leptos::*;
-use leptos_router::Outlet;
+use leptos_router::{Outlet, Redirect};
 use server_fn::codec::GetUrl;
 
 use super::navbar::Navbar;
 use super::state::StateProvider;
 use crate::components::icons::SpinCircle;
 use crate::components::Logo;
-use crate::connection::CallSection;
-use crate::LOGIN_PATH;
+use crate::connection::{CallSection, RoomCallSection};
+use crate::{LOGIN_PATH, PASSWORD_PATH};
 use common::{Error, User};
 
 #[server(input = GetUrl)]
@@ -15,7 +15,7 @@ async fn get_auth_user() -> Result<User, ServerFnError<Error>> {
     use common::{AuthExtractor, StoreExtractor};
 
     let store = StoreExtractor::use_store()?;
-    AuthExtractor::use_auth(true, &store).await
+    AuthExtractor::use_login(true, &store).await
 }
 
 #[component]
@@ -33,9 +33,13 @@ pub fn HomeLayout() -> impl IntoView {
             }>
                 {move || {
                     if let Some(Ok(user)) = user_res.get() {
+                        if user.must_change_password {
+                            return view! { <Redirect path=PASSWORD_PATH /> }.into_view();
+                        }
                         view! {
                             <StateProvider user>
                                 <CallSection />
+                                <RoomCallSection />
                                 <Navbar />
                                 <Outlet />
                             </StateProvider>
@@ -3,17 +3,19 @@ use leptos_meta::Title;
 use leptos_router::{use_location, use_navigate, Outlet, Redirect, Route};
 
 use super::SETTINGS_PATH;
-use crate::components::icons::{LockClosed, SessionKey, UserOutline};
+use crate::components::icons::{InviteTicket, LockClosed, SessionKey, UserOutline};
 use crate::components::MenuListItem;
 use crate::home::DateTimeState;
 
 use profile::ProfilePage;
 use security::SecurityPage;
 use session::SessionPage;
+use tokens::TokensPage;
 
 mod profile;
 mod security;
 mod session;
+mod tokens;
 
 const PROFILE_NAME: &str = "/profile";
 const PROFILE_PATH: &str = "/settings/profile";
@@ -21,6 +23,8 @@ const SECURITY_NAME: &str = "/security";
 const SECURITY_PATH: &str = "/settings/security";
 const SESSION_NAME: &str = "/session";
 const SESSION_PATH: &str = "/settings/session";
+const TOKENS_NAME: &str = "/tokens";
+const TOKENS_PATH: &str = "/settings/tokens";
 
 #[component(transparent)]
 pub fn SettingsRoutes() -> impl IntoView {
@@ -30,6 +34,7 @@ pub fn SettingsRoutes() -> impl IntoView {
             <Route path=PROFILE_NAME view=ProfilePage />
             <Route path=SECURITY_NAME view=SecurityPage />
             <Route path=SESSION_NAME view=SessionPage />
+            <Route path=TOKENS_NAME view=TokensPage />
         </Route>
     }
 }
@@ -79,6 +84,13 @@ fn SideBar() -> impl IntoView {
                     <SessionKey class="size-5" />
                     <h3>"Session"</h3>
                 </MenuListItem>
+                <MenuListItem
+                    active=Signal::derive(move || pathname.with(|v| v.as_str() == TOKENS_PATH))
+                    on:click=move |_| navigator.call(TOKENS_PATH)
+                >
+                    <InviteTicket class="size-5" />
+                    <h3>"Access Tokens"</h3>
+                </MenuListItem>
             </ul>
         </div>
     }
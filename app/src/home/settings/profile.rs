@@ -5,7 +5,7 @@ use web_sys::{Event, FormData, SubmitEvent};
 
 use crate::components::{Avatar, Toast};
 use crate::home::UserState;
-use common::{Error, FnError, UpdateUserArg, User};
+use common::{Error, FnError, StorageUsage, UpdateUserArg, User};
 
 #[server(input = MultipartFormData)]
 pub async fn upload_avatar(data: MultipartData) -> Result<String, ServerFnError<Error>> {
@@ -20,13 +20,25 @@ pub async fn upload_avatar(data: MultipartData) -> Result<String, ServerFnError<
 
     if let Ok(Some(field)) = data.next_field().await {
         let config = ConfigExtractor::use_config()?;
-        let url = FileManager::save_avatar(user.id, field.into(), config).await?;
+        let url = FileManager::save_avatar(user.id, field.into(), &store, config).await?;
         Ok(url)
     } else {
         Err(Error::BadRequest(String::from("No field found in multipart data")).into())
     }
 }
 
+#[server]
+async fn get_storage_usage() -> Result<StorageUsage, ServerFnError<Error>> {
+    use common::{AuthExtractor, ConfigExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let config = ConfigExtractor::use_config()?;
+    let usage = StorageUsage::get(user.id, &store, &config).await?;
+    Ok(usage)
+}
+
 #[server]
 async fn update_profile(arg: UpdateUserArg) -> Result<User, ServerFnError<Error>> {
     use common::{ArgsValidator, AuthExtractor, ConfigExtractor, FileManager, StoreExtractor};
@@ -41,6 +53,7 @@ async fn update_profile(arg: UpdateUserArg) -> Result<User, ServerFnError<Error>
         || arg.password.is_some()
         || arg.role.is_some()
         || arg.active.is_some()
+        || arg.quota.is_some()
     {
         return Err(Error::Forbidden.into());
     }
@@ -48,7 +61,7 @@ async fn update_profile(arg: UpdateUserArg) -> Result<User, ServerFnError<Error>
     let rsp = arg.call(&store).await?;
     if arg.avatar.is_some() {
         let config = ConfigExtractor::use_config()?;
-        FileManager::clean_avatars(rsp.id, &rsp.avatar, config).await?;
+        FileManager::clean_avatars(rsp.id, &rsp.avatar, &store, config).await?;
     }
     Ok(rsp)
 }
@@ -63,6 +76,7 @@ pub fn ProfilePage() -> impl IntoView {
     let value = action.value();
 
     let avatar = create_rw_signal(user.with_untracked(|u| u.avatar.clone()));
+    let usage = create_resource(move || avatar.get(), move |_| get_storage_usage());
 
     let candidates = (0..10)
         .map(|idx| {
@@ -159,6 +173,28 @@ pub fn ProfilePage() -> impl IntoView {
                 </div>
             </div>
 
+            <div class="mt-12 mb-3 text-sm font-medium leading-none">"Storage"</div>
+            <Transition fallback=|| ()>
+                {move || {
+                    if let Some(Ok(usage)) = usage.get() {
+                        let width = format!("width: {:.1}%", usage.percent());
+                        view! {
+                            <div class="w-full h-2 rounded-full bg-accent overflow-hidden">
+                                <div
+                                    class="h-full bg-primary"
+                                    class=("bg-danger", usage.percent() >= 90.0)
+                                    style=width
+                                ></div>
+                            </div>
+                            <p class="mt-2 text-sm text-muted">{usage.label()}</p>
+                        }
+                            .into_view()
+                    } else {
+                        ().into_view()
+                    }
+                }}
+            </Transition>
+
             <ActionForm class="mt-12 grid gap-8" action on:submit=on_submit>
                 <input type="hidden" name="arg[id]" prop:value=move || user.with(|u| u.id) />
                 <input type="hidden" name="arg[avatar]" prop:value=avatar />
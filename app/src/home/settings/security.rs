@@ -4,11 +4,11 @@ use web_sys::SubmitEvent;
 
 use crate::components::Toast;
 use crate::home::UserState;
-use common::{Error, FnError, UpdatePasswordArg};
+use common::{DateTime, Error, FnError, Identity, TwoFactorSetup, TwoFactorStatus, UpdatePasswordArg};
 
 #[server]
 async fn update_password(arg: UpdatePasswordArg) -> Result<(), ServerFnError<Error>> {
-    use common::{ArgsValidator, AuthExtractor, StoreExtractor};
+    use common::{ArgsValidator, AuthExtractor, CookieManager, HubManager, Session, StoreExtractor};
 
     let arg = ArgsValidator::validate(arg)?;
 
@@ -21,6 +21,11 @@ async fn update_password(arg: UpdatePasswordArg) -> Result<(), ServerFnError<Err
 
     arg.call(&store).await?;
 
+    // other devices sign in again with the new password
+    let (_, session) = CookieManager::get_auth()?;
+    let others = Session::delete_others(auth_user.id, session, &store).await?;
+    HubManager::kick_sessions(auth_user.id, &others, "Your password has been changed")?;
+
     Ok(())
 }
 
@@ -114,6 +119,310 @@ pub fn SecurityPage() -> impl IntoView {
                     </button>
                 </div>
             </ActionForm>
+
+            <TwoFactorPanel />
+            <SingleSignOnPanel />
         </div>
     }
 }
+
+// ==================== // TwoFactorPanel // ==================== //
+
+#[server]
+async fn get_two_factor() -> Result<TwoFactorStatus, ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor, TwoFactor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let status = TwoFactor::status(user.id, &store).await?;
+    Ok(status)
+}
+
+#[server]
+async fn setup_two_factor() -> Result<TwoFactorSetup, ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor, TwoFactor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let setup = TwoFactor::setup(&user, &store).await?;
+    Ok(setup)
+}
+
+/// Returns the recovery codes
+///
+#[server]
+async fn enable_two_factor(code: String) -> Result<Vec<String>, ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor, TwoFactor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let codes = TwoFactor::enable(user.id, &code, &store).await?;
+    Ok(codes)
+}
+
+#[server]
+async fn disable_two_factor(password: String) -> Result<(), ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor, TwoFactor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    TwoFactor::disable(user.id, &password, &store).await?;
+    Ok(())
+}
+
+#[component]
+fn TwoFactorPanel() -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let refresh = create_rw_signal(0);
+    let setup = create_rw_signal(None::<TwoFactorSetup>);
+    let codes = create_rw_signal(Vec::<String>::new());
+
+    let status_resource = create_resource(move || refresh.get(), |_| get_two_factor());
+
+    let setup_action = create_server_action::<SetupTwoFactor>();
+    let enable_action = create_server_action::<EnableTwoFactor>();
+    let disable_action = create_server_action::<DisableTwoFactor>();
+
+    create_effect(move |_| {
+        setup_action.value().with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(v)) => setup.set(Some(v.clone())),
+            _ => {}
+        });
+    });
+    create_effect(move |_| {
+        enable_action.value().with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(v)) => {
+                toast.success(String::from("Two-factor authentication enabled"));
+                setup.set(None);
+                codes.set(v.clone());
+                refresh.update(|x| *x += 1);
+            }
+            _ => {}
+        });
+    });
+    create_effect(move |_| {
+        disable_action.value().with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => {
+                toast.success(String::from("Two-factor authentication disabled"));
+                codes.set(Vec::new());
+                refresh.update(|x| *x += 1);
+            }
+            _ => {}
+        });
+    });
+
+    let enabled_view = move |status: TwoFactorStatus| {
+        view! {
+            <p class="text-sm">
+                <span class="text-success font-medium">"Enabled"</span>
+                <span class="text-muted">
+                    {format!(" · {} recovery codes left", status.recovery_codes)}
+                </span>
+            </p>
+            <ActionForm class="mt-6 grid gap-4" action=disable_action>
+                <div class="grid gap-2">
+                    <label for="2fa-password" class="text-sm font-medium leading-none">
+                        "Password"
+                    </label>
+                    <input
+                        id="2fa-password"
+                        type="password"
+                        name="password"
+                        disabled=disable_action.pending()
+                        autocomplete="off"
+                        placeholder="Enter your password to disable"
+                        class="w-full h-11 px-3 input"
+                    />
+                </div>
+                <div class="flex items-center justify-end">
+                    <button type="submit" disabled=disable_action.pending() class="h-9 px-5 btn-outline">
+                        "Disable 2FA"
+                    </button>
+                </div>
+            </ActionForm>
+        }
+    };
+
+    let setup_view = move || {
+        match setup.get() {
+        Some(v) => view! {
+            <div class="flex gap-6 items-start">
+                <img src=v.qrcode alt="QR code" class="size-40 rounded-md border border-border" />
+                <div class="grow">
+                    <p class="text-sm text-muted">
+                        "Scan the QR code with your authenticator app, or enter the secret manually:"
+                    </p>
+                    <p class="my-2 font-mono text-sm break-all">{v.secret}</p>
+                    <ActionForm class="mt-4 flex gap-3" action=enable_action>
+                        <input
+                            type="text"
+                            name="code"
+                            inputmode="numeric"
+                            autocomplete="one-time-code"
+                            disabled=enable_action.pending()
+                            placeholder="6-digit code"
+                            class="w-40 h-9 px-3 input"
+                        />
+                        <button type="submit" disabled=enable_action.pending() class="h-9 px-5 btn-primary">
+                            "Confirm"
+                        </button>
+                    </ActionForm>
+                </div>
+            </div>
+        }
+        .into_view(),
+        None => view! {
+            <div class="flex items-center justify-between">
+                <p class="text-sm text-muted">"Two-factor authentication is disabled."</p>
+                <button
+                    type="button"
+                    disabled=setup_action.pending()
+                    on:click=move |_| setup_action.dispatch(SetupTwoFactor {})
+                    class="h-9 px-5 btn-primary"
+                >
+                    "Enable 2FA"
+                </button>
+            </div>
+        }
+        .into_view(),
+    }
+    };
+
+    let content = move || {
+        status_resource.get().map(|res| match res {
+            Ok(status) if status.enabled => enabled_view(status).into_view(),
+            Ok(_) => setup_view.into_view(),
+            Err(e) => view! { <p class="text-sm text-danger">{e.to_string()}</p> }.into_view(),
+        })
+    };
+
+    view! {
+        <h3 class="mt-12 text-lg font-semibold">"Two-factor authentication"</h3>
+        <p class="my-1 mb-6 text-sm text-muted">
+            "Require a code from an authenticator app when signing in."
+        </p>
+        <Transition fallback=|| ()>{content}</Transition>
+
+        <Show when=move || !codes.with(Vec::is_empty)>
+            <div class="mt-6 p-4 rounded-md border border-border bg-accent">
+                <p class="text-sm font-medium">"Recovery codes"</p>
+                <p class="mb-3 text-xs text-muted">
+                    "Save these codes somewhere safe, each can be used once to sign in without your device. They will not be shown again."
+                </p>
+                <div class="grid grid-cols-2 gap-1 font-mono text-sm">
+                    {move || codes.get().into_iter().map(|code| view! { <span>{code}</span> }).collect_view()}
+                </div>
+            </div>
+        </Show>
+    }
+}
+
+// ==================== // SingleSignOnPanel // ==================== //
+
+/// Returns the label of the identity provider and the linked identities, none if
+/// the single sign-on is not configured
+///
+#[server]
+async fn get_identities() -> Result<Option<(String, Vec<Identity>)>, ServerFnError<Error>> {
+    use common::{AuthExtractor, ConfigExtractor, StoreExtractor};
+
+    let config = ConfigExtractor::use_config()?;
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let Some(oidc) = config.oidc.as_ref() else {
+        return Ok(None);
+    };
+    let identities = Identity::list(user.id, &store).await?;
+    Ok(Some((oidc.label.clone(), identities)))
+}
+
+#[server]
+async fn unlink_identity(issuer: String, subject: String) -> Result<(), ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    Identity::unlink(user.id, &issuer, &subject, &store).await?;
+    Ok(())
+}
+
+#[component]
+fn SingleSignOnPanel() -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let unlink_action = create_server_action::<UnlinkIdentity>();
+
+    let identities_resource = create_resource(
+        move || unlink_action.version().get(),
+        |_| get_identities(),
+    );
+
+    create_effect(move |_| {
+        unlink_action.value().with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => toast.success(String::from("Identity unlinked")),
+            _ => {}
+        });
+    });
+
+    let identity_view = move |identity: Identity| {
+        let Identity {
+            issuer,
+            subject,
+            name,
+            create_at,
+        } = identity;
+        let linked = DateTime::now().fmt_date(create_at);
+        view! {
+            <div class="flex items-center justify-between py-2">
+                <div>
+                    <p class="text-sm font-medium">{name}</p>
+                    <p class="text-xs text-muted">{format!("{} · linked {}", issuer, linked)}</p>
+                </div>
+                <button
+                    type="button"
+                    disabled=unlink_action.pending()
+                    on:click=move |_| {
+                        unlink_action
+                            .dispatch(UnlinkIdentity {
+                                issuer: issuer.clone(),
+                                subject: subject.clone(),
+                            })
+                    }
+                    class="h-9 px-5 btn-outline"
+                >
+                    "Unlink"
+                </button>
+            </div>
+        }
+    };
+
+    let content = move || {
+        identities_resource.get().and_then(Result::ok).flatten().map(|(label, identities)| {
+            view! {
+                <h3 class="mt-12 text-lg font-semibold">"Single sign-on"</h3>
+                <p class="my-1 mb-6 text-sm text-muted">
+                    {format!("Sign in with your {} account instead of the password.", label)}
+                </p>
+                <div class="grid divide-y divide-border">
+                    {identities.into_iter().map(identity_view).collect_view()}
+                </div>
+                <div class="mt-4 flex items-center justify-end">
+                    <a rel="external" href="/auth/oidc/login?link=true" class="h-9 px-5 btn-primary flex items-center">
+                        {format!("Link {} account", label)}
+                    </a>
+                </div>
+            }
+        })
+    };
+
+    view! { <Transition fallback=|| ()>{content}</Transition> }
+}
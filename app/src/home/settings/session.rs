@@ -24,8 +24,13 @@ pub fn SessionPage() -> impl IntoView {
 
     view! {
         <div class="px-12 py-8 grow h-full w-full">
-            <h2 class="text-xl font-semibold">Session</h2>
-            <p class="my-1 text-sm text-muted">"Manage your sessions."</p>
+            <div class="flex items-center justify-between">
+                <div>
+                    <h2 class="text-xl font-semibold">Session</h2>
+                    <p class="my-1 text-sm text-muted">"Manage your sessions."</p>
+                </div>
+                <DeleteOtherSessionsButton refresh />
+            </div>
 
             <div class="mt-12 w-full overflow-auto rounded-md border border-border">
                 <Transition fallback=|| {
@@ -59,9 +64,11 @@ fn SessionTable(sessions: Vec<Session>, refresh: RwSignal<i32>) -> impl IntoView
         <table class="w-full text-sm">
             <thead>
                 <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
-                    <th class="h-10 px-4 font-medium">Session</th>
+                    <th class="h-10 px-4 font-medium">Device</th>
+                    <th class="h-10 px-2 font-medium">"IP address"</th>
                     <th class="h-10 px-2 font-medium">Status</th>
-                    <th class="h-10 px-3 font-medium">"Last used"</th>
+                    <th class="h-10 px-2 font-medium">"Signed in"</th>
+                    <th class="h-10 px-3 font-medium">"Last active"</th>
                     <th class="h-10 px-2"></th>
                 </tr>
             </thead>
@@ -71,9 +78,17 @@ fn SessionTable(sessions: Vec<Session>, refresh: RwSignal<i32>) -> impl IntoView
                     key=move |session| session.id.clone()
                     children=move |session| {
                         let id = store_value(session.id);
+                        // sessions created before the metadata was recorded have none
+                        let create_at = match session.create_at {
+                            0 => String::from("-"),
+                            ts => dts.fmt_lg(ts),
+                        };
                         view! {
                             <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
-                                <td class="px-4 h-12">{id.with_value(|v| v[0..8].to_owned())}</td>
+                                <td class="px-4 h-12" title=session.user_agent>
+                                    {session.device}
+                                </td>
+                                <td class="px-2 h-12">{session.ip}</td>
                                 <td class="px-2 h-12">
                                     <Show when=move || session.current>
                                         <div class="inline-flex items-center rounded-md px-2.5 py-0.5 bg-danger/90 text-danger-on text-xs font-semibold">
@@ -81,7 +96,8 @@ fn SessionTable(sessions: Vec<Session>, refresh: RwSignal<i32>) -> impl IntoView
                                         </div>
                                     </Show>
                                 </td>
-                                <td class="px-2 h-12">{dts.fmt_lg(session.timestamp)}</td>
+                                <td class="px-2 h-12">{create_at}</td>
+                                <td class="px-2 h-12">{dts.fmt_lg(session.last_seen)}</td>
                                 <td class="px-2 h-12">
                                     <div class="flex items-center justify-center">
                                         <DeleteSessionButton session=id refresh />
@@ -97,7 +113,7 @@ fn SessionTable(sessions: Vec<Session>, refresh: RwSignal<i32>) -> impl IntoView
                     .map(|_| {
                         view! {
                             <tr class="hover:bg-accent/50">
-                                <td class="px-2 h-12 w-full" colspan="4"></td>
+                                <td class="px-2 h-12 w-full" colspan="6"></td>
                             </tr>
                         }
                     })
@@ -111,12 +127,13 @@ fn SessionTable(sessions: Vec<Session>, refresh: RwSignal<i32>) -> impl IntoView
 
 #[server]
 async fn delete_session(session: String) -> Result<(), ServerFnError<Error>> {
-    use common::{AuthExtractor, StoreExtractor};
+    use common::{AuthExtractor, HubManager, StoreExtractor};
 
     let store = StoreExtractor::use_store()?;
     let user = AuthExtractor::use_auth(false, &store).await?;
 
-    Session::delete(user.id, session, &store).await?;
+    Session::delete(user.id, session.clone(), &store).await?;
+    HubManager::kick_sessions(user.id, &[session], "This session has been signed out")?;
     Ok(())
 }
 
@@ -168,3 +185,70 @@ fn DeleteSessionButton(session: StoredValue<String>, refresh: RwSignal<i32>) ->
         </Show>
     }
 }
+
+// ========================================================= //
+
+/// Returns the number of sessions signed out
+///
+#[server]
+async fn delete_other_sessions() -> Result<usize, ServerFnError<Error>> {
+    use common::{AuthExtractor, CookieManager, HubManager, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+    let (_, session) = CookieManager::get_auth()?;
+
+    let others = Session::delete_others(user.id, session, &store).await?;
+    HubManager::kick_sessions(user.id, &others, "This session has been signed out")?;
+    Ok(others.len())
+}
+
+#[component]
+fn DeleteOtherSessionsButton(refresh: RwSignal<i32>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let show_modal = create_rw_signal(false);
+
+    let action = create_server_action::<DeleteOtherSessions>();
+    let value = action.value();
+    let pending = action.pending();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(count)) => {
+                toast.success(format!("{} sessions have been signed out", count));
+                refresh.update(|v| *v += 1);
+                show_modal.set(false);
+            }
+            _ => {}
+        });
+    });
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true) class="h-9 px-4 btn-outline">
+            "Sign out other sessions"
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <div class="my-6 size-16 flex items-center justify-center rounded-full bg-danger/20">
+                        <WarnTriangle class="size-10 stroke-danger" />
+                    </div>
+                    <h3 class="my-1 text-center text-xl font-semibold">"Sign out other sessions"</h3>
+                    <p class="mb-4 text-center text-muted">
+                        "All devices except this one will have to sign in again."
+                    </p>
+                    <ActionForm class="w-full my-5" action>
+                        <button type="submit" disabled=pending class="w-full h-9 px-3 btn-primary">
+                            "Confirm"
+                        </button>
+                    </ActionForm>
+                    <button type="button" on:click=move |_| show_modal.set(false) class="w-full h-9 px-3 btn-outline">
+                        Cancel
+                    </button>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
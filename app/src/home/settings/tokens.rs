@@ -0,0 +1,343 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::components::icons::{DeleteTrash, PlusCircle, SpinCircle, WarnTriangle};
+use crate::components::{BlankTableItem, ModalWrapper, Selector, Toast};
+use crate::home::DateTimeState;
+use common::{ApiToken, Error, FnError, InsertTokenArg};
+
+#[server]
+async fn list_tokens() -> Result<Vec<ApiToken>, ServerFnError<Error>> {
+    use common::{AuthExtractor, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let tokens = ApiToken::list(user.id, &store).await?;
+    Ok(tokens)
+}
+
+#[component]
+pub fn TokensPage() -> impl IntoView {
+    let refresh = create_rw_signal(0);
+    let rsc = create_resource(move || refresh.get(), move |_| list_tokens());
+
+    view! {
+        <div class="px-12 py-8 grow h-full w-full">
+            <div class="flex items-center justify-between">
+                <div>
+                    <h2 class="text-xl font-semibold">"Access Tokens"</h2>
+                    <p class="my-1 text-sm text-muted">
+                        "Tokens let scripts use the API with "
+                        <code>"Authorization: Bearer"</code>
+                        "."
+                    </p>
+                </div>
+                <CreateTokenButton refresh />
+            </div>
+
+            <div class="mt-12 w-full overflow-auto rounded-md border border-border">
+                <Transition fallback=|| {
+                    view! {
+                        <div class="w-full h-56 flex items-center justify-center">
+                            <SpinCircle class="animate-spin size-10" />
+                        </div>
+                    }
+                }>
+                    {move || {
+                        if let Some(Ok(tokens)) = rsc.get() {
+                            view! { <TokenTable tokens refresh /> }
+                        } else {
+                            ().into_view()
+                        }
+                    }}
+
+                </Transition>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn TokenTable(tokens: Vec<ApiToken>, refresh: RwSignal<i32>) -> impl IntoView {
+    let empty = tokens.is_empty();
+    let tokens = store_value(tokens);
+    let dts = expect_context::<DateTimeState>();
+
+    view! {
+        <table class="w-full text-sm">
+            <thead>
+                <tr class="border-b border-border text-left text-muted hover:bg-accent/50">
+                    <th class="h-10 px-4 font-medium">Name</th>
+                    <th class="h-10 px-2 font-medium">Token</th>
+                    <th class="h-10 px-2 font-medium">Scopes</th>
+                    <th class="h-10 px-2 font-medium">Expires</th>
+                    <th class="h-10 px-3 font-medium">"Last used"</th>
+                    <th class="h-10 px-2"></th>
+                </tr>
+            </thead>
+            <tbody>
+                <Show when=move || empty>
+                    <BlankTableItem msg="No access tokens" cols=6 />
+                </Show>
+                <For
+                    each=move || tokens.get_value()
+                    key=move |token| token.id
+                    children=move |token| {
+                        let expire_at = match token.expire_at {
+                            0 => String::from("Never"),
+                            ts => dts.fmt_lg(ts),
+                        };
+                        let last_used_at = match token.last_used_at {
+                            0 => String::from("Never"),
+                            ts => dts.fmt_lg(ts),
+                        };
+                        view! {
+                            <tr class="last:border-b-0 border-b border-border hover:bg-accent/50">
+                                <td class="px-4 h-12">{token.name.clone()}</td>
+                                <td class="px-2 h-12 font-mono">{format!("{}…", token.prefix)}</td>
+                                <td class="px-2 h-12">{token.scope_labels().join(", ")}</td>
+                                <td class="px-2 h-12">{expire_at}</td>
+                                <td class="px-2 h-12">{last_used_at}</td>
+                                <td class="px-2 h-12">
+                                    <div class="flex items-center justify-center">
+                                        <RevokeTokenButton token_id=token.id refresh />
+                                    </div>
+                                </td>
+                            </tr>
+                        }
+                    }
+                />
+            </tbody>
+        </table>
+    }
+}
+
+// ==================== // CreateTokenButton // ==================== //
+
+/// Returns the token, it cannot be seen again
+///
+#[server]
+async fn create_token(arg: InsertTokenArg) -> Result<String, ServerFnError<Error>> {
+    use common::{ArgsValidator, AuthExtractor, StoreExtractor};
+
+    let arg = ArgsValidator::validate(arg)?;
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    let token = arg.call(user.id, &store).await?;
+    Ok(token)
+}
+
+#[component]
+fn CreateTokenButton(refresh: RwSignal<i32>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let show_modal = create_rw_signal(false);
+    let created = create_rw_signal(None::<String>);
+
+    let action = create_server_action::<CreateToken>();
+    let pending = action.pending();
+    let value = action.value();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(token)) => {
+                created.set(Some(token.clone()));
+                refresh.update(|x| *x += 1);
+                show_modal.set(false)
+            }
+            _ => {}
+        });
+    });
+
+    let read_messages = create_rw_signal(true);
+    let send_messages = create_rw_signal(false);
+    let manage_files = create_rw_signal(false);
+    let bool_options = store_value(vec![true, false]);
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true) class="h-9 px-4 gap-1 btn-primary">
+            <PlusCircle class="size-4 stroke-2" />
+            <span class="text-sm font-medium">"New Token"</span>
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <h3 class="text-xl font-semibold tracking-tight">"New Token"</h3>
+                    <p class="text-sm text-muted">"Grant a script access to your account"</p>
+
+                    <ActionForm class="w-full flex flex-col gap-6 mt-6 mb-4" action>
+                        <div class="grid gap-2">
+                            <label for="token_name" class="text-sm font-medium leading-none">
+                                Name
+                            </label>
+                            <input
+                                id="token_name"
+                                type="text"
+                                name="arg[name]"
+                                disabled=pending
+                                autocomplete="off"
+                                placeholder="What the token is used for"
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid gap-2">
+                            <label for="expire_days" class="text-sm font-medium leading-none">
+                                "Expires in days"
+                            </label>
+                            <input
+                                id="expire_days"
+                                type="number"
+                                min="0"
+                                max="3650"
+                                name="arg[expire_days]"
+                                value="30"
+                                disabled=pending
+                                autocomplete="off"
+                                placeholder="0 for never"
+                                class="w-full h-9 px-3 input"
+                            />
+                        </div>
+
+                        <div class="grid grid-cols-3 gap-4 mb-3">
+                            <div class="grid gap-2">
+                                <label for="read_messages" class="text-sm font-medium leading-none">
+                                    "Read messages"
+                                </label>
+                                <input
+                                    id="read_messages"
+                                    type="hidden"
+                                    name="arg[read_messages]"
+                                    prop:value=move || read_messages.get().to_string()
+                                />
+                                <Selector value=read_messages options=bool_options />
+                            </div>
+                            <div class="grid gap-2">
+                                <label for="send_messages" class="text-sm font-medium leading-none">
+                                    "Send messages"
+                                </label>
+                                <input
+                                    id="send_messages"
+                                    type="hidden"
+                                    name="arg[send_messages]"
+                                    prop:value=move || send_messages.get().to_string()
+                                />
+                                <Selector value=send_messages options=bool_options />
+                            </div>
+                            <div class="grid gap-2">
+                                <label for="manage_files" class="text-sm font-medium leading-none">
+                                    "Manage files"
+                                </label>
+                                <input
+                                    id="manage_files"
+                                    type="hidden"
+                                    name="arg[manage_files]"
+                                    prop:value=move || manage_files.get().to_string()
+                                />
+                                <Selector value=manage_files options=bool_options />
+                            </div>
+                        </div>
+
+                        <div class="flex items-center justify-between space-x-2">
+                            <button type="button" on:click=move |_| show_modal.set(false) class="h-9 px-5 btn-ghost">
+                                Cancel
+                            </button>
+                            <button type="submit" disabled=pending class="h-9 px-5 btn-primary">
+                                "Create"
+                            </button>
+                        </div>
+                    </ActionForm>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+
+        <Show when=move || created.with(Option::is_some)>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <h3 class="text-xl font-semibold tracking-tight">"Token created"</h3>
+                    <p class="text-sm text-muted">"Copy the token now, it will not be shown again."</p>
+                    <input
+                        type="text"
+                        readonly=true
+                        prop:value=move || created.get().unwrap_or_default()
+                        class="w-full h-9 my-6 px-3 font-mono input"
+                    />
+                    <button type="button" on:click=move |_| created.set(None) class="w-full h-9 px-3 btn-primary">
+                        Done
+                    </button>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
+
+// ==================== // RevokeTokenButton // ==================== //
+
+#[server]
+async fn revoke_token(token_id: i64) -> Result<(), ServerFnError<Error>> {
+    use common::{AuthExtractor, HubManager, StoreExtractor};
+
+    let store = StoreExtractor::use_store()?;
+    let user = AuthExtractor::use_auth(false, &store).await?;
+
+    ApiToken::revoke(user.id, token_id, &store).await?;
+    HubManager::kick_sessions(
+        user.id,
+        &[ApiToken::session_key(token_id)],
+        "The access token has been revoked",
+    )?;
+    Ok(())
+}
+
+#[component]
+fn RevokeTokenButton(token_id: i64, refresh: RwSignal<i32>) -> impl IntoView {
+    let toast = expect_context::<Toast>();
+    let show_modal = create_rw_signal(false);
+
+    let action = create_server_action::<RevokeToken>();
+    let value = action.value();
+    let pending = action.pending();
+
+    create_effect(move |_| {
+        value.with(|val| match val {
+            Some(Err(FnError::WrappedServerError(e))) => toast.error(e.to_string()),
+            Some(Ok(_)) => {
+                toast.success(String::from("Token has been revoked"));
+                refresh.update(|v| *v += 1);
+                show_modal.set(false);
+            }
+            _ => {}
+        });
+    });
+
+    view! {
+        <button type="button" on:click=move |_| show_modal.set(true)>
+            <DeleteTrash class="size-4 hover:stroke-danger" />
+        </button>
+
+        <Show when=move || show_modal.get()>
+            <Portal mount=document().get_element_by_id("app").unwrap()>
+                <ModalWrapper>
+                    <div class="my-6 size-16 flex items-center justify-center rounded-full bg-danger/20">
+                        <WarnTriangle class="size-10 stroke-danger" />
+                    </div>
+                    <h3 class="my-1 text-center text-xl font-semibold">"Revoke token"</h3>
+                    <p class="mb-4 text-center text-muted">"Scripts using this token will lose access."</p>
+                    <ActionForm class="w-full my-5" action>
+                        <input type="hidden" name="token_id" value=token_id />
+                        <button type="submit" disabled=pending class="w-full h-9 px-3 btn-primary">
+                            "Confirm"
+                        </button>
+                    </ActionForm>
+                    <button type="button" on:click=move |_| show_modal.set(false) class="w-full h-9 px-3 btn-outline">
+                        Cancel
+                    </button>
+                </ModalWrapper>
+            </Portal>
+        </Show>
+    }
+}
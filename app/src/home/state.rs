@@ -1,7 +1,7 @@
 use leptos::*;
 use std::collections::HashMap;
 
-use crate::connection::{provide_websocket, WebRtcState};
+use crate::connection::{provide_websocket, RoomRtcState, WebRtcState};
 use common::{DateTime, Friend, Message, Room, User};
 
 // ==================== // StateProvider // ==================== //
@@ -19,7 +19,11 @@ pub fn StateProvider(user: User, children: Children) -> impl IntoView {
     let webrtc = WebRtcState::new();
     provide_context(webrtc);
 
-    provide_websocket(chats, webrtc);
+    // state for room calls
+    let roomrtc = RoomRtcState::new();
+    provide_context(roomrtc);
+
+    provide_websocket(chats, webrtc, roomrtc);
 
     view! { <div class="w-full h-full flex">{children()}</div> }
 }
@@ -115,4 +119,12 @@ impl DateTimeState {
     pub fn fmt_lg(&self, ts: i64) -> String {
         self.0.with_value(|v| v.fmt_lg(ts))
     }
+
+    pub fn fmt_date(&self, ts: i64) -> String {
+        self.0.with_value(|v| v.fmt_date(ts))
+    }
+
+    pub fn offset(&self) -> i32 {
+        self.0.with_value(|v| v.offset)
+    }
 }
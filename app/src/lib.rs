@@ -1,8 +1,8 @@
 use leptos::*;
-use leptos_meta::{provide_meta_context, Body, Html, Link, Meta, Stylesheet, Title};
+use leptos_meta::{provide_meta_context, Body, Html, Link, Meta, Script, Stylesheet, Title};
 use leptos_router::{Route, Router, Routes};
 
-use auth::LoginPage;
+use auth::{LoginPage, PasswordPage, RegisterPage};
 use components::provide_dark_mode;
 use home::HomeRoutes;
 use layout::{MainLayout, NotFoundPage};
@@ -14,6 +14,8 @@ mod home;
 mod layout;
 
 const LOGIN_PATH: &str = "/login";
+const REGISTER_PATH: &str = "/register";
+const PASSWORD_PATH: &str = "/password";
 const CHATS_PATH: &str = "/chats";
 
 #[component]
@@ -27,6 +29,7 @@ pub fn App() -> impl IntoView {
         <Meta name="description" content="Chat: A Web App implemented by Leptos." />
         <Link rel="shortcut icon" type_="image/ico" href="/favicon.ico" />
         <Stylesheet id="leptos" href="/pkg/chat.css" />
+        <Script src="/csrf.js" />
 
         <Html class=move || { if is_dark.get() { "dark" } else { "" } } />
         <Body class="h-screen w-full bg-container" />
@@ -36,6 +39,8 @@ pub fn App() -> impl IntoView {
                 <Route path="" view=MainLayout>
                     <HomeRoutes />
                     <Route path=LOGIN_PATH view=LoginPage />
+                    <Route path=REGISTER_PATH view=RegisterPage />
+                    <Route path=PASSWORD_PATH view=PasswordPage />
                 </Route>
             </Routes>
         </Router>
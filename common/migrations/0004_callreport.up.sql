@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS callreports (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL,
+  friend_id INTEGER NOT NULL,
+  duration INTEGER NOT NULL,
+  rtt REAL NOT NULL,
+  jitter REAL NOT NULL,
+  packet_loss REAL NOT NULL,
+  bitrate REAL NOT NULL,
+  candidate_type TEXT NOT NULL,
+  create_at INTEGER NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users (id)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
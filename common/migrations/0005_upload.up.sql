@@ -0,0 +1,17 @@
+ALTER TABLE users ADD COLUMN quota INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS uploads (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL,
+  url TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  create_at INTEGER NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users (id)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+CREATE INDEX idx_uploads_user_id
+ON uploads (user_id);
+
+CREATE UNIQUE INDEX idx_uploads_url
+ON uploads (url);
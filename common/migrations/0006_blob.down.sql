@@ -0,0 +1,12 @@
+DROP INDEX IF EXISTS idx_uploads_url;
+
+-- the urls were unique before the blobs were shared, keep the first record of each
+DELETE FROM uploads
+WHERE id NOT IN (SELECT MIN(id) FROM uploads GROUP BY url);
+
+CREATE UNIQUE INDEX idx_uploads_url
+ON uploads (url);
+
+DROP TABLE IF EXISTS blob_refs;
+
+DROP TABLE IF EXISTS blobs;
@@ -0,0 +1,31 @@
+CREATE TABLE IF NOT EXISTS blobs (
+  hash TEXT PRIMARY KEY NOT NULL,
+  path TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  image TEXT,
+  create_at INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_blobs_path
+ON blobs (path);
+
+CREATE TABLE IF NOT EXISTS blob_refs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  hash TEXT NOT NULL,
+  owner TEXT NOT NULL,
+  create_at INTEGER NOT NULL,
+  FOREIGN KEY (hash) REFERENCES blobs (hash)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+CREATE UNIQUE INDEX idx_blob_refs_hash_owner
+ON blob_refs (hash, owner);
+
+CREATE INDEX idx_blob_refs_owner
+ON blob_refs (owner);
+
+-- identical uploads share the same url now
+DROP INDEX IF EXISTS idx_uploads_url;
+
+CREATE INDEX idx_uploads_url
+ON uploads (url);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS upload_sessions (
+  id TEXT PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL,
+  key TEXT NOT NULL,
+  name TEXT NOT NULL,
+  mime TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  received INTEGER NOT NULL DEFAULT 0,
+  update_at INTEGER NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users (id)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+CREATE UNIQUE INDEX idx_upload_sessions_user_key
+ON upload_sessions (user_id, key);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS room_files (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  room_id TEXT NOT NULL,
+  url TEXT NOT NULL,
+  create_at INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_room_files_url_room_id
+ON room_files (url, room_id);
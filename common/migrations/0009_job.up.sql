@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS jobs (
+  name TEXT PRIMARY KEY NOT NULL,
+  last_run INTEGER NOT NULL,
+  duration INTEGER NOT NULL,
+  success BOOLEAN NOT NULL,
+  result TEXT NOT NULL
+);
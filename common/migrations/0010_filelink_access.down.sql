@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS idx_filelinks_token;
+
+ALTER TABLE filelinks DROP COLUMN revoked;
+ALTER TABLE filelinks DROP COLUMN downloads;
+ALTER TABLE filelinks DROP COLUMN max_downloads;
+ALTER TABLE filelinks DROP COLUMN expire_at;
+ALTER TABLE filelinks DROP COLUMN password;
+ALTER TABLE filelinks DROP COLUMN token;
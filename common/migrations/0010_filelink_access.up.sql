@@ -0,0 +1,12 @@
+ALTER TABLE filelinks ADD COLUMN token TEXT NOT NULL DEFAULT '';
+ALTER TABLE filelinks ADD COLUMN password TEXT NOT NULL DEFAULT '';
+ALTER TABLE filelinks ADD COLUMN expire_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE filelinks ADD COLUMN max_downloads INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE filelinks ADD COLUMN downloads INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE filelinks ADD COLUMN revoked BOOLEAN NOT NULL DEFAULT 0;
+
+-- existing links get a token, their files are no longer served publicly from the archive
+UPDATE filelinks SET token = lower(hex(randomblob(12))) WHERE token = '';
+
+CREATE UNIQUE INDEX idx_filelinks_token
+ON filelinks (token);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_link_downloads_link_id_create_at;
+DROP TABLE IF EXISTS link_downloads;
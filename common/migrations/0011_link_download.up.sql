@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS link_downloads (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  link_id INTEGER NOT NULL,
+  client TEXT NOT NULL,
+  success BOOLEAN NOT NULL,
+  create_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_link_downloads_link_id_create_at
+ON link_downloads (link_id, create_at);
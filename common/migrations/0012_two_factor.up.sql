@@ -0,0 +1,7 @@
+ALTER TABLE users ADD COLUMN totp_secret TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+  user_id INTEGER NOT NULL,
+  code TEXT NOT NULL,
+  PRIMARY KEY (user_id, code)
+);
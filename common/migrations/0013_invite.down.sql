@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_invites_code;
+DROP TABLE IF EXISTS invites;
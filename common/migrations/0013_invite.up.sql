@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS invites (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  code TEXT NOT NULL,
+  creator_id INTEGER NOT NULL,
+  role INTEGER NOT NULL,
+  max_uses INTEGER NOT NULL DEFAULT 1,
+  uses INTEGER NOT NULL DEFAULT 0,
+  expire_at INTEGER NOT NULL DEFAULT 0,
+  auto_friend BOOLEAN NOT NULL DEFAULT 0,
+  create_at INTEGER NOT NULL,
+  FOREIGN KEY (creator_id) REFERENCES users (id)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+CREATE UNIQUE INDEX idx_invites_code
+ON invites (code);
@@ -0,0 +1,45 @@
+DROP INDEX IF EXISTS idx_uploads_user_url;
+
+-- the blobs were unique by content, keep the first path of each
+CREATE TABLE IF NOT EXISTS blobs_old (
+  hash TEXT PRIMARY KEY NOT NULL,
+  path TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  image TEXT,
+  create_at INTEGER NOT NULL
+);
+
+INSERT INTO blobs_old (hash, path, size, image, create_at)
+SELECT hash, path, size, image, create_at FROM blobs
+WHERE path IN (SELECT MIN(path) FROM blobs GROUP BY hash);
+
+CREATE TABLE IF NOT EXISTS blob_refs_old (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  hash TEXT NOT NULL,
+  owner TEXT NOT NULL,
+  create_at INTEGER NOT NULL,
+  FOREIGN KEY (hash) REFERENCES blobs_old (hash)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+INSERT INTO blob_refs_old (hash, owner, create_at)
+SELECT b.hash, r.owner, MIN(r.create_at) FROM blob_refs AS r
+JOIN blobs AS b ON b.path = r.path
+GROUP BY b.hash, r.owner;
+
+DROP TABLE blob_refs;
+
+DROP TABLE blobs;
+
+ALTER TABLE blobs_old RENAME TO blobs;
+
+ALTER TABLE blob_refs_old RENAME TO blob_refs;
+
+CREATE UNIQUE INDEX idx_blobs_path
+ON blobs (path);
+
+CREATE UNIQUE INDEX idx_blob_refs_hash_owner
+ON blob_refs (hash, owner);
+
+CREATE INDEX idx_blob_refs_owner
+ON blob_refs (owner);
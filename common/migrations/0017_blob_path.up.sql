@@ -0,0 +1,49 @@
+-- blobs are kept per directory and extension, the same content may be stored
+-- once in each of them
+CREATE TABLE IF NOT EXISTS blobs_new (
+  path TEXT PRIMARY KEY NOT NULL,
+  hash TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  image TEXT,
+  create_at INTEGER NOT NULL
+);
+
+INSERT INTO blobs_new (path, hash, size, image, create_at)
+SELECT path, hash, size, image, create_at FROM blobs;
+
+CREATE TABLE IF NOT EXISTS blob_refs_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  path TEXT NOT NULL,
+  owner TEXT NOT NULL,
+  create_at INTEGER NOT NULL,
+  FOREIGN KEY (path) REFERENCES blobs_new (path)
+    ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+INSERT INTO blob_refs_new (id, path, owner, create_at)
+SELECT r.id, b.path, r.owner, r.create_at FROM blob_refs AS r
+JOIN blobs AS b ON b.hash = r.hash;
+
+DROP TABLE blob_refs;
+
+DROP TABLE blobs;
+
+ALTER TABLE blobs_new RENAME TO blobs;
+
+ALTER TABLE blob_refs_new RENAME TO blob_refs;
+
+CREATE INDEX idx_blobs_hash
+ON blobs (hash);
+
+CREATE UNIQUE INDEX idx_blob_refs_path_owner
+ON blob_refs (path, owner);
+
+CREATE INDEX idx_blob_refs_owner
+ON blob_refs (owner);
+
+-- a file is counted once in the usage of each user
+DELETE FROM uploads
+WHERE id NOT IN (SELECT MIN(id) FROM uploads GROUP BY user_id, url);
+
+CREATE UNIQUE INDEX idx_uploads_user_url
+ON uploads (user_id, url);
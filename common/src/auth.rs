@@ -1,12 +1,23 @@
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use std::{collections::HashMap, net::SocketAddr};
+    use axum::http::{header::USER_AGENT, HeaderMap};
     use uuid::Uuid;
     use redis::AsyncCommands;
-    use crate::{Result, Error, Store, User, DateTime};
+    use crate::{Result, Error, LoginConfig, LoginThrottle, Store, TwoFactor, User, DateTime};
     use super::user::{UserEntity};
+    use crate::file::client_family;
 }}
 
 use serde::{Deserialize, Serialize};
 
+/// Seconds to enter the 2FA code after the password is verified
+#[cfg(feature = "ssr")]
+const CHALLENGE_EXPIRE: i64 = 300;
+
+/// Attempts to enter the 2FA code before the login must be restarted
+#[cfg(feature = "ssr")]
+const CHALLENGE_ATTEMPTS: i64 = 5;
+
 // ==================== // LoginArg // ==================== //
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -25,36 +36,204 @@ pub struct LoginArg {
 }
 
 impl LoginArg {
-    /// Authenticate the user in the database
+    /// Authenticate the user in the database, users with 2FA get a challenge to
+    /// verify the code before the session is created. Failed logins are throttled
+    /// per username and client address
     ///
     #[cfg(feature = "ssr")]
-    pub async fn call(&self, store: &Store) -> Result<(String, User)> {
+    pub async fn call(
+        &self,
+        client: &ClientInfo,
+        config: &LoginConfig,
+        store: &Store,
+    ) -> Result<LoginStep> {
+        let ip = client.ip.as_str();
+        LoginThrottle::check(&self.username, ip, config, store).await?;
+
+        let user = match self.authenticate(store).await {
+            Ok(v) => v,
+            Err(err @ Error::BadRequest(_)) => {
+                LoginThrottle::fail(&self.username, ip, config, store).await?;
+                return Err(err);
+            }
+            Err(err) => return Err(err),
+        };
+
+        // the failures are only forgiven once the second factor is verified too
+        if TwoFactor::secret(user.id, store).await?.is_some() {
+            let challenge = VerifyLoginArg::challenge(user.id, &self.username, store).await?;
+            return Ok(LoginStep::TwoFactor(challenge));
+        }
+        LoginThrottle::reset(&self.username, store).await?;
+
+        let session = Session::create(&user, client, store).await?;
+        Ok(LoginStep::Session(session, user))
+    }
+
+    /// Verify the username and password
+    ///
+    #[cfg(feature = "ssr")]
+    async fn authenticate(&self, store: &Store) -> Result<User> {
         let user = UserEntity::find(&self.username, store)
             .await?
             .ok_or(Error::BadRequest(String::from("User not found")))?;
 
         user.verify_password(&self.password)?;
+        Ok(User::from(user))
+    }
+}
+
+/// Result of the password step of the login
+///
+#[cfg(feature = "ssr")]
+pub enum LoginStep {
+    Session(String, User),
+    /// The code is required, with the challenge of the login
+    TwoFactor(String),
+}
 
+// ==================== // VerifyLoginArg // ==================== //
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct VerifyLoginArg {
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 64, message = "The login has expired"))
+    )]
+    pub challenge: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 6, max = 16, message = "The code is too short"))
+    )]
+    pub code: String,
+}
+
+impl VerifyLoginArg {
+    /// Verify the 2FA code of the challenge and create the session. Wrong codes
+    /// count as failed logins, so that a known password does not give unlimited
+    /// guesses of the code
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn call(
+        &self,
+        client: &ClientInfo,
+        config: &LoginConfig,
+        store: &Store,
+    ) -> Result<(String, User)> {
+        let key = Self::make_key(&self.challenge);
         let mut con = store.con.clone();
 
-        let user = User::from(user);
-        let ukey = user.key();
-        let user_str = serde_json::to_string(&user)?;
+        let (user_id, username): (Option<i64>, Option<String>) = redis::pipe()
+            .hget(&key, "user_id")
+            .hget(&key, "username")
+            .query_async(&mut con)
+            .await?;
+        let (user_id, username) = match (user_id, username) {
+            (Some(user_id), Some(username)) => (user_id, username),
+            _ => {
+                return Err(Error::BadRequest(String::from(
+                    "The login has expired, please sign in again",
+                )))
+            }
+        };
 
-        let skey = Session::make_key(user.id);
-        let session = Uuid::new_v4().to_string();
-        let now = DateTime::now().timestamp;
+        let ip = client.ip.as_str();
+        if let Err(err) = LoginThrottle::check(&username, ip, config, store).await {
+            let _: () = con.del(&key).await?;
+            return Err(err);
+        }
 
+        let attempts: i64 = con.hincr(&key, "attempts", 1).await?;
+        if attempts > CHALLENGE_ATTEMPTS {
+            let _: () = con.del(&key).await?;
+            return Err(Error::BadRequest(String::from(
+                "Too many attempts, please sign in again",
+            )));
+        }
+
+        match TwoFactor::verify(user_id, &self.code, store).await {
+            Ok(_) => {}
+            Err(err @ Error::BadRequest(_)) => {
+                LoginThrottle::fail(&username, ip, config, store).await?;
+                return Err(err);
+            }
+            Err(err) => return Err(err),
+        }
+        let _: () = con.del(&key).await?;
+        LoginThrottle::reset(&username, store).await?;
+
+        let user = User::get(user_id, store).await?;
+        let session = Session::create(&user, client, store).await?;
+        Ok((session, user))
+    }
+
+    /// Start the second step of the login of a user with 2FA, returns the challenge.
+    /// The failures of the code are counted for the username
+    ///
+    #[cfg(feature = "ssr")]
+    pub(crate) async fn challenge(user_id: i64, username: &str, store: &Store) -> Result<String> {
+        let challenge = Uuid::new_v4().to_string();
+        let key = Self::make_key(&challenge);
+
+        let mut con = store.con.clone();
         let _: () = redis::pipe()
-            .set_ex(ukey, user_str, 604800)
+            .hset(&key, "user_id", user_id)
             .ignore()
-            .zadd(&skey, &session, now)
+            .hset(&key, "username", username)
+            .ignore()
+            .hset(&key, "attempts", 0)
+            .ignore()
+            .expire(&key, CHALLENGE_EXPIRE)
             .ignore()
-            .zremrangebyrank(skey, 0, -6)
             .query_async(&mut con)
             .await?;
+        Ok(challenge)
+    }
 
-        Ok((session, user))
+    /// Create a key of the login challenge for redis
+    ///
+    #[cfg(feature = "ssr")]
+    fn make_key(challenge: &str) -> String {
+        format!("login-2fa:{}", challenge)
+    }
+}
+
+// ==================== // ClientInfo // ==================== //
+
+/// The client that makes the request, recorded with the session
+///
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub ip: String,
+    pub user_agent: String,
+}
+
+#[cfg(feature = "ssr")]
+impl ClientInfo {
+    /// Read the client from the request, `X-Forwarded-For` is only trusted behind a
+    /// proxy since clients can set it to anything
+    ///
+    pub fn new(headers: &HeaderMap, addr: SocketAddr, trust_proxy: bool) -> Self {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .filter(|v| trust_proxy && !v.is_empty());
+        let ip = match forwarded {
+            Some(ip) => ip.to_owned(),
+            None => addr.ip().to_string(),
+        };
+
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(512).collect())
+            .unwrap_or_default();
+
+        Self { ip, user_agent }
     }
 }
 
@@ -63,12 +242,68 @@ impl LoginArg {
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Session {
     pub id: String,
-    pub timestamp: i64,
+    /// Browser and system parsed from the user agent
+    pub device: String,
+    pub user_agent: String,
+    pub ip: String,
+    pub create_at: i64,
+    /// Last time the session was used
+    pub last_seen: i64,
     pub current: bool,
 }
 
+/// Metadata of a session kept in the redis, the last seen time is the score of the
+/// session in the sorted set
+///
+#[cfg(feature = "ssr")]
+#[derive(Deserialize, Serialize, Default)]
+struct SessionInfo {
+    user_agent: String,
+    ip: String,
+    create_at: i64,
+}
+
 impl Session {
-    /// Get a login user in the redis and database
+    /// Create a login session of the user, the least recently used sessions over the
+    /// limit are removed
+    ///
+    #[cfg(feature = "ssr")]
+    pub(crate) async fn create(user: &User, client: &ClientInfo, store: &Store) -> Result<String> {
+        let mut con = store.con.clone();
+
+        let ukey = user.key();
+        let user_str = serde_json::to_string(user)?;
+
+        let skey = Session::make_key(user.id);
+        let session = Uuid::new_v4().to_string();
+        let now = DateTime::now().timestamp;
+        let info = serde_json::to_string(&SessionInfo {
+            user_agent: client.user_agent.clone(),
+            ip: client.ip.clone(),
+            create_at: now,
+        })?;
+
+        let _: () = redis::pipe()
+            .set_ex(ukey, user_str, 604800)
+            .ignore()
+            .zadd(&skey, &session, now)
+            .ignore()
+            .hset(Session::make_info_key(user.id), &session, info)
+            .ignore()
+            .query_async(&mut con)
+            .await?;
+
+        let max_count = store.session.max_count;
+        if max_count > 0 {
+            let stale: Vec<String> = con.zrange(&skey, 0, -(max_count as isize) - 1).await?;
+            Self::remove(user.id, &stale, store).await?;
+        }
+
+        Ok(session)
+    }
+
+    /// Get a login user in the redis and database, the session is removed if it has
+    /// been idle or alive for too long
     ///
     #[cfg(feature = "ssr")]
     pub async fn verify(
@@ -80,41 +315,72 @@ impl Session {
         let key = Session::make_key(user_id);
         let mut con = store.con.clone();
 
-        let score: Option<i64> = con.zscore(&key, &session).await?;
-        if score.is_none() {
+        let (score, info): (Option<i64>, Option<String>) = redis::pipe()
+            .zscore(&key, &session)
+            .hget(Session::make_info_key(user_id), &session)
+            .query_async(&mut con)
+            .await?;
+        let last_seen = score.ok_or(Error::Unauthorized)?;
+        let create_at = info
+            .and_then(|v| serde_json::from_str::<SessionInfo>(&v).ok())
+            .map(|v| v.create_at)
+            .unwrap_or_default();
+
+        let now = DateTime::now().timestamp;
+        if Self::is_expired(create_at, last_seen, now, store) {
+            Self::remove(user_id, &[session], store).await?;
             return Err(Error::Unauthorized);
         }
 
-        let user = User::get(user_id, store)
-            .await
-            .map_err(|_| Error::BadRequest(String::from("User not exists")))?;
+        // the sessions of a deleted user are signed out
+        let user = match User::get(user_id, store).await {
+            Ok(user) => user,
+            Err(Error::NotFound) => {
+                Self::remove(user_id, &[session], store).await?;
+                return Err(Error::Unauthorized);
+            }
+            Err(err) => return Err(err),
+        };
 
         if !user.active {
+            Self::remove(user_id, &[session], store).await?;
             return Err(Error::Forbidden);
         }
 
         if refresh {
-            let now = DateTime::now().timestamp;
             let _: () = con.zadd(key, session, now).await?;
         }
 
         Ok(user)
     }
 
-    /// List all sessions of the user in the redis
+    /// List all sessions of the user in the redis, the latest used first
     ///
     #[cfg(feature = "ssr")]
     pub async fn list(user_id: i64, session: String, store: &Store) -> Result<Vec<Self>> {
         let key = Self::make_key(user_id);
         let mut con = store.con.clone();
 
-        let data: Vec<(String, i64)> = con.zrange_withscores(key, 0, -1).await?;
+        let data: Vec<(String, i64)> = con.zrevrange_withscores(key, 0, -1).await?;
+        let mut infos: HashMap<String, String> = con.hgetall(Self::make_info_key(user_id)).await?;
+
         let ret: Vec<Self> = data
             .into_iter()
-            .map(|(id, timestamp)| Self {
-                current: id == session,
-                id,
-                timestamp,
+            .map(|(id, last_seen)| {
+                // sessions created before the metadata was recorded have none
+                let info = infos
+                    .remove(&id)
+                    .and_then(|v| serde_json::from_str::<SessionInfo>(&v).ok())
+                    .unwrap_or_default();
+                Self {
+                    current: id == session,
+                    device: device_label(&info.user_agent),
+                    user_agent: info.user_agent,
+                    ip: info.ip,
+                    create_at: info.create_at,
+                    last_seen,
+                    id,
+                }
             })
             .collect();
 
@@ -129,9 +395,100 @@ impl Session {
     ///
     #[cfg(feature = "ssr")]
     pub async fn delete(user_id: i64, session: String, store: &Store) -> Result<()> {
-        let key = Self::make_key(user_id);
+        Self::remove(user_id, &[session], store).await
+    }
+
+    /// Delete all sessions of the user except the current one, returns the sessions
+    /// deleted
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn delete_others(user_id: i64, session: String, store: &Store) -> Result<Vec<String>> {
+        let mut con = store.con.clone();
+        let ids: Vec<String> = con.zrange(Self::make_key(user_id), 0, -1).await?;
+        let others: Vec<String> = ids.into_iter().filter(|id| *id != session).collect();
+
+        Self::remove(user_id, &others, store).await?;
+        Ok(others)
+    }
+
+    /// Delete all sessions of the user
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn delete_all(user_id: i64, store: &Store) -> Result<()> {
         let mut con = store.con.clone();
-        let _: () = con.zrem(key, session).await?;
+        let _: () = con
+            .del(&[Self::make_key(user_id), Self::make_info_key(user_id)])
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the sessions which have expired at the time, returns the number removed
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn prune(now: i64, store: &Store) -> Result<u64> {
+        let user_ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM users")
+            .fetch_all(&store.pool)
+            .await?;
+
+        let mut con = store.con.clone();
+        let mut removed = 0_u64;
+        for (user_id,) in user_ids {
+            let data: Vec<(String, i64)> = con
+                .zrange_withscores(Self::make_key(user_id), 0, -1)
+                .await?;
+            let infos: HashMap<String, String> =
+                con.hgetall(Self::make_info_key(user_id)).await?;
+
+            let mut ids: Vec<String> = data
+                .into_iter()
+                .filter(|(id, last_seen)| {
+                    let create_at = infos
+                        .get(id)
+                        .and_then(|v| serde_json::from_str::<SessionInfo>(v).ok())
+                        .map(|v| v.create_at)
+                        .unwrap_or_default();
+                    Self::is_expired(create_at, *last_seen, now, store)
+                })
+                .map(|(id, _)| id)
+                .collect();
+            removed += ids.len() as u64;
+
+            // metadata left behind by sessions removed in other ways
+            let live: Vec<String> = con.zrange(Self::make_key(user_id), 0, -1).await?;
+            ids.extend(infos.into_keys().filter(|id| !live.contains(id)));
+            Self::remove(user_id, &ids, store).await?;
+        }
+        Ok(removed)
+    }
+
+    /// Check the lifetime and idle timeout, sessions without the metadata have no
+    /// creation time and only time out when idle
+    ///
+    #[cfg(feature = "ssr")]
+    fn is_expired(create_at: i64, last_seen: i64, now: i64, store: &Store) -> bool {
+        let lifetime = store.session.lifetime.as_secs() as i64;
+        let idle_timeout = store.session.idle_timeout.as_secs() as i64;
+
+        (lifetime > 0 && create_at > 0 && now - create_at > lifetime)
+            || (idle_timeout > 0 && now - last_seen > idle_timeout)
+    }
+
+    /// Remove the sessions with their metadata
+    ///
+    #[cfg(feature = "ssr")]
+    async fn remove(user_id: i64, ids: &[String], store: &Store) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut con = store.con.clone();
+        let _: () = redis::pipe()
+            .zrem(Self::make_key(user_id), ids)
+            .ignore()
+            .hdel(Self::make_info_key(user_id), ids)
+            .ignore()
+            .query_async(&mut con)
+            .await?;
         Ok(())
     }
 
@@ -141,4 +498,43 @@ impl Session {
     fn make_key(user_id: i64) -> String {
         format!("session:{}", user_id)
     }
+
+    /// Create a key of the session metadata from user_id for redis
+    ///
+    #[cfg(feature = "ssr")]
+    fn make_info_key(user_id: i64) -> String {
+        format!("session-info:{}", user_id)
+    }
+}
+
+// ==================== // UTILS // ==================== //
+
+/// Describe the device of the user agent like `Firefox on Linux`, the browser is
+/// named like in the download analytics
+///
+#[cfg(feature = "ssr")]
+fn device_label(user_agent: &str) -> String {
+    if user_agent.is_empty() {
+        return String::from("Unknown device");
+    }
+
+    let browser = client_family(user_agent);
+
+    let system = [
+        ("Windows", "Windows"),
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(pat, _)| user_agent.contains(pat))
+    .map(|(_, name)| *name);
+
+    match system {
+        Some(system) => format!("{} on {}", browser, system),
+        None => browser.to_owned(),
+    }
 }
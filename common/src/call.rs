@@ -0,0 +1,163 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use crate::{DateTime, Result, Store};
+}}
+
+use serde::{Deserialize, Serialize};
+
+// ==================== // CallStats // ==================== //
+
+/// Quality statistics of a call sampled from the peer connection
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CallStats {
+    /// Round-trip time in milliseconds
+    pub rtt: f64,
+    /// Jitter of the inbound audio in milliseconds
+    pub jitter: f64,
+    /// Percentage of the inbound packets lost
+    pub packet_loss: f64,
+    /// Bitrate of the inbound audio in kbps
+    pub bitrate: f64,
+    /// Type of the selected local candidate, host/srflx/prflx/relay
+    pub candidate_type: String,
+}
+
+impl CallStats {
+    /// Average a list of samples, the candidate type of the last one is kept
+    ///
+    pub fn average(samples: &[CallStats]) -> Option<CallStats> {
+        let last = samples.last()?;
+        let n = samples.len() as f64;
+
+        let mut avg = CallStats {
+            candidate_type: last.candidate_type.clone(),
+            ..Default::default()
+        };
+        for s in samples {
+            avg.rtt += s.rtt / n;
+            avg.jitter += s.jitter / n;
+            avg.packet_loss += s.packet_loss / n;
+            avg.bitrate += s.bitrate / n;
+        }
+        Some(avg)
+    }
+}
+
+// ==================== // CallReport // ==================== //
+
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct CallReport {
+    pub id: i64,
+    pub nickname: String,
+    pub friend_nickname: String,
+    pub duration: i64,
+    pub rtt: f64,
+    pub jitter: f64,
+    pub packet_loss: f64,
+    pub bitrate: f64,
+    pub candidate_type: String,
+    pub create_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CallReports {
+    pub num_calls: i32,
+    pub avg_rtt: f64,
+    pub avg_packet_loss: f64,
+    pub reports: Vec<CallReport>,
+}
+
+#[cfg(feature = "ssr")]
+impl CallReport {
+    /// Get the latest call reports and the averages of all reports
+    ///
+    pub async fn list(limit: i32, store: &Store) -> Result<CallReports> {
+        let reports: Vec<CallReport> = sqlx::query_as(
+            "
+            SELECT c.id, u.nickname, coalesce(f.nickname, '') AS friend_nickname,
+                c.duration, c.rtt, c.jitter, c.packet_loss, c.bitrate,
+                c.candidate_type, c.create_at
+            FROM callreports AS c
+            JOIN users AS u ON u.id = c.user_id
+            LEFT JOIN users AS f ON f.id = c.friend_id
+            ORDER BY c.id DESC LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&store.pool)
+        .await?;
+
+        let (num_calls, avg_rtt, avg_packet_loss): (i32, Option<f64>, Option<f64>) =
+            sqlx::query_as("SELECT count(*), avg(rtt), avg(packet_loss) FROM callreports")
+                .fetch_one(&store.pool)
+                .await?;
+
+        Ok(CallReports {
+            num_calls,
+            avg_rtt: avg_rtt.unwrap_or_default(),
+            avg_packet_loss: avg_packet_loss.unwrap_or_default(),
+            reports,
+        })
+    }
+
+    /// Remove the reports created before the time, returns the number removed
+    ///
+    pub async fn clean(before: i64, store: &Store) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM callreports WHERE create_at < $1")
+            .bind(before)
+            .execute(&store.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+// ==================== // InsertCallReportArg // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct InsertCallReportArg {
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 1, message = "Invalid friend id"))
+    )]
+    pub friend_id: i64,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 0, message = "Duration cannot be negative"))
+    )]
+    pub duration: i64,
+    pub stats: CallStats,
+}
+
+impl InsertCallReportArg {
+    /// Save the summary of a finished call in the database
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn insert(&self, user_id: i64, store: &Store) -> Result<()> {
+        let now = DateTime::now().timestamp;
+        let candidate_type = match self.stats.candidate_type.as_str() {
+            "host" | "srflx" | "prflx" | "relay" => self.stats.candidate_type.as_str(),
+            _ => "unknown",
+        };
+
+        sqlx::query(
+            "
+            INSERT INTO callreports (
+                user_id, friend_id, duration, rtt, jitter,
+                packet_loss, bitrate, candidate_type, create_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(user_id)
+        .bind(self.friend_id)
+        .bind(self.duration)
+        .bind(self.stats.rtt)
+        .bind(self.stats.jitter)
+        .bind(self.stats.packet_loss)
+        .bind(self.stats.bitrate)
+        .bind(candidate_type)
+        .bind(now)
+        .execute(&store.pool)
+        .await?;
+        Ok(())
+    }
+}
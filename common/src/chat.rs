@@ -7,7 +7,30 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::{DateTime, FileMeta, Friend, User};
+use crate::{DateTime, FileMeta, Friend, ImageMeta, User};
+
+/// Remove the outdated messages at the tail of a room, the newest message comes
+/// first. The script runs at once, so the list cannot be trimmed by a new message
+/// in between
+#[cfg(feature = "ssr")]
+const CLEAN_SCRIPT: &str = r#"
+local values = redis.call('LRANGE', KEYS[1], 0, -1)
+local count = 0
+for i = #values, 1, -1 do
+    local ok, msg = pcall(cjson.decode, values[i])
+    if ok and type(msg) == 'table' and tonumber(msg.send_at)
+        and tonumber(msg.send_at) >= tonumber(ARGV[1]) then
+        break
+    end
+    count = count + 1
+end
+if count == #values then
+    redis.call('DEL', KEYS[1])
+elseif count > 0 then
+    redis.call('LTRIM', KEYS[1], 0, #values - count - 1)
+end
+return count
+"#;
 
 // ==================== // Event // ==================== //
 
@@ -41,6 +64,15 @@ pub enum Event {
     ReceiveAnswer(String),
     SendCandidate(i64, Uuid, IceCandidate),
     ReceiveCandidate(IceCandidate),
+    // handle room call
+    JoinRoomCall(String),
+    LeaveRoomCall(String),
+    ReceiveRoomCall(String, Vec<CallPeer>),
+    ReceiveCallMembers(String, Vec<CallPeer>),
+    SendSignal(PeerSignal),
+    ReceiveSignal(PeerSignal),
+    // the session is ended by the server, with the reason
+    ReceiveKick(String),
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -60,6 +92,29 @@ pub struct IceCandidate {
     pub sdp_m_line_index: u16,
 }
 
+/// A websocket client taking part in a room call
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallPeer {
+    pub user_id: i64,
+    pub client_id: Uuid,
+}
+
+/// Signalling data exchanged between two peers of a room call,
+/// the peer is the target when sending and the source when receiving
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeerSignal {
+    pub room_id: String,
+    pub peer: CallPeer,
+    pub data: SignalData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SignalData {
+    Offer(String),
+    Answer(String),
+    Candidate(IceCandidate),
+}
+
 // ==================== // Message // ==================== //
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -68,14 +123,27 @@ pub enum MessageKind {
     Text = 1,
     Image = 2,
     File = 3,
+    Voice = 4,
+    Media = 5,
+    Album = 6,
 }
 
+/// Max files sent together in an album
+pub const MAX_ALBUM_FILES: usize = 10;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Message {
     pub id: Uuid,
     pub content: String,
     pub url: String,
     pub kind: MessageKind,
+    #[serde(default)]
+    pub duration: u32,
+    #[serde(default)]
+    pub image: Option<ImageMeta>,
+    /// Attachments of an album, empty for other kinds
+    #[serde(default)]
+    pub files: Vec<FileMeta>,
     pub divide: bool,
     pub room_id: String,
     pub sender: User,
@@ -91,6 +159,9 @@ impl Message {
             content,
             url: String::new(),
             kind: MessageKind::Text,
+            duration: 0,
+            image: None,
+            files: Vec::new(),
             divide: false,
             room_id,
             sender,
@@ -103,6 +174,10 @@ impl Message {
     pub fn file(room_id: String, sender: User, file_meta: FileMeta) -> Self {
         let kind = if file_meta.img {
             MessageKind::Image
+        } else if file_meta.duration > 0 {
+            MessageKind::Voice
+        } else if file_meta.media {
+            MessageKind::Media
         } else {
             MessageKind::File
         };
@@ -112,6 +187,9 @@ impl Message {
             content: file_meta.name,
             url: file_meta.url,
             kind,
+            duration: file_meta.duration,
+            image: file_meta.image,
+            files: Vec::new(),
             divide: false,
             room_id,
             sender,
@@ -119,6 +197,42 @@ impl Message {
         }
     }
 
+    /// Create a new album message with several files
+    ///
+    pub fn album(room_id: String, sender: User, files: Vec<FileMeta>) -> Self {
+        let images = files.iter().filter(|v| v.img).count();
+        let content = if images == files.len() {
+            format!("{} photos", files.len())
+        } else {
+            format!("{} files", files.len())
+        };
+
+        Self {
+            id: Uuid::new_v4(),
+            content,
+            url: String::new(),
+            kind: MessageKind::Album,
+            duration: 0,
+            image: None,
+            files,
+            divide: false,
+            room_id,
+            sender,
+            send_at: DateTime::now().timestamp,
+        }
+    }
+
+    /// Get the urls of the shared files in the message
+    ///
+    #[cfg(feature = "ssr")]
+    pub fn urls(&self) -> Vec<&str> {
+        match self.kind {
+            MessageKind::Text => Vec::new(),
+            MessageKind::Album => self.files.iter().map(|v| v.url.as_str()).collect(),
+            _ => vec![self.url.as_str()],
+        }
+    }
+
     /// Update divide by last send time
     ///
     #[cfg(feature = "ssr")]
@@ -130,6 +244,9 @@ impl Message {
             content: self.content,
             url: self.url,
             kind: self.kind,
+            duration: self.duration,
+            image: self.image,
+            files: self.files,
             divide,
             room_id: self.room_id,
             sender: self.sender,
@@ -153,6 +270,36 @@ impl Message {
         Ok(())
     }
 
+    /// Remove the messages sent before the time in all rooms, returns the number removed
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn clean(before: i64, store: &Store) -> Result<u64> {
+        let user_ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM users")
+            .fetch_all(&store.pool)
+            .await?;
+        let fsps: Vec<FriendShip> = sqlx::query_as("SELECT * FROM friendships")
+            .fetch_all(&store.pool)
+            .await?;
+        let room_ids = user_ids
+            .into_iter()
+            .map(|(id,)| Room::user_room_id(id))
+            .chain(fsps.iter().map(Room::friend_room_id));
+
+        let mut con = store.con.clone();
+        let mut removed = 0_u64;
+        for room_id in room_ids {
+            let outdated: u64 = redis::cmd("EVAL")
+                .arg(CLEAN_SCRIPT)
+                .arg(1)
+                .arg(&room_id)
+                .arg(before)
+                .query_async(&mut con)
+                .await?;
+            removed += outdated;
+        }
+        Ok(removed)
+    }
+
     /// Get a list of messages from redis
     ///
     #[cfg(feature = "ssr")]
@@ -223,6 +370,37 @@ impl Room {
     pub fn friend_room_id(fsp: &FriendShip) -> String {
         format!("chats:room-{}-{}", fsp.id0, fsp.id1)
     }
+
+    /// Check if the user is a member of the room, a friendship's room
+    /// lasts as long as the friendship is accepted
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn is_member(user_id: i64, room_id: &str, store: &Store) -> Result<bool> {
+        if room_id == Self::user_room_id(user_id) {
+            return Ok(true);
+        }
+
+        let ids = room_id
+            .strip_prefix("chats:room-")
+            .and_then(|v| v.split_once('-'))
+            .and_then(|(id0, id1)| Some((id0.parse::<i64>().ok()?, id1.parse::<i64>().ok()?)));
+        let Some((id0, id1)) = ids else {
+            return Ok(false);
+        };
+        if user_id != id0 && user_id != id1 {
+            return Ok(false);
+        }
+
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT count(*) FROM friendships WHERE id0 = $1 AND id1 = $2 AND status = $3",
+        )
+        .bind(id0)
+        .bind(id1)
+        .bind(FriendStatus::Accepted)
+        .fetch_one(&store.pool)
+        .await?;
+        Ok(count > 0)
+    }
 }
 
 #[cfg(feature = "ssr")]
@@ -0,0 +1,183 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::{ApiToken, Error, Result};
+
+/// Cookie of the token, readable by `public/csrf.js` which sends it back
+const CSRF_KEY: &str = "csrf";
+
+/// Header which must repeat the token of the cookie
+const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Length of the token
+const CSRF_LENGTH: usize = 32;
+
+/// Paths of plain html forms which do not use the session, they are only checked
+/// for the origin
+const EXEMPT_PATHS: [&str; 1] = ["/s/"];
+
+/// Prefix of the api for scripts, which needs no token of the cookie once the access
+/// token is verified
+const API_PREFIX: &str = "/api/v1/";
+
+// ==================== // CsrfConfig // ==================== //
+
+/// Origins accepted for the requests which change the state and the sockets
+///
+#[derive(Debug, Clone, Default)]
+pub struct CsrfConfig {
+    /// Origins allowed besides the host of the request, e.g. `https://chat.example.com`
+    pub allowed_origins: Vec<String>,
+    /// Take the host from `X-Forwarded-Host` behind a reverse proxy
+    pub trust_proxy: bool,
+}
+
+// ==================== // CsrfGuard // ==================== //
+
+/// Double-submit protection, a random token is kept in a cookie and each request
+/// which changes the state must send it again in a header. Another site can make
+/// the browser send the cookie but cannot read it
+///
+pub struct CsrfGuard;
+
+impl CsrfGuard {
+    /// Middleware which checks the origin and the token of the requests changing the
+    /// state as well as the origin of the socket upgrades, and gives a token to the
+    /// browsers without one. The access token of the api must already be verified
+    /// into the extensions of the request
+    ///
+    pub async fn middleware(
+        State(config): State<CsrfConfig>,
+        request: Request,
+        next: Next,
+    ) -> Response {
+        let cookie_jar = CookieJar::from_headers(request.headers());
+        let has_token = cookie_jar.get(CSRF_KEY).is_some();
+
+        let path = request.uri().path();
+        let result = if !Self::is_safe(request.method()) {
+            Self::check_origin(request.headers(), &config).and_then(|_| {
+                if Self::is_exempt(&request) {
+                    Ok(())
+                } else {
+                    Self::check_token(request.headers(), &cookie_jar)
+                }
+            })
+        } else if Self::is_upgrade(request.headers()) {
+            // the socket is opened with a GET which the pages of other sites could
+            // send with the cookie of the user
+            Self::check_origin(request.headers(), &config)
+        } else {
+            Ok(())
+        };
+        if let Err(err) = result {
+            log::warn!("request to {} rejected: {}", path, err);
+            return err.into_response();
+        }
+
+        let mut response = next.run(request).await;
+        if !has_token {
+            let cookie = Cookie::build((CSRF_KEY, Self::new_token()))
+                .path("/")
+                .same_site(SameSite::Strict)
+                .secure(true)
+                .permanent()
+                .build();
+            if let Ok(value) = HeaderValue::from_str(&cookie.to_string()) {
+                response.headers_mut().append(header::SET_COOKIE, value);
+            }
+        }
+        response
+    }
+
+    /// Reject the requests sent by the pages of other sites. Requests without an
+    /// origin do not come from a browser, which always sends it across sites
+    ///
+    pub fn check_origin(headers: &HeaderMap, config: &CsrfConfig) -> Result<()> {
+        let origin = headers
+            .get(header::ORIGIN)
+            .or_else(|| headers.get(header::REFERER))
+            .map(|v| v.to_str().map_err(|_| Error::Forbidden))
+            .transpose()?;
+        let origin = match origin {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let (scheme, rest) = origin.split_once("://").ok_or(Error::Forbidden)?;
+        let host = rest.split('/').next().unwrap_or_default();
+        let origin = format!("{}://{}", scheme, host);
+
+        let allowed = config
+            .allowed_origins
+            .iter()
+            .any(|v| v.trim_end_matches('/').eq_ignore_ascii_case(&origin));
+        let same_host =
+            Self::request_host(headers, config).is_some_and(|v| v.eq_ignore_ascii_case(host));
+        if allowed || same_host {
+            Ok(())
+        } else {
+            Err(Error::Forbidden)
+        }
+    }
+
+    /// Ensure the header repeats the token of the cookie
+    ///
+    pub fn check_token(headers: &HeaderMap, cookie_jar: &CookieJar) -> Result<()> {
+        let expected = cookie_jar.get(CSRF_KEY).map(|v| v.value());
+        let token = headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok());
+        match (expected, token) {
+            (Some(expected), Some(token)) if !expected.is_empty() && expected == token => Ok(()),
+            _ => Err(Error::Forbidden),
+        }
+    }
+
+    /// Plain html forms, and the api routes called with a verified access token which
+    /// a browser never sends on its own
+    ///
+    fn is_exempt(request: &Request) -> bool {
+        let path = request.uri().path();
+        if EXEMPT_PATHS.iter().any(|v| path.starts_with(v)) {
+            return true;
+        }
+
+        path.starts_with(API_PREFIX) && request.extensions().get::<ApiToken>().is_some()
+    }
+
+    fn is_upgrade(headers: &HeaderMap) -> bool {
+        headers
+            .get(header::UPGRADE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+    }
+
+    fn is_safe(method: &Method) -> bool {
+        matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+    }
+
+    fn request_host<'a>(headers: &'a HeaderMap, config: &CsrfConfig) -> Option<&'a str> {
+        let forwarded = config
+            .trust_proxy
+            .then(|| headers.get("x-forwarded-host"))
+            .flatten();
+        forwarded
+            .or_else(|| headers.get(header::HOST))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+    }
+
+    fn new_token() -> String {
+        thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(CSRF_LENGTH)
+            .map(char::from)
+            .collect()
+    }
+}
@@ -76,6 +76,43 @@ impl DateTime {
         }
     }
 
+    /// Format the local date of the timestamp as `YYYY-MM-DD`, the value of a date input
+    ///
+    pub fn fmt_date(&self, ts: i64) -> String {
+        let (days, _) = self.get_local_date(ts);
+        let (yr, mo, d) = ymd_from_days(days);
+        format!("{:04}-{:02}-{:02}", yr, mo, d)
+    }
+
+    /// Format the local time of the timestamp as `YYYY-MM-DD HH:MM:SS`
+    ///
+    pub fn fmt_full(&self, ts: i64) -> String {
+        let (days, extra) = self.get_local_date(ts);
+        let (yr, mo, d) = ymd_from_days(days);
+        let (h, m) = hms_from_seconds(extra);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            yr,
+            mo,
+            d,
+            h,
+            m,
+            extra % 60
+        )
+    }
+
+    /// Parse a local date `YYYY-MM-DD` into the Unix timestamp of its midnight,
+    /// the offset is the timezone offset of the client in seconds
+    ///
+    pub fn parse_date(value: &str, offset: i32) -> Option<i64> {
+        let mut parts = value.trim().splitn(3, '-').map(|v| v.parse::<i32>().ok());
+        let (yr, mo, d) = (parts.next()??, parts.next()??, parts.next()??);
+        if !(1..=12).contains(&mo) || !(1..=31).contains(&d) {
+            return None;
+        }
+        Some(days_from_ymd(yr, mo, d) as i64 * 86400 + offset as i64)
+    }
+
     /// Returns days from UNIX_EPOCH and extra seconds
     ///
     fn get_local_date(&self, ts: i64) -> (i32, i32) {
@@ -136,6 +173,20 @@ fn ymd_from_days(days: i32) -> (i32, i32, i32) {
     (year, month, day)
 }
 
+/// Returns number of days since UNIX_EPOCH, the inverse of `ymd_from_days`
+///
+pub(crate) fn days_from_ymd(year: i32, month: i32, day: i32) -> i32 {
+    // shift the year to begin at March so that the leap day is the last one
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
 /// Returns hour/minute/second triple
 ///
 /// extra is number of seconds since begin of today
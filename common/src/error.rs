@@ -10,6 +10,16 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 pub type FnError = ServerFnError<Error>;
+
+/// Messages of the unique violations by the table, SQLite names the columns in the
+/// error, e.g. `UNIQUE constraint failed: users.username`
+#[cfg(feature = "ssr")]
+const UNIQUE_MESSAGES: [(&str, &str); 4] = [
+    ("users.", "Username already exists"),
+    ("invites.", "The invite code already exists"),
+    ("identities.", "The identity is already linked"),
+    ("api_tokens.", "The token already exists"),
+];
 pub type FnResult<T> = core::result::Result<T, ServerFnError<Error>>;
 
 pub type Result<T, E = Error> = core::result::Result<T, E>;
@@ -28,6 +38,12 @@ pub enum Error {
     #[error("Not Found")]
     NotFound, // 404
 
+    #[error("Password change required")]
+    PasswordChange, // 403
+
+    #[error("{0}")]
+    TooManyRequests(String), // 429
+
     #[error("Internal Server Error")]
     InternalServer, // 500
 
@@ -43,6 +59,7 @@ impl FromStr for Error {
             "Unauthorized" => Ok(Self::Unauthorized),
             "Forbidden" => Ok(Self::Forbidden),
             "Not Found" => Ok(Self::NotFound),
+            "Password change required" => Ok(Self::PasswordChange),
             "Internal Server Error" => Ok(Self::InternalServer),
             _ => Ok(Self::BadRequest(s.to_string())),
         }
@@ -55,8 +72,9 @@ impl Error {
         match self {
             Error::BadRequest(_) => StatusCode::BAD_REQUEST,
             Error::Unauthorized => StatusCode::UNAUTHORIZED,
-            Error::Forbidden => StatusCode::FORBIDDEN,
+            Error::Forbidden | Error::PasswordChange => StatusCode::FORBIDDEN,
             Error::NotFound => StatusCode::NOT_FOUND,
+            Error::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             Error::InternalServer | Error::SendError => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -75,7 +93,13 @@ impl From<sqlx::Error> for Error {
         match err {
             sqlx::Error::Database(dbe) => {
                 if dbe.is_unique_violation() {
-                    Self::BadRequest(String::from("Username already exists"))
+                    let columns = dbe.message().rsplit(": ").next().unwrap_or_default();
+                    let msg = UNIQUE_MESSAGES
+                        .iter()
+                        .find(|(table, _)| columns.starts_with(table))
+                        .map(|(_, msg)| *msg)
+                        .unwrap_or("The record already exists");
+                    Self::BadRequest(String::from(msg))
                 } else {
                     log::error!("database: {}", dbe);
                     Self::InternalServer
@@ -148,6 +172,35 @@ impl From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "ssr")]
+impl From<s3::error::S3Error> for Error {
+    fn from(err: s3::error::S3Error) -> Self {
+        match err {
+            s3::error::S3Error::HttpFailWithBody(404, _) => Self::NotFound,
+            _ => {
+                log::error!("s3: {}", err);
+                Self::InternalServer
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        log::error!("reqwest: {}", err);
+        Self::InternalServer
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        log::warn!("jsonwebtoken: {}", err);
+        Self::BadRequest(String::from("The identity token is invalid"))
+    }
+}
+
 #[cfg(feature = "ssr")]
 impl<T> From<tokio::sync::broadcast::error::SendError<T>> for Error {
     fn from(err: tokio::sync::broadcast::error::SendError<T>) -> Self {
@@ -1,10 +1,10 @@
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
     use validator::Validate;
     use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
     use leptos::use_context;
 
-    use crate::{Config, Error, Session, Store, Hub, Result, FnResult, FnError, FileManager};
+    use crate::{ClientInfo, Config, Error, Event, FriendShip, Room, Session, Store, Hub, Result, FnResult, FnError, FileManager, Scheduler};
 }}
 
 use crate::User;
@@ -39,6 +39,28 @@ impl HostExtractor {
     }
 }
 
+// ==================== // ClientIpExtractor // ==================== //
+
+#[cfg(feature = "ssr")]
+pub struct ClientIpExtractor;
+
+#[cfg(feature = "ssr")]
+impl ClientIpExtractor {
+    /// Get the address and user agent of the client, `X-Forwarded-For` is only trusted
+    /// behind a proxy since clients can set it to anything
+    ///
+    pub async fn use_client(trust_proxy: bool) -> FnResult<ClientInfo> {
+        use axum::{extract::ConnectInfo, http::HeaderMap};
+        use leptos_axum::extract;
+        use std::net::SocketAddr;
+
+        let headers: HeaderMap = extract().await.map_err(|_| Error::InternalServer)?;
+        let ConnectInfo(addr): ConnectInfo<SocketAddr> =
+            extract().await.map_err(|_| Error::InternalServer)?;
+        Ok(ClientInfo::new(&headers, addr, trust_proxy))
+    }
+}
+
 // ==================== // StoreExtractor // ==================== //
 
 #[cfg(feature = "ssr")]
@@ -65,6 +87,19 @@ impl ConfigExtractor {
     }
 }
 
+// ==================== // SchedulerExtractor // ==================== //
+
+#[cfg(feature = "ssr")]
+pub struct SchedulerExtractor;
+
+#[cfg(feature = "ssr")]
+impl SchedulerExtractor {
+    pub fn use_scheduler() -> FnResult<Scheduler> {
+        use_context::<Scheduler>()
+            .ok_or_else(|| FnError::ServerError(String::from("failed to get scheduler")))
+    }
+}
+
 // ==================== // HubManager // ==================== //
 
 #[cfg(feature = "ssr")]
@@ -105,7 +140,7 @@ impl HubManager {
         }
 
         let config = ConfigExtractor::use_config()?;
-        let share_size = FileManager::get_shared_size(config).await?;
+        let share_size = FileManager::get_shared_size(store, config).await?;
 
         let rsp = HubData {
             num_feeds,
@@ -118,11 +153,33 @@ impl HubManager {
         Ok(rsp)
     }
 
-    pub fn remove_user(user_id: i64) -> FnResult<()> {
+    /// Close the sockets of the sessions of a user with the reason
+    ///
+    pub fn kick_sessions(user_id: i64, sessions: &[String], reason: &str) -> FnResult<()> {
         let hub = Self::use_hub()?;
+        hub.kick(user_id, Some(sessions), reason);
+        Ok(())
+    }
+
+    /// Close all sockets of a user with the reason and remove the user from the hub
+    ///
+    pub fn kick_user(user_id: i64, reason: &str) -> FnResult<()> {
+        let hub = Self::use_hub()?;
+        hub.kick(user_id, None, reason);
         hub.remove(user_id);
         Ok(())
     }
+
+    /// Open the room of a new friendship and send it to the user if online
+    ///
+    pub async fn add_friend_room(user_id: i64, fsp: FriendShip, store: &Store) -> FnResult<()> {
+        let hub = Self::use_hub()?;
+        let (_, friend) = Room::get(user_id, &fsp, store).await?;
+
+        hub.create_friend_room(fsp);
+        hub.send(user_id, &Event::ReceiveRoom(friend))?;
+        Ok(())
+    }
 }
 
 // ==================== // CookieManager // ==================== //
@@ -168,25 +225,31 @@ impl CookieManager {
     ///
     #[cfg(feature = "ssr")]
     pub fn add_auth(user_id: i64, session: String) -> FnResult<()> {
-        let cookie_jar = CookieJar::new();
+        let config = ConfigExtractor::use_config()?;
+        Self::add_cookie_to_response(Self::auth_cookies(user_id, session, config.session.lifetime))
+    }
 
-        let cookie_jar = cookie_jar.add(
-            Cookie::build((USER_ID_KEY, user_id.to_string()))
-                .path("/")
-                .same_site(SameSite::Strict)
-                .secure(true)
-                .http_only(true)
-                .permanent(),
-        );
-        let cookie_jar = cookie_jar.add(
-            Cookie::build((USER_SESSION_KEY, session))
+    /// Build the auth cookies, for the handlers outside of the server functions. The
+    /// cookies last as long as the session, or forever if it has no lifetime
+    ///
+    #[cfg(feature = "ssr")]
+    pub fn auth_cookies(user_id: i64, session: String, lifetime: Duration) -> CookieJar {
+        let cookie = |name: &'static str, value: String| {
+            let builder = Cookie::build((name, value))
                 .path("/")
                 .same_site(SameSite::Strict)
                 .secure(true)
-                .http_only(true)
-                .permanent(),
-        );
-        Self::add_cookie_to_response(cookie_jar)
+                .http_only(true);
+            if lifetime.is_zero() {
+                builder.permanent()
+            } else {
+                builder.max_age(time::Duration::seconds(lifetime.as_secs() as i64))
+            }
+        };
+
+        CookieJar::new()
+            .add(cookie(USER_ID_KEY, user_id.to_string()))
+            .add(cookie(USER_SESSION_KEY, session))
     }
 
     /// Remove auth data in the cookie
@@ -293,7 +356,18 @@ pub struct AuthExtractor;
 
 #[cfg(feature = "ssr")]
 impl AuthExtractor {
+    /// Get the logged in user, who must have changed the password set by an admin
+    ///
     pub async fn use_auth(refresh: bool, store: &Store) -> FnResult<User> {
+        let user = Self::use_login(refresh, store).await?;
+        user.ensure_password_changed()?;
+        Ok(user)
+    }
+
+    /// Get the logged in user even if the password must be changed, only for
+    /// showing the password change screen
+    ///
+    pub async fn use_login(refresh: bool, store: &Store) -> FnResult<User> {
         let (user_id, session) = CookieManager::get_auth()?;
 
         let user = Session::verify(user_id, session, refresh, &store).await?;
@@ -1,15 +1,18 @@
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
-    use std::{io, ffi::OsStr, path::{Path, PathBuf}, time::SystemTime, sync::Arc};
+    use std::{io, collections::HashSet, ffi::OsStr, path::{Path, PathBuf}, sync::Arc};
     use axum::{body::Bytes, BoxError};
-    use tokio::{fs::File, io::BufWriter};
-    use tokio_util::io::StreamReader;
+    use tokio::{fs::File, io::{AsyncWriteExt, BufWriter}};
     use futures_util::{Stream, TryStreamExt};
     use multer::Field;
-    use uuid::Uuid;
     use qrcode::QrCode;
-    use image::Luma;
+    use image::{
+        codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat, ImageReader,
+        Luma,
+    };
     use rand::{distributions::Alphanumeric, thread_rng, Rng};
-    use crate::{DateTime, Result, Error, Config, Store};
+    use sha2::{Digest, Sha256};
+    use crate::{ByteStream, DateTime, Result, Error, Config, Room, Store, UploadSession};
+    use crate::user::{hash_password, verify_password};
 }}
 
 use serde::{Deserialize, Serialize};
@@ -19,8 +22,42 @@ pub struct FileMeta {
     pub name: String,
     pub url: String,
     pub img: bool,
+    /// Duration of a voice message in seconds, zero for other files
+    #[serde(default)]
+    pub duration: u32,
+    /// Thumbnail and size of an image, none if it cannot be decoded
+    #[serde(default)]
+    pub image: Option<ImageMeta>,
+    /// Audio or video played inline
+    #[serde(default)]
+    pub media: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImageMeta {
+    pub thumb: String,
+    pub width: u32,
+    pub height: u32,
+    /// Dominant color in hex used as the placeholder
+    pub color: String,
+}
+
+/// Max width and height of a thumbnail
+#[cfg(feature = "ssr")]
+const THUMB_SIZE: u32 = 480;
+
+/// Max duration of a voice message in seconds
+pub const MAX_VOICE_DURATION: u32 = 300;
+
+/// Audio types allowed for voice messages and their file extensions
+#[cfg(feature = "ssr")]
+const VOICE_TYPES: [(&str, &str); 4] = [
+    ("audio/webm", "webm"),
+    ("audio/ogg", "ogg"),
+    ("audio/mp4", "m4a"),
+    ("audio/mpeg", "mp3"),
+];
+
 // ==================== // FileManager // ==================== //
 
 #[cfg(feature = "ssr")]
@@ -33,11 +70,13 @@ impl FileManager {
     pub async fn save_avatar(
         user_id: i64,
         field: Field<'static>,
+        store: &Store,
         config: Arc<Config>,
     ) -> Result<String> {
         if !is_image(&field) {
             return Err(Error::BadRequest(String::from("Not an image file")));
         }
+        let limit = StorageUsage::limit(user_id, config.max_avatar_size, store, &config).await?;
 
         // get file extension
         let (_, ext) = extract_filename(&field)?;
@@ -55,96 +94,210 @@ impl FileManager {
             )
         };
 
-        // save stream to file
-        let avatar_path = get_fullpath(&config, &url);
-        stream_to_file(avatar_path, field).await?;
+        // save stream to a temporary file and move it to the storage
+        let tmp_path = temp_path();
+        let size = stream_to_file(tmp_path.clone(), field, limit).await?;
+
+        if config.strip_metadata {
+            let path = tmp_path.clone();
+            let stripped = tokio::task::spawn_blocking(move || strip_metadata(&path))
+                .await
+                .map_err(|_| Error::InternalServer)
+                .and_then(|v| v);
+            if let Err(err) = stripped {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(err);
+            }
+        }
+
+        store.storage.put_file(&url, &tmp_path).await?;
+        StorageUsage::record(user_id, &url, size, store).await?;
 
         Ok(url)
     }
 
     /// Remove all unused avatar
     ///
-    pub async fn clean_avatars(user_id: i64, avatar: &str, config: Arc<Config>) -> Result<()> {
+    pub async fn clean_avatars(
+        user_id: i64,
+        avatar: &str,
+        store: &Store,
+        config: Arc<Config>,
+    ) -> Result<()> {
         // this pattern is associated with the avatar url
         let pat = format!("img{}-", user_id);
-        let avatar_path = get_fullpath(&config, &config.avatar_dir);
 
-        let mut entries = tokio::fs::read_dir(avatar_path).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            if let Some(name) = entry.file_name().to_str() {
-                if name.starts_with(&pat) && !avatar.contains(name) {
-                    tokio::fs::remove_file(entry.path()).await?;
-                }
+        for object in store.storage.list(&config.avatar_dir).await? {
+            let name = object.url.rsplit('/').next().unwrap_or_default();
+            if name.starts_with(&pat) && !avatar.contains(name) {
+                store.storage.delete(&object.url).await?;
+                StorageUsage::release(&object.url, store).await?;
             }
         }
         Ok(())
     }
 
-    /// Save the uploaded file in share directory
+    /// Remove the avatars no user is using, returns the size removed. Recent files
+    /// are kept since the profile may not be saved yet
+    ///
+    pub async fn clean_orphan_avatars(store: &Store, config: &Arc<Config>) -> Result<String> {
+        let avatars: Vec<(String,)> = sqlx::query_as("SELECT avatar FROM users")
+            .fetch_all(&store.pool)
+            .await?;
+        let recent = DateTime::now().timestamp - 60 * 60;
+
+        let mut sz = 0_u64;
+        for object in store.storage.list(&config.avatar_dir).await? {
+            let name = object.url.rsplit('/').next().unwrap_or_default();
+            let used = avatars.iter().any(|(avatar,)| avatar.contains(name));
+            if used || object.modified >= recent {
+                continue;
+            }
+            store.storage.delete(&object.url).await?;
+            StorageUsage::release(&object.url, store).await?;
+            sz += object.size;
+        }
+        Ok(stringify_size(sz as f64))
+    }
+
+    /// Save the uploaded file in share directory, identical files are stored once
     ///
-    pub async fn save_shared_file(field: Field<'static>, config: Arc<Config>) -> Result<FileMeta> {
+    pub async fn save_shared_file(
+        user_id: i64,
+        field: Field<'static>,
+        store: &Store,
+        config: Arc<Config>,
+    ) -> Result<FileMeta> {
         // get file name from field
         let (name, ext) = extract_filename(&field)?;
+        let limit = StorageUsage::limit(user_id, config.max_file_size, store, &config).await?;
 
         // check if the file is an image
         let img = is_image(&field);
 
-        // generate new filename and url
-        let str9 = random_string(9);
-        let now = DateTime::now().timestamp;
+        // save stream to a temporary file
+        let tmp_path = temp_path();
+        stream_to_file(tmp_path.clone(), field, limit).await?;
 
-        let url = if ext.is_empty() {
-            format!("{}/s{}-{}", &config.share_dir, str9, now)
-        } else {
-            format!("{}/s{}-{}.{}", &config.share_dir, str9, now, ext)
-        };
+        Self::share_local_file(user_id, name, &ext, img, &tmp_path, store, &config).await
+    }
 
-        // save stream to file
-        let file_path = get_fullpath(&config, &url);
-        stream_to_file(file_path, field).await?;
+    /// Save a local file in share directory as a blob, the local file is removed afterwards
+    ///
+    pub(crate) async fn share_local_file(
+        user_id: i64,
+        name: String,
+        ext: &str,
+        img: bool,
+        path: &Path,
+        store: &Store,
+        config: &Arc<Config>,
+    ) -> Result<FileMeta> {
+        let blob = Blob::save(path, &config.share_dir, ext, img, store, config).await?;
+        StorageUsage::record(user_id, &blob.path, blob.size as u64, store).await?;
 
-        let file_meta = FileMeta { name, url, img };
+        let file_meta = FileMeta {
+            name,
+            image: blob.image_meta(),
+            media: !img && is_media(&blob.path),
+            url: blob.path,
+            img,
+            duration: 0,
+        };
         Ok(file_meta)
     }
 
-    /// Get the size of share directory
+    /// Save the recorded voice message in share directory
     ///
-    pub async fn get_shared_size(config: Arc<Config>) -> Result<String> {
-        let mut sz = 0_u64;
+    pub async fn save_voice(
+        user_id: i64,
+        field: Field<'static>,
+        duration: u32,
+        store: &Store,
+        config: Arc<Config>,
+    ) -> Result<FileMeta> {
+        if duration == 0 || duration > MAX_VOICE_DURATION {
+            return Err(Error::BadRequest(format!(
+                "Voice message must be between 1 and {} seconds",
+                MAX_VOICE_DURATION
+            )));
+        }
 
-        let shared_path = get_fullpath(&config, &config.share_dir);
-        let mut entries = tokio::fs::read_dir(shared_path).await?;
+        // the recorded type may carry codecs, e.g. "audio/webm;codecs=opus"
+        let mime = field
+            .content_type()
+            .map(|v| v.essence_str().to_owned())
+            .unwrap_or_default();
+        let Some((_, ext)) = VOICE_TYPES.iter().find(|(ty, _)| *ty == mime) else {
+            return Err(Error::BadRequest(String::from("Unsupported audio type")));
+        };
 
-        while let Some(entry) = entries.next_entry().await? {
-            if let Ok(metadata) = entry.metadata().await {
-                sz += metadata.len();
-            }
-        }
+        // save stream to a blob named by its hash
+        let limit = StorageUsage::limit(user_id, config.max_file_size, store, &config).await?;
+        let tmp_path = temp_path();
+        stream_to_file(tmp_path.clone(), field, limit).await?;
+
+        let blob = Blob::save(&tmp_path, &config.share_dir, ext, false, store, &config).await?;
+        StorageUsage::record(user_id, &blob.path, blob.size as u64, store).await?;
+
+        let file_meta = FileMeta {
+            name: String::from("Voice message"),
+            url: blob.path,
+            img: false,
+            duration,
+            image: None,
+            media: false,
+        };
+        Ok(file_meta)
+    }
+
+    /// Get the size of share directory
+    ///
+    pub async fn get_shared_size(store: &Store, config: Arc<Config>) -> Result<String> {
+        let objects = store.storage.list(&config.share_dir).await?;
+        let sz: u64 = objects.iter().map(|v| v.size).sum();
         Ok(stringify_size(sz as f64))
     }
 
-    /// Remove outdated shared files
+    /// Remove outdated shared files, a blob is only removed when nothing references it
     ///
-    pub async fn clean_outdated_files(config: Arc<Config>) -> Result<String> {
+    pub async fn clean_outdated_files(store: &Store, config: Arc<Config>) -> Result<String> {
         let mut sz = 0_u64;
-        let now = SystemTime::now();
-
-        let shared_path = get_fullpath(&config, &config.share_dir);
-        let mut entries = tokio::fs::read_dir(shared_path).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            if let Ok(metadata) = entry.metadata().await {
-                let Ok(create_at) = metadata.created() else {
-                    continue;
-                };
-                let Ok(dur) = now.duration_since(create_at) else {
-                    continue;
-                };
-                if dur > config.expire_duration {
-                    tokio::fs::remove_file(entry.path()).await?;
-                    sz += metadata.len();
-                }
+        let expire_at = DateTime::now().timestamp - config.expire_duration.as_secs() as i64;
+
+        // messages older than the expire duration no longer hold their files
+        sqlx::query("DELETE FROM blob_refs WHERE owner LIKE 'message:%' AND create_at < $1")
+            .bind(expire_at)
+            .execute(&store.pool)
+            .await?;
+
+        // recent blobs are kept since the message may not be sent yet
+        let blobs: Vec<Blob> = sqlx::query_as(
+            "
+            SELECT * FROM blobs AS b
+            WHERE b.create_at < $1
+                AND NOT EXISTS (SELECT 1 FROM blob_refs AS r WHERE r.path = b.path)",
+        )
+        .bind(expire_at)
+        .fetch_all(&store.pool)
+        .await?;
+
+        for blob in blobs {
+            sz += blob.remove(store).await?;
+        }
+
+        // partial files of abandoned uploads
+        UploadSession::clean_abandoned(store).await?;
+
+        // files not tracked by any blob are cleaned by their modified time
+        let tracked = Blob::list_urls(store).await?;
+        for object in store.storage.list(&config.share_dir).await? {
+            if tracked.contains(&object.url) || object.modified >= expire_at {
+                continue;
             }
+            store.storage.delete(&object.url).await?;
+            StorageUsage::release(&object.url, store).await?;
+            sz += object.size;
         }
         Ok(stringify_size(sz as f64))
     }
@@ -153,12 +306,42 @@ impl FileManager {
 // ==================== // FileLink // ==================== //
 
 #[derive(Serialize, Deserialize, Clone)]
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
 pub struct FileLink {
     pub id: i64,
     pub name: String,
     pub link: String,
     pub qrlink: String,
+    /// Path of the public download page, e.g. `/s/{token}`
+    pub share: String,
+    /// Unix timestamp after which the link expires, zero if it never expires
+    pub expire_at: i64,
+    /// Zero if the downloads are not limited
+    pub max_downloads: i64,
+    pub downloads: i64,
+    pub has_password: bool,
+    pub revoked: bool,
+    /// Successful downloads of the last days by UTC day, the oldest first
+    #[serde(default)]
+    pub daily: Vec<i64>,
+    /// Failed download attempts of the last days
+    #[serde(default)]
+    pub failed: i64,
+}
+
+impl FileLink {
+    /// Describe whether the file can still be downloaded
+    ///
+    pub fn status(&self, now: i64) -> &'static str {
+        if self.revoked {
+            "Revoked"
+        } else if self.expire_at > 0 && self.expire_at <= now {
+            "Expired"
+        } else if self.max_downloads > 0 && self.downloads >= self.max_downloads {
+            "Limit reached"
+        } else {
+            "Active"
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -173,6 +356,7 @@ impl FileLink {
     /// Save a file with link and qr code link
     ///
     pub async fn save(
+        user_id: i64,
         field: Field<'static>,
         host: String,
         store: &Store,
@@ -180,66 +364,77 @@ impl FileLink {
     ) -> Result<()> {
         // get file name from field
         let (name, ext) = extract_filename(&field)?;
+        let limit = StorageUsage::limit(user_id, config.max_archive_size, store, &config).await?;
 
-        // create new file name and link
-        let str7 = random_string(7);
-        let key = Uuid::new_v4();
-        let link = if ext.is_empty() {
-            format!("{}/{}{}", &config.archive_dir, str7, key)
-        } else {
-            format!("{}/{}{}.{}", &config.archive_dir, str7, key, ext)
-        };
+        // save stream to a blob named by its hash
+        let tmp_path = temp_path();
+        stream_to_file(tmp_path.clone(), field, limit).await?;
 
-        // save stream to file
-        let file_path = get_fullpath(&config, &link);
-        stream_to_file(file_path, field).await?;
+        let blob = Blob::save(&tmp_path, &config.archive_dir, &ext, false, store, &config).await?;
+        StorageUsage::record(user_id, &blob.path, blob.size as u64, store).await?;
+        let link = blob.path;
 
-        // convert file full link to qr code
-        let full_link = format!("{}{}", host, link);
+        // convert the public download page to qr code
+        let token = random_string(24);
+        let full_link = format!("{}{}", host, share_path(&token));
         let qrcode = QrCode::new(&full_link).map_err(|_| Error::InternalServer)?;
 
         // create qr image file link
         let now = DateTime::now().timestamp;
-        let qrlink = format!("{}/qr{}-{}.png", &config.archive_dir, str7, now);
+        let qrlink = format!("{}/qr{}-{}.png", &config.archive_dir, random_string(7), now);
 
         // save qr image
         let image_file = qrcode.render::<Luma<u8>>().build();
-        let image_path = get_fullpath(&config, &qrlink);
+        let image_path = temp_path().with_extension("png");
         image_file
-            .save(image_path)
+            .save(&image_path)
             .map_err(|_| Error::InternalServer)?;
+        store.storage.put_file(&qrlink, &image_path).await?;
 
         // save file information in database
-        sqlx::query("INSERT INTO filelinks (name, link, qrlink) VALUES ($1, $2, $3)")
-            .bind(&name)
-            .bind(&link)
-            .bind(&qrlink)
-            .execute(&store.pool)
-            .await?;
+        let (link_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO filelinks (name, link, qrlink, token) VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(&name)
+        .bind(&link)
+        .bind(&qrlink)
+        .bind(&token)
+        .fetch_one(&store.pool)
+        .await?;
+
+        Blob::reference(&link, &format!("link:{}", link_id), store).await?;
         Ok(())
     }
 
     /// Delete the file and links in database
     ///
     #[cfg(feature = "ssr")]
-    pub async fn delete(link_id: i64, store: &Store, config: Arc<Config>) -> Result<()> {
-        let fl: FileLink =
-            sqlx::query_as("SELECT id, name, link, qrlink FROM filelinks WHERE id = $1")
+    pub async fn delete(link_id: i64, store: &Store) -> Result<()> {
+        let (link, qrlink): (String, String) =
+            sqlx::query_as("SELECT link, qrlink FROM filelinks WHERE id = $1")
                 .bind(&link_id)
                 .fetch_one(&store.pool)
                 .await?;
 
-        // delete file and qr image
-        let file_path = get_fullpath(&config, &fl.link);
-        if tokio::fs::try_exists(&file_path).await? {
-            tokio::fs::remove_file(file_path).await?;
-        }
-
-        let qr_image = get_fullpath(&config, &fl.qrlink);
-        if tokio::fs::try_exists(&qr_image).await? {
-            tokio::fs::remove_file(qr_image).await?;
+        // delete the file once no message or link references it
+        Blob::unreference(&format!("link:{}", link_id), store).await?;
+        match Blob::get_by_path(&link, store).await? {
+            Some(blob) => {
+                if !blob.is_referenced(store).await? {
+                    blob.remove(store).await?;
+                }
+            }
+            None => {
+                store.storage.delete(&link).await?;
+                StorageUsage::release(&link, store).await?;
+            }
         }
+        store.storage.delete(&qrlink).await?;
 
+        sqlx::query("DELETE FROM link_downloads WHERE link_id = $1")
+            .bind(&link_id)
+            .execute(&store.pool)
+            .await?;
         sqlx::query("DELETE FROM filelinks WHERE id = $1")
             .bind(&link_id)
             .execute(&store.pool)
@@ -252,7 +447,8 @@ impl FileLink {
 
         let result: Vec<ListLinksRow> = sqlx::query_as(
             "
-            SELECT id, name, link, qrlink, count(*) OVER() AS total
+            SELECT id, name, link, qrlink, token, password, expire_at, max_downloads,
+                downloads, revoked, count(*) OVER() AS total
             FROM filelinks LIMIT 5 OFFSET $1",
         )
         .bind(offset)
@@ -265,7 +461,31 @@ impl FileLink {
         };
         let pages = (total + 4) / 5;
 
-        let filelinks: Vec<FileLink> = result.into_iter().map(|row| row.into()).collect();
+        let mut filelinks: Vec<FileLink> = result.into_iter().map(|row| row.into()).collect();
+
+        // downloads of the links on the page grouped by day
+        let today = DateTime::now().timestamp / 86400;
+        let since = today - LINK_HISTORY_DAYS as i64 + 1;
+        let stats: Vec<(i64, i64, bool, i64)> = sqlx::query_as(
+            "
+            SELECT link_id, create_at / 86400 AS day, success, count(*)
+            FROM link_downloads WHERE create_at >= $1
+            GROUP BY link_id, day, success",
+        )
+        .bind(since * 86400)
+        .fetch_all(&store.pool)
+        .await?;
+
+        for link in filelinks.iter_mut() {
+            link.daily = vec![0; LINK_HISTORY_DAYS];
+            for (_, day, success, count) in stats.iter().filter(|v| v.0 == link.id) {
+                if !success {
+                    link.failed += count;
+                } else if let Some(v) = link.daily.get_mut((day - since) as usize) {
+                    *v += count;
+                }
+            }
+        }
 
         Ok(FileLinks {
             total,
@@ -273,6 +493,57 @@ impl FileLink {
             filelinks,
         })
     }
+
+    /// Check the link of the token and count the download, returns the url and the name
+    /// of the file. A missing or incorrect password is unauthorized
+    ///
+    pub async fn access(
+        token: &str,
+        password: Option<&str>,
+        store: &Store,
+    ) -> Result<(String, String)> {
+        let row: Option<LinkAccessRow> = sqlx::query_as(
+            "
+            SELECT id, name, link, password, expire_at, revoked
+            FROM filelinks WHERE token = $1",
+        )
+        .bind(token)
+        .fetch_optional(&store.pool)
+        .await?;
+        let row = match row {
+            Some(v) if !v.revoked => v,
+            _ => return Err(Error::NotFound),
+        };
+
+        let now = DateTime::now().timestamp;
+        if row.expire_at > 0 && row.expire_at <= now {
+            return Err(Error::BadRequest(String::from("The link has expired")));
+        }
+        if !row.password.is_empty() {
+            match password {
+                Some(v) if verify_password(&row.password, v).is_ok() => {}
+                _ => return Err(Error::Unauthorized),
+            }
+        }
+
+        // count the download unless the limit is reached by a concurrent request
+        let counted = sqlx::query(
+            "
+            UPDATE filelinks SET downloads = downloads + 1
+            WHERE id = $1 AND (max_downloads = 0 OR downloads < max_downloads)",
+        )
+        .bind(row.id)
+        .execute(&store.pool)
+        .await?
+        .rows_affected();
+        if counted == 0 {
+            return Err(Error::BadRequest(String::from(
+                "The download limit of the link is reached",
+            )));
+        }
+
+        Ok((row.link, row.name))
+    }
 }
 
 #[cfg(feature = "ssr")]
@@ -282,9 +553,26 @@ struct ListLinksRow {
     name: String,
     link: String,
     qrlink: String,
+    token: String,
+    password: String,
+    expire_at: i64,
+    max_downloads: i64,
+    downloads: i64,
+    revoked: bool,
     total: Option<i32>,
 }
 
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+struct LinkAccessRow {
+    id: i64,
+    name: String,
+    link: String,
+    password: String,
+    expire_at: i64,
+    revoked: bool,
+}
+
 #[cfg(feature = "ssr")]
 impl From<ListLinksRow> for FileLink {
     fn from(v: ListLinksRow) -> Self {
@@ -293,7 +581,636 @@ impl From<ListLinksRow> for FileLink {
             name: v.name,
             link: v.link,
             qrlink: v.qrlink,
+            share: share_path(&v.token),
+            expire_at: v.expire_at,
+            max_downloads: v.max_downloads,
+            downloads: v.downloads,
+            has_password: !v.password.is_empty(),
+            revoked: v.revoked,
+            daily: Vec::new(),
+            failed: 0,
+        }
+    }
+}
+
+/// Number of days in the download history of a link
+///
+#[cfg(feature = "ssr")]
+const LINK_HISTORY_DAYS: usize = 14;
+
+// ==================== // LinkDownload // ==================== //
+
+/// A download attempt of a QR link
+///
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+pub struct LinkDownload {
+    pub link_id: i64,
+    pub name: String,
+    pub client: String,
+    pub success: bool,
+    pub create_at: i64,
+}
+
+#[cfg(feature = "ssr")]
+impl LinkDownload {
+    /// Record a download attempt of the link with the token, unknown tokens are ignored
+    ///
+    pub async fn record(token: &str, user_agent: &str, success: bool, store: &Store) -> Result<()> {
+        sqlx::query(
+            "
+            INSERT INTO link_downloads (link_id, client, success, create_at)
+            SELECT id, $1, $2, $3 FROM filelinks WHERE token = $4",
+        )
+        .bind(client_family(user_agent))
+        .bind(success)
+        .bind(DateTime::now().timestamp)
+        .bind(token)
+        .execute(&store.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Export all download attempts as CSV, the newest first
+    ///
+    pub async fn export_csv(store: &Store) -> Result<String> {
+        let rows: Vec<LinkDownload> = sqlx::query_as(
+            "
+            SELECT d.link_id, f.name, d.client, d.success, d.create_at
+            FROM link_downloads d JOIN filelinks f ON f.id = d.link_id
+            ORDER BY d.create_at DESC",
+        )
+        .fetch_all(&store.pool)
+        .await?;
+
+        let dt = DateTime::now();
+        let mut csv = String::from("link_id,name,time_utc,client,success\r\n");
+        for row in rows {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\r\n",
+                row.link_id,
+                csv_field(&row.name),
+                dt.fmt_full(row.create_at),
+                row.client,
+                row.success
+            ));
+        }
+        Ok(csv)
+    }
+}
+
+/// Coarse family of the client from the user agent, the order matters since most
+/// browsers claim to be the others as well
+///
+#[cfg(feature = "ssr")]
+pub(crate) fn client_family(user_agent: &str) -> &'static str {
+    let ua = user_agent.to_ascii_lowercase();
+    if ua.is_empty() {
+        "Unknown"
+    } else if ["bot", "crawler", "spider", "preview"]
+        .iter()
+        .any(|v| ua.contains(v))
+    {
+        "Bot"
+    } else if ua.starts_with("curl") || ua.starts_with("wget") {
+        "CLI"
+    } else if ua.contains("edg/") {
+        "Edge"
+    } else if ua.contains("opr/") || ua.contains("opera") {
+        "Opera"
+    } else if ua.contains("firefox") || ua.contains("fxios") {
+        "Firefox"
+    } else if ua.contains("chrome") || ua.contains("crios") {
+        "Chrome"
+    } else if ua.contains("safari") {
+        "Safari"
+    } else {
+        "Other"
+    }
+}
+
+/// Quote a CSV field and defuse spreadsheet formulas
+///
+#[cfg(feature = "ssr")]
+fn csv_field(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@']) {
+        true => format!("'{}", value),
+        false => value.to_owned(),
+    };
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Path of the public download page of a link
+///
+#[cfg(feature = "ssr")]
+fn share_path(token: &str) -> String {
+    format!("/s/{}", token)
+}
+
+// ==================== // UpdateLinkArg // ==================== //
+
+/// Access settings of a link edited by admins
+///
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct UpdateLinkArg {
+    #[cfg_attr(feature = "ssr", validate(range(min = 1, message = "Invalid link id")))]
+    pub link_id: i64,
+    /// Last local day of the link as `YYYY-MM-DD`, empty if it never expires
+    pub expire_date: String,
+    /// Timezone offset of the client in seconds
+    pub offset: i32,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 0, message = "Invalid download limit"))
+    )]
+    pub max_downloads: i64,
+    /// New password, empty to keep the current one
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(max = 128, message = "The password is too long"))
+    )]
+    pub password: String,
+    /// Remove the password so that anyone with the link can download
+    pub clear_password: bool,
+    pub revoked: bool,
+}
+
+impl UpdateLinkArg {
+    /// Update the access settings in the database
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn call(&self, store: &Store) -> Result<()> {
+        let expire_at = match self.expire_date.trim() {
+            "" => 0,
+            date => {
+                let midnight = DateTime::parse_date(date, self.offset)
+                    .ok_or(Error::BadRequest(String::from("Invalid expiry date")))?;
+                // the link is available until the end of the day
+                midnight + 86400
+            }
+        };
+
+        let result = sqlx::query(
+            "UPDATE filelinks SET expire_at = $1, max_downloads = $2, revoked = $3 WHERE id = $4",
+        )
+        .bind(expire_at)
+        .bind(self.max_downloads)
+        .bind(self.revoked)
+        .bind(self.link_id)
+        .execute(&store.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        let password = if self.clear_password {
+            Some(String::new())
+        } else if !self.password.is_empty() {
+            Some(hash_password(&self.password)?)
+        } else {
+            None
+        };
+        if let Some(password) = password {
+            sqlx::query("UPDATE filelinks SET password = $1 WHERE id = $2")
+                .bind(password)
+                .bind(self.link_id)
+                .execute(&store.pool)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+// ==================== // Blob // ==================== //
+
+/// A stored file named by the SHA-256 hash of its content, messages and
+/// links reference it by the owner, e.g. `message:{id}` or `link:{id}`. The same
+/// content is stored once in each directory and with each extension, so that a
+/// file keeps the access rules and the type of its own upload
+///
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+pub struct Blob {
+    pub hash: String,
+    pub path: String,
+    pub size: i64,
+    /// Thumbnail and size of an image in json
+    pub image: Option<String>,
+    pub create_at: i64,
+}
+
+#[cfg(feature = "ssr")]
+impl Blob {
+    /// Save the local file in the directory of the storage, an existing blob with
+    /// the same content, directory and extension is returned instead of storing the
+    /// file twice
+    ///
+    async fn save(
+        tmp_path: &Path,
+        dir: &str,
+        ext: &str,
+        img: bool,
+        store: &Store,
+        config: &Arc<Config>,
+    ) -> Result<Self> {
+        // the metadata is stripped first so the hash matches the stored file
+        let strip = img && config.strip_metadata;
+        let path = tmp_path.to_owned();
+        let hashed = tokio::task::spawn_blocking(move || {
+            if strip {
+                strip_metadata(&path)?;
+            }
+            hash_file(&path).map_err(Error::from)
+        })
+        .await
+        .map_err(|_| Error::InternalServer)
+        .and_then(|v| v);
+
+        let (hash, size) = match hashed {
+            Ok(v) => v,
+            Err(err) => {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(err);
+            }
+        };
+
+        let (name, path) = if ext.is_empty() {
+            (hash.clone(), format!("{}/{}", dir, hash))
+        } else {
+            (
+                format!("{}-{}", hash, ext),
+                format!("{}/{}.{}", dir, hash, ext),
+            )
+        };
+
+        if let Some(blob) = Self::get_by_path(&path, store).await? {
+            tokio::fs::remove_file(&tmp_path).await?;
+            return Ok(blob);
+        }
+
+        // the thumbnail is created before the file moves to the storage
+        let image = if img {
+            Self::create_image(&name, tmp_path, store, config).await
+        } else {
+            None
+        };
+        store.storage.put_file(&path, tmp_path).await?;
+
+        // a concurrent upload of the same content may have won the race
+        let now = DateTime::now().timestamp;
+        sqlx::query(
+            "
+            INSERT INTO blobs (hash, path, size, image, create_at) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (path) DO NOTHING",
+        )
+        .bind(&hash)
+        .bind(&path)
+        .bind(size as i64)
+        .bind(image.as_ref().map(serde_json::to_string).transpose()?)
+        .bind(now)
+        .execute(&store.pool)
+        .await?;
+
+        Self::get_by_path(&path, store)
+            .await?
+            .ok_or(Error::InternalServer)
+    }
+
+    /// Get the blob by the url of the file
+    ///
+    pub async fn get_by_path(path: &str, store: &Store) -> Result<Option<Self>> {
+        let blob = sqlx::query_as("SELECT * FROM blobs WHERE path = $1")
+            .bind(path)
+            .fetch_optional(&store.pool)
+            .await?;
+        Ok(blob)
+    }
+
+    /// Get the thumbnail and size of an image blob
+    ///
+    pub fn image_meta(&self) -> Option<ImageMeta> {
+        self.image
+            .as_ref()
+            .and_then(|v| serde_json::from_str::<ImageMeta>(v).ok())
+    }
+
+    /// Create a thumbnail of the local image file in share directory, named after
+    /// the blob
+    ///
+    async fn create_image(
+        name: &str,
+        file_path: &Path,
+        store: &Store,
+        config: &Arc<Config>,
+    ) -> Option<ImageMeta> {
+        let thumb_path = temp_path();
+        let (file, thumb) = (file_path.to_owned(), thumb_path.clone());
+        let (ext, width, height, color) =
+            tokio::task::spawn_blocking(move || create_thumbnail(file, thumb))
+                .await
+                .ok()
+                .flatten()?;
+
+        let thumb_url = format!("{}/t{}.{}", &config.share_dir, name, ext);
+        store
+            .storage
+            .put_file(&thumb_url, &thumb_path.with_extension(ext))
+            .await
+            .ok()?;
+
+        Some(ImageMeta {
+            thumb: thumb_url,
+            width,
+            height,
+            color,
+        })
+    }
+
+    /// Reference the blob of the url by the owner, urls not stored as blobs are ignored
+    ///
+    pub async fn reference(url: &str, owner: &str, store: &Store) -> Result<()> {
+        sqlx::query(
+            "
+            INSERT INTO blob_refs (path, owner, create_at)
+            SELECT path, $2, $3 FROM blobs WHERE path = $1
+            ON CONFLICT (path, owner) DO NOTHING",
+        )
+        .bind(url)
+        .bind(owner)
+        .bind(DateTime::now().timestamp)
+        .execute(&store.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove all references of the owner
+    ///
+    pub async fn unreference(owner: &str, store: &Store) -> Result<()> {
+        sqlx::query("DELETE FROM blob_refs WHERE owner = $1")
+            .bind(owner)
+            .execute(&store.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Check if a message or link still references the blob
+    ///
+    async fn is_referenced(&self, store: &Store) -> Result<bool> {
+        let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM blob_refs WHERE path = $1")
+            .bind(&self.path)
+            .fetch_one(&store.pool)
+            .await?;
+        Ok(count > 0)
+    }
+
+    /// Get the urls of all blobs and thumbnails
+    ///
+    async fn list_urls(store: &Store) -> Result<HashSet<String>> {
+        let blobs: Vec<Blob> = sqlx::query_as("SELECT * FROM blobs")
+            .fetch_all(&store.pool)
+            .await?;
+
+        let mut urls = HashSet::new();
+        for blob in blobs {
+            if let Some(meta) = blob.image_meta() {
+                urls.insert(meta.thumb);
+            }
+            urls.insert(blob.path);
+        }
+        Ok(urls)
+    }
+
+    /// Delete the file, thumbnail and record of the blob, returns the bytes removed
+    ///
+    async fn remove(self, store: &Store) -> Result<u64> {
+        let mut urls = vec![self.path.clone()];
+        if let Some(meta) = self.image_meta() {
+            urls.push(meta.thumb);
+        }
+
+        let mut sz = 0_u64;
+        for url in urls {
+            if let Some(size) = store.storage.size(&url).await? {
+                store.storage.delete(&url).await?;
+                sz += size;
+            }
+        }
+
+        sqlx::query("DELETE FROM blobs WHERE path = $1")
+            .bind(&self.path)
+            .execute(&store.pool)
+            .await?;
+        StorageUsage::release(&self.path, store).await?;
+        RoomFile::release(&self.path, store).await?;
+        Ok(sz)
+    }
+
+    /// Check the stored files against their hashes, returns the urls of
+    /// missing or corrupted files
+    ///
+    pub async fn verify(store: &Store) -> Result<Vec<String>> {
+        let blobs: Vec<Blob> = sqlx::query_as("SELECT * FROM blobs")
+            .fetch_all(&store.pool)
+            .await?;
+
+        let mut corrupted = Vec::new();
+        for blob in blobs {
+            let hashed = match store.storage.get(&blob.path).await {
+                Ok(stream) => hash_stream(stream).await.ok(),
+                Err(_) => None,
+            };
+            if hashed.as_ref() != Some(&blob.hash) {
+                corrupted.push(blob.path);
+            }
+        }
+        Ok(corrupted)
+    }
+}
+
+// ==================== // RoomFile // ==================== //
+
+/// Rooms a shared file was sent to, the file can only be downloaded
+/// by its uploader and the members of these rooms
+///
+#[cfg(feature = "ssr")]
+pub struct RoomFile;
+
+#[cfg(feature = "ssr")]
+impl RoomFile {
+    /// Record the room of a file sent in a message, the sender must have access to
+    /// the file so that a known url cannot be shared into another room
+    ///
+    pub async fn record(user_id: i64, room_id: &str, url: &str, store: &Store) -> Result<()> {
+        let url = Self::source_url(url, store).await?;
+        if !Self::authorize(user_id, &url, store).await? {
+            return Err(Error::Forbidden);
+        }
+
+        sqlx::query(
+            "
+            INSERT INTO room_files (room_id, url, create_at) VALUES ($1, $2, $3)
+            ON CONFLICT (url, room_id) DO NOTHING",
+        )
+        .bind(room_id)
+        .bind(&url)
+        .bind(DateTime::now().timestamp)
+        .execute(&store.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Check if the user uploaded the file or is a member of a room it was sent to,
+    /// derived files like thumbnails follow the file they were created from
+    ///
+    pub async fn authorize(user_id: i64, url: &str, store: &Store) -> Result<bool> {
+        let url = Self::source_url(url, store).await?;
+
+        let (uploads,): (i64,) =
+            sqlx::query_as("SELECT count(*) FROM uploads WHERE user_id = $1 AND url = $2")
+                .bind(user_id)
+                .bind(&url)
+                .fetch_one(&store.pool)
+                .await?;
+        if uploads > 0 {
+            return Ok(true);
+        }
+
+        let room_ids: Vec<(String,)> =
+            sqlx::query_as("SELECT room_id FROM room_files WHERE url = $1")
+                .bind(&url)
+                .fetch_all(&store.pool)
+                .await?;
+        for (room_id,) in room_ids {
+            if Room::is_member(user_id, &room_id, store).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Get the url of the file a thumbnail was created from, other urls are returned as is
+    ///
+    async fn source_url(url: &str, store: &Store) -> Result<String> {
+        let path: Option<(String,)> =
+            sqlx::query_as("SELECT path FROM blobs WHERE json_extract(image, '$.thumb') = $1")
+                .bind(url)
+                .fetch_optional(&store.pool)
+                .await?;
+        Ok(path.map(|(v,)| v).unwrap_or_else(|| url.to_owned()))
+    }
+
+    /// Remove the rooms of a deleted file
+    ///
+    async fn release(url: &str, store: &Store) -> Result<()> {
+        sqlx::query("DELETE FROM room_files WHERE url = $1")
+            .bind(url)
+            .execute(&store.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+// ==================== // StorageUsage // ==================== //
+
+/// Bytes stored by a user and the quota of the user
+///
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct StorageUsage {
+    pub used: u64,
+    pub quota: u64,
+}
+
+impl StorageUsage {
+    /// Returns the percentage of the quota used
+    ///
+    pub fn percent(&self) -> f64 {
+        if self.quota == 0 {
+            100.0
+        } else {
+            (self.used as f64 / self.quota as f64 * 100.0).min(100.0)
+        }
+    }
+
+    /// Returns the usage in a readable format
+    ///
+    pub fn label(&self) -> String {
+        format!(
+            "{} of {} used",
+            stringify_size(self.used as f64),
+            stringify_size(self.quota as f64)
+        )
+    }
+
+    /// Get the storage usage of a user, a quota of zero means the default one
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn get(user_id: i64, store: &Store, config: &Config) -> Result<Self> {
+        let (used, quota): (i64, i64) = sqlx::query_as(
+            "
+            SELECT
+                (SELECT coalesce(sum(size), 0) FROM uploads WHERE user_id = $1),
+                quota
+            FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_one(&store.pool)
+        .await?;
+
+        let quota = if quota > 0 {
+            quota as u64 * 1_000_000
+        } else {
+            config.default_quota
+        };
+        Ok(Self {
+            used: used as u64,
+            quota,
+        })
+    }
+
+    /// Returns the max bytes a user can upload for a single file
+    ///
+    #[cfg(feature = "ssr")]
+    pub(crate) async fn limit(
+        user_id: i64,
+        max_size: u64,
+        store: &Store,
+        config: &Config,
+    ) -> Result<u64> {
+        let usage = Self::get(user_id, store, config).await?;
+        if usage.used >= usage.quota {
+            return Err(Error::BadRequest(String::from("Storage quota exceeded")));
         }
+        Ok(max_size.min(usage.quota - usage.used))
+    }
+
+    /// Record an uploaded file of the user, a file uploaded again is counted once
+    ///
+    #[cfg(feature = "ssr")]
+    async fn record(user_id: i64, url: &str, size: u64, store: &Store) -> Result<()> {
+        sqlx::query(
+            "
+            INSERT INTO uploads (user_id, url, size, create_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, url) DO NOTHING",
+        )
+        .bind(user_id)
+        .bind(url)
+        .bind(size as i64)
+        .bind(DateTime::now().timestamp)
+        .execute(&store.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Release the space of a removed file
+    ///
+    #[cfg(feature = "ssr")]
+    async fn release(url: &str, store: &Store) -> Result<()> {
+        sqlx::query("DELETE FROM uploads WHERE url = $1")
+            .bind(url)
+            .execute(&store.pool)
+            .await?;
+        Ok(())
     }
 }
 
@@ -316,38 +1233,217 @@ impl FileInfo {
 
 // ==================== // UTILS // ==================== //
 
+/// Save the stream to a file and returns the size, the upload aborts
+/// and the partial file is removed once the size exceeds the limit
+///
 #[cfg(feature = "ssr")]
-async fn stream_to_file<S, E>(path: PathBuf, stream: S) -> Result<()>
+async fn stream_to_file<S, E>(path: PathBuf, stream: S, limit: u64) -> Result<u64>
 where
     S: Stream<Item = Result<Bytes, E>>,
     E: Into<BoxError>,
 {
-    async {
-        // convert the stream into an `AsyncRead`.
-        let body_with_io_error = stream.map_err(|err| io::Error::new(io::ErrorKind::Other, err));
-        let body_reader = StreamReader::new(body_with_io_error);
-        futures_util::pin_mut!(body_reader);
+    let result = async {
+        futures_util::pin_mut!(stream);
 
-        // copy the body into the file.
-        let mut file = BufWriter::new(File::create(path).await?);
-        tokio::io::copy(&mut body_reader, &mut file).await?;
+        let mut size = 0_u64;
+        let mut file = BufWriter::new(File::create(&path).await?);
 
-        Ok::<_, io::Error>(())
+        while let Some(chunk) = stream.try_next().await.map_err(|err| {
+            let err: BoxError = err.into();
+            log::error!("upload stream: {}", err);
+            Error::BadRequest(String::from("Failed to receive the file"))
+        })? {
+            size += chunk.len() as u64;
+            if size > limit {
+                return Err(Error::BadRequest(format!(
+                    "File exceeds the limit of {}",
+                    stringify_size(limit as f64)
+                )));
+            }
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        Ok(size)
     }
-    .await?;
+    .await;
 
-    Ok(())
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+    result
 }
 
-/// Get the full path of the file or directory
+/// Resize the image to a thumbnail, which is saved as png if the image
+/// has alpha or jpeg otherwise. Returns the extension of the thumbnail,
+/// the size and dominant color of the original image
 ///
 #[cfg(feature = "ssr")]
-fn get_fullpath(config: &Arc<Config>, url: &str) -> PathBuf {
-    if url.starts_with("/") {
-        Path::new(&config.site_root).join(&url[1..])
+fn create_thumbnail(path: PathBuf, thumb: PathBuf) -> Option<(&'static str, u32, u32, String)> {
+    let (img, _) = open_image(&path)?;
+
+    let (width, height) = (img.width(), img.height());
+    let small = if width > THUMB_SIZE || height > THUMB_SIZE {
+        img.thumbnail(THUMB_SIZE, THUMB_SIZE)
     } else {
-        Path::new(&config.site_root).join(url)
+        img
+    };
+    let color = dominant_color(&small);
+
+    let (ext, format, small) = if small.color().has_alpha() {
+        (
+            "png",
+            ImageFormat::Png,
+            DynamicImage::from(small.to_rgba8()),
+        )
+    } else {
+        (
+            "jpg",
+            ImageFormat::Jpeg,
+            DynamicImage::from(small.to_rgb8()),
+        )
+    };
+
+    small
+        .save_with_format(thumb.with_extension(ext), format)
+        .map_err(|err| log::error!("thumbnail: {}", err))
+        .ok()?;
+
+    Some((ext, width, height, color))
+}
+
+/// Re-encode the image in place so that EXIF and other metadata are dropped,
+/// the orientation is applied to the pixels first to keep it upright.
+/// Other formats are left untouched unless they carry EXIF, such as HEIC or TIFF
+/// from phones, and an image which cannot be re-encoded is rejected rather than
+/// kept with its metadata
+///
+#[cfg(feature = "ssr")]
+fn strip_metadata(path: &Path) -> Result<()> {
+    let format = ImageReader::open(path)?.with_guessed_format()?.format();
+    if !matches!(
+        format,
+        Some(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)
+    ) {
+        if has_exif(path) {
+            return Err(Error::BadRequest(String::from(
+                "The metadata of this image format cannot be removed",
+            )));
+        }
+        return Ok(());
+    }
+
+    let rejected = || Error::BadRequest(String::from("The image cannot be processed"));
+    let (img, format) = open_image(path).ok_or_else(rejected)?;
+
+    // write to a temporary file so a failure never loses the original
+    let tmp = path.with_extension("strip");
+    let result = match format {
+        ImageFormat::Jpeg => std::fs::File::create(&tmp)
+            .map_err(image::ImageError::IoError)
+            .and_then(|file| {
+                let encoder = JpegEncoder::new_with_quality(io::BufWriter::new(file), 90);
+                DynamicImage::from(img.to_rgb8()).write_with_encoder(encoder)
+            }),
+        _ => img.save_with_format(&tmp, format),
+    };
+
+    match result
+        .map_err(|err| err.to_string())
+        .and_then(|_| std::fs::rename(&tmp, path).map_err(|err| err.to_string()))
+    {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            log::error!("strip metadata: {}", err);
+            let _ = std::fs::remove_file(&tmp);
+            Err(rejected())
+        }
+    }
+}
+
+/// Check if the file has EXIF data in any container known to the reader
+///
+#[cfg(feature = "ssr")]
+fn has_exif(path: &Path) -> bool {
+    std::fs::File::open(path)
+        .map(|file| {
+            exif::Reader::new()
+                .read_from_container(&mut io::BufReader::new(file))
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Decode an image and apply its EXIF orientation
+///
+#[cfg(feature = "ssr")]
+fn open_image(path: &Path) -> Option<(DynamicImage, ImageFormat)> {
+    let reader = ImageReader::open(path).ok()?.with_guessed_format().ok()?;
+    let format = reader.format()?;
+    let img = reader.decode().ok()?;
+
+    let orientation = std::fs::File::open(path)
+        .ok()
+        .and_then(|file| {
+            exif::Reader::new()
+                .read_from_container(&mut io::BufReader::new(file))
+                .ok()
+        })
+        .and_then(|data| {
+            data.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1);
+
+    let img = match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    };
+    Some((img, format))
+}
+
+/// Get the average color of the image in hex
+///
+#[cfg(feature = "ssr")]
+fn dominant_color(img: &DynamicImage) -> String {
+    let pixel = img.resize_exact(1, 1, FilterType::Triangle).to_rgb8();
+    let [r, g, b] = pixel.get_pixel(0, 0).0;
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Get the SHA-256 hash in hex and the size of the file
+///
+#[cfg(feature = "ssr")]
+pub(crate) fn hash_file(path: &Path) -> io::Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Get the SHA-256 hash in hex of a stream from the storage
+///
+#[cfg(feature = "ssr")]
+async fn hash_stream(mut stream: ByteStream) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.try_next().await? {
+        hasher.update(&chunk);
     }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Get a new path in the temp directory, uploads are processed there
+/// before moving to the storage
+///
+#[cfg(feature = "ssr")]
+fn temp_path() -> PathBuf {
+    std::env::temp_dir().join(format!("chat-{}.part", random_string(12)))
 }
 
 #[cfg(feature = "ssr")]
@@ -375,21 +1471,7 @@ fn is_image(field: &Field<'static>) -> bool {
 #[cfg(feature = "ssr")]
 fn extract_filename(field: &Field<'static>) -> Result<(String, String)> {
     if let Some(file_name) = field.file_name() {
-        let fname = Path::new(file_name.trim());
-
-        // parse name and extension
-        let name = fname
-            .file_stem()
-            .and_then(OsStr::to_str)
-            .unwrap_or_default()
-            .to_owned();
-        let ext = fname
-            .extension()
-            .and_then(OsStr::to_str)
-            .unwrap_or_default()
-            .to_owned();
-
-        Ok((name, ext))
+        Ok(split_filename(file_name))
     } else {
         Err(Error::BadRequest(String::from(
             "File name not found in Field",
@@ -397,7 +1479,78 @@ fn extract_filename(field: &Field<'static>) -> Result<(String, String)> {
     }
 }
 
-fn stringify_size(size: f64) -> String {
+/// Split the file name into the name and extension
+///
+#[cfg(feature = "ssr")]
+pub(crate) fn split_filename(file_name: &str) -> (String, String) {
+    let fname = Path::new(file_name.trim());
+
+    // parse name and extension
+    let name = fname
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_owned();
+    let ext = fname
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_owned();
+
+    (name, ext)
+}
+
+/// Guess the content type of a file by the extension of the url
+///
+pub fn content_type(url: &str) -> &'static str {
+    let ext = url
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "aac" => "audio/aac",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Check if the file can be played by an audio or video element
+///
+pub fn is_media(url: &str) -> bool {
+    let ty = content_type(url);
+    ty.starts_with("audio/") || ty.starts_with("video/")
+}
+
+/// Percent-encode a component of an url, or a file name for `filename*`
+///
+pub fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub(crate) fn stringify_size(size: f64) -> String {
     if size < 1000.0 {
         format!("{:.1} B", size)
     } else if size < 1e6 {
@@ -408,3 +1561,68 @@ fn stringify_size(size: f64) -> String {
         format!("{:.1} GB", size / 1e9)
     }
 }
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    /// Copy a fixture to the temp directory, the stripping works in place
+    ///
+    fn fixture(bytes: &[u8]) -> PathBuf {
+        let path = temp_path().with_extension("jpg");
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn read_exif(path: &Path) -> std::result::Result<exif::Exif, exif::Error> {
+        let file = std::fs::File::open(path).unwrap();
+        exif::Reader::new().read_from_container(&mut io::BufReader::new(file))
+    }
+
+    #[test]
+    fn strips_gps_data() {
+        let path = fixture(include_bytes!("../tests/fixtures/gps.jpg"));
+        let exif = read_exif(&path).expect("the fixture has exif data");
+        assert!(exif
+            .get_field(exif::Tag::GPSLatitude, exif::In::PRIMARY)
+            .is_some());
+
+        strip_metadata(&path).unwrap();
+
+        assert!(read_exif(&path).is_err());
+        let img = image::open(&path).unwrap();
+        assert_eq!((img.width(), img.height()), (16, 8));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn applies_orientation() {
+        let path = fixture(include_bytes!("../tests/fixtures/rotated.jpg"));
+
+        strip_metadata(&path).unwrap();
+
+        assert!(read_exif(&path).is_err());
+        let img = image::open(&path).unwrap();
+        assert_eq!((img.width(), img.height()), (8, 16));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_metadata_it_cannot_strip() {
+        let path = temp_path().with_extension("tiff");
+        std::fs::write(&path, include_bytes!("../tests/fixtures/gps.tiff")).unwrap();
+        assert!(read_exif(&path).is_ok());
+
+        assert!(matches!(strip_metadata(&path), Err(Error::BadRequest(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_broken_image() {
+        let bytes = include_bytes!("../tests/fixtures/gps.jpg");
+        let path = fixture(&bytes[..bytes.len() / 2]);
+
+        assert!(matches!(strip_metadata(&path), Err(Error::BadRequest(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,10 +2,10 @@ use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
     sync::{Arc, Mutex},
 };
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, oneshot};
 use uuid::Uuid;
 
-use crate::{Error, Event, FeedData, FriendShip, HungUpReson, Message, Result, Room};
+use crate::{CallPeer, Error, Event, FeedData, FriendShip, HungUpReson, Message, Result, Room};
 
 // ==================== // Hub // ==================== //
 
@@ -16,22 +16,37 @@ pub struct Hub(Arc<HubInner>);
 struct HubInner {
     users: Mutex<HashMap<i64, UserState>>,
     feeds: Mutex<HashMap<String, Feed>>,
+    calls: Mutex<HashMap<String, Vec<CallPeer>>>,
+    clients: Mutex<HashMap<Uuid, ClientSession>>,
 }
 
 impl Hub {
-    /// Register a new client of websocket connection
+    /// Register a new client of websocket connection, the client is closed when the
+    /// reason is sent to the kick channel
     ///
     pub fn register(
         &self,
         user_id: i64,
         client_id: Uuid,
+        session: String,
         rooms: &Vec<Room>,
         tx: broadcast::Sender<Vec<u8>>,
+        kick: oneshot::Sender<String>,
     ) {
         let room_ids: HashSet<String> = rooms.iter().map(|r| r.id.clone()).collect();
 
         let mut users = self.0.users.lock().unwrap();
         let mut feeds = self.0.feeds.lock().unwrap();
+        let mut clients = self.0.clients.lock().unwrap();
+
+        clients.insert(
+            client_id,
+            ClientSession {
+                user_id,
+                session,
+                kick: Some(kick),
+            },
+        );
 
         for room_id in &room_ids {
             match feeds.entry(room_id.clone()) {
@@ -60,6 +75,7 @@ impl Hub {
     pub fn unregister(&self, user_id: i64, client_id: &Uuid) {
         let mut users = self.0.users.lock().unwrap();
         let mut feeds = self.0.feeds.lock().unwrap();
+        self.0.clients.lock().unwrap().remove(client_id);
 
         if let Some(user) = users.get(&user_id) {
             for room_id in &user.room_ids {
@@ -83,6 +99,25 @@ impl Hub {
     /// Remove all clients of a user
     ///
     pub fn remove(&self, user_id: i64) {
+        // the other members of the calls are told that the clients left
+        let client_ids: Vec<Uuid> = {
+            let clients = self.0.clients.lock().unwrap();
+            clients
+                .iter()
+                .filter(|(_, c)| c.user_id == user_id)
+                .map(|(client_id, _)| *client_id)
+                .collect()
+        };
+        for client_id in client_ids {
+            let peer = CallPeer { user_id, client_id };
+            for (room_id, members) in self.leave_calls(peer) {
+                let event = Event::ReceiveCallMembers(room_id.clone(), members);
+                if let Err(err) = self.publish(&room_id, &event) {
+                    log::error!("failed to publish call members: {}", err);
+                }
+            }
+        }
+
         let user_room_id = Room::user_room_id(user_id);
 
         let mut users = self.0.users.lock().unwrap();
@@ -108,6 +143,21 @@ impl Hub {
         }
     }
 
+    /// Close the clients of the sessions of a user with the reason, or all clients of
+    /// the user if no sessions are given. Returns the number of clients closed
+    ///
+    pub fn kick(&self, user_id: i64, sessions: Option<&[String]>, reason: &str) -> usize {
+        let mut clients = self.0.clients.lock().unwrap();
+
+        clients
+            .values_mut()
+            .filter(|c| c.user_id == user_id)
+            .filter(|c| sessions.map_or(true, |v| v.contains(&c.session)))
+            .filter_map(|c| c.kick.take())
+            .filter(|kick| kick.send(reason.to_owned()).is_ok())
+            .count()
+    }
+
     /// Create a room for a friendship
     ///
     pub fn create_friend_room(&self, fsp: FriendShip) {
@@ -165,24 +215,24 @@ impl Hub {
     pub fn broadcast(&self, msg: Message) -> Result<Message> {
         let mut feeds = self.0.feeds.lock().unwrap();
         if let Some(feed) = feeds.get_mut(&msg.room_id) {
-            let event = Event::Receive(msg.update(feed.last_send_at));
-
-            let msg = serde_json::to_vec(&event)?;
-            for sender in feed.clients.values() {
-                sender.send(msg.clone())?;
-            }
-
-            if let Event::Receive(message) = event {
-                feed.last_send_at = message.send_at;
-                Ok(message)
-            } else {
-                Err(Error::InternalServer)
-            }
+            feed.broadcast(msg)
         } else {
             Err(Error::BadRequest(String::from("The room doesn't exists!")))
         }
     }
 
+    /// Broadcast a message in a room which may have no online clients, the sender must
+    /// be checked to be a member of the room
+    ///
+    pub fn deliver(&self, msg: Message) -> Result<Message> {
+        let mut feeds = self.0.feeds.lock().unwrap();
+        if let Some(feed) = feeds.get_mut(&msg.room_id) {
+            feed.broadcast(msg)
+        } else {
+            Ok(msg.update(0))
+        }
+    }
+
     /// Send message to a user's all clients
     ///
     pub fn send(&self, user_id: i64, event: &Event) -> Result<()> {
@@ -259,6 +309,104 @@ impl Hub {
         Ok(())
     }
 
+    /// Add a client to the call of a room and returns the members before joining
+    ///
+    pub fn join_call(&self, room_id: &str, peer: CallPeer) -> Result<Vec<CallPeer>> {
+        let mut users = self.0.users.lock().unwrap();
+        let feeds = self.0.feeds.lock().unwrap();
+        let mut calls = self.0.calls.lock().unwrap();
+
+        let is_member = feeds
+            .get(room_id)
+            .map(|feed| feed.clients.contains_key(&peer.client_id))
+            .unwrap_or(false);
+        if !is_member {
+            return Err(Error::BadRequest(String::from("The room doesn't exists!")));
+        }
+
+        let Some(user) = users.get_mut(&peer.user_id) else {
+            return Err(Error::InternalServer);
+        };
+        if !user.callable {
+            return Err(Error::BadRequest(String::from(
+                "The user is in another call",
+            )));
+        }
+        user.callable = false;
+
+        let members = calls.entry(room_id.to_owned()).or_default();
+        let peers = members.clone();
+        members.push(peer);
+
+        Ok(peers)
+    }
+
+    /// Remove a client from the call of a room and returns the remaining members
+    ///
+    pub fn leave_call(&self, room_id: &str, peer: CallPeer) -> Option<Vec<CallPeer>> {
+        let mut users = self.0.users.lock().unwrap();
+        let mut calls = self.0.calls.lock().unwrap();
+
+        let members = calls.get_mut(room_id)?;
+        let pos = members.iter().position(|v| *v == peer)?;
+        members.remove(pos);
+        let members = members.clone();
+
+        if members.is_empty() {
+            calls.remove(room_id);
+        }
+        if let Some(user) = users.get_mut(&peer.user_id) {
+            user.callable = true;
+        }
+
+        Some(members)
+    }
+
+    /// Remove a client from all room calls and returns the remaining members of each room
+    ///
+    pub fn leave_calls(&self, peer: CallPeer) -> Vec<(String, Vec<CallPeer>)> {
+        let room_ids: Vec<String> = {
+            let calls = self.0.calls.lock().unwrap();
+            calls
+                .iter()
+                .filter(|(_, members)| members.contains(&peer))
+                .map(|(room_id, _)| room_id.clone())
+                .collect()
+        };
+
+        room_ids
+            .into_iter()
+            .filter_map(|room_id| {
+                self.leave_call(&room_id, peer)
+                    .map(|members| (room_id, members))
+            })
+            .collect()
+    }
+
+    /// Returns whether the two clients are in the call of the same room
+    ///
+    pub fn in_same_call(&self, room_id: &str, peer0: &CallPeer, peer1: &CallPeer) -> bool {
+        let calls = self.0.calls.lock().unwrap();
+        calls
+            .get(room_id)
+            .map(|members| members.contains(peer0) && members.contains(peer1))
+            .unwrap_or(false)
+    }
+
+    /// Send an event to all clients of a room
+    ///
+    pub fn publish(&self, room_id: &str, event: &Event) -> Result<()> {
+        let msg = serde_json::to_vec(&event)?;
+
+        let feeds = self.0.feeds.lock().unwrap();
+        if let Some(feed) = feeds.get(room_id) {
+            for sender in feed.clients.values() {
+                sender.send(msg.clone())?;
+            }
+        }
+        Ok(())
+    }
+
     /// Get first n feeds in the Hub
     ///
     pub fn get_feeds(&self, num: usize) -> (i32, Vec<FeedData>) {
@@ -318,6 +466,16 @@ impl UserState {
     }
 }
 
+// ==================== // ClientSession // ==================== //
+
+/// The login session of a client, taken to close the client when the session ends
+///
+struct ClientSession {
+    user_id: i64,
+    session: String,
+    kick: Option<oneshot::Sender<String>>,
+}
+
 // ==================== // Feed // ==================== //
 
 #[derive(Clone)]
@@ -333,4 +491,74 @@ impl Feed {
             clients,
         }
     }
+
+    /// Send the message to all clients of the feed
+    ///
+    fn broadcast(&mut self, msg: Message) -> Result<Message> {
+        let event = Event::Receive(msg.update(self.last_send_at));
+
+        let msg = serde_json::to_vec(&event)?;
+        for sender in self.clients.values() {
+            sender.send(msg.clone())?;
+        }
+
+        if let Event::Receive(message) = event {
+            self.last_send_at = message.send_at;
+            Ok(message)
+        } else {
+            Err(Error::InternalServer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(id: &str) -> Room {
+        Room {
+            key: Uuid::new_v4(),
+            id: id.to_owned(),
+            name: String::new(),
+            cover: String::new(),
+            unreads: 0,
+            content: String::new(),
+            send_at: 0,
+        }
+    }
+
+    #[test]
+    fn removed_user_leaves_calls() {
+        let hub = Hub::default();
+        let rooms = vec![room("friend")];
+        let peer0 = CallPeer {
+            user_id: 1,
+            client_id: Uuid::new_v4(),
+        };
+        let peer1 = CallPeer {
+            user_id: 2,
+            client_id: Uuid::new_v4(),
+        };
+
+        let (tx0, _rx0) = broadcast::channel(8);
+        let (tx1, mut rx1) = broadcast::channel(8);
+        let (kick0, _kick_rx0) = oneshot::channel();
+        let (kick1, _kick_rx1) = oneshot::channel();
+        hub.register(1, peer0.client_id, String::from("s0"), &rooms, tx0, kick0);
+        hub.register(2, peer1.client_id, String::from("s1"), &rooms, tx1, kick1);
+        hub.join_call("friend", peer0).unwrap();
+        hub.join_call("friend", peer1).unwrap();
+
+        hub.remove(1);
+
+        assert!(!hub.in_same_call("friend", &peer0, &peer1));
+        let data = rx1.try_recv().expect("the call members are not published");
+        match serde_json::from_slice::<Event>(&data).unwrap() {
+            Event::ReceiveCallMembers(room_id, members) => {
+                assert_eq!(room_id, "friend");
+                assert_eq!(members, vec![peer1]);
+            }
+            _ => panic!("unexpected event"),
+        }
+    }
 }
@@ -0,0 +1,267 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use rand::{distributions::Alphanumeric, thread_rng, Rng};
+    use crate::{ClientInfo, DateTime, Error, FriendShip, InsertUserArg, Result, Session, Store};
+    use crate::totp::qrcode_data_url;
+    use crate::user::{validate_password_strength, UserEntity};
+}}
+
+use crate::{User, UserRole};
+use serde::{Deserialize, Serialize};
+
+/// Length of the generated invite codes
+#[cfg(feature = "ssr")]
+const CODE_LENGTH: usize = 10;
+
+// ==================== // Invite // ==================== //
+
+/// A code issued by an admin for self-service registration
+///
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct Invite {
+    pub id: i64,
+    pub code: String,
+    /// Nickname of the admin who issued the code
+    pub creator: String,
+    /// Role of the users registered with the code
+    pub role: UserRole,
+    /// Zero if the uses are not limited
+    pub max_uses: i64,
+    pub uses: i64,
+    /// Unix timestamp after which the code expires, zero if it never expires
+    pub expire_at: i64,
+    /// Make the new user and the admin friends
+    pub auto_friend: bool,
+    pub create_at: i64,
+}
+
+impl Invite {
+    /// Describe whether the code can still be used
+    ///
+    pub fn status(&self, now: i64) -> &'static str {
+        if self.expire_at > 0 && self.expire_at <= now {
+            "Expired"
+        } else if self.max_uses > 0 && self.uses >= self.max_uses {
+            "Used up"
+        } else {
+            "Active"
+        }
+    }
+
+    /// Path of the registration page with the code filled in
+    ///
+    pub fn register_path(code: &str) -> String {
+        format!("/register?code={}", code)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Invite {
+    /// List all invite codes, the newest first
+    ///
+    pub async fn list(store: &Store) -> Result<Vec<Self>> {
+        let invites: Vec<Self> = sqlx::query_as(
+            "
+            SELECT i.id, i.code, u.nickname AS creator, i.role, i.max_uses, i.uses,
+                i.expire_at, i.auto_friend, i.create_at
+            FROM invites AS i JOIN users AS u ON u.id = i.creator_id
+            ORDER BY i.id DESC",
+        )
+        .fetch_all(&store.pool)
+        .await?;
+        Ok(invites)
+    }
+
+    /// Delete an invite code, the registered users are kept
+    ///
+    pub async fn delete(invite_id: i64, store: &Store) -> Result<()> {
+        let result = sqlx::query("DELETE FROM invites WHERE id = $1")
+            .bind(invite_id)
+            .execute(&store.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Get the QR code of the registration page as a data url
+    ///
+    pub async fn qrcode(invite_id: i64, host: &str, store: &Store) -> Result<String> {
+        let code: Option<String> = sqlx::query_scalar("SELECT code FROM invites WHERE id = $1")
+            .bind(invite_id)
+            .fetch_optional(&store.pool)
+            .await?;
+        let code = code.ok_or(Error::NotFound)?;
+
+        qrcode_data_url(&format!("{}{}", host, Self::register_path(&code)))
+    }
+}
+
+// ==================== // InsertInviteArg // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct InsertInviteArg {
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 0, max = 1000, message = "Invalid usage count"))
+    )]
+    pub max_uses: i64,
+    /// Last local day of the code as `YYYY-MM-DD`, empty if it never expires
+    pub expire_date: String,
+    /// Timezone offset of the client in seconds
+    pub offset: i32,
+    pub role: UserRole,
+    pub auto_friend: bool,
+}
+
+impl InsertInviteArg {
+    /// Generate a new invite code in the database
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn call(&self, creator_id: i64, store: &Store) -> Result<String> {
+        let expire_at = match self.expire_date.trim() {
+            "" => 0,
+            date => {
+                let midnight = DateTime::parse_date(date, self.offset)
+                    .ok_or(Error::BadRequest(String::from("Invalid expiry date")))?;
+                // the code is available until the end of the day
+                midnight + 86400
+            }
+        };
+
+        let code: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(CODE_LENGTH)
+            .map(|c| char::from(c).to_ascii_uppercase())
+            .collect();
+
+        sqlx::query(
+            "
+            INSERT INTO invites (code, creator_id, role, max_uses, expire_at, auto_friend, create_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&code)
+        .bind(creator_id)
+        .bind(&self.role)
+        .bind(self.max_uses)
+        .bind(expire_at)
+        .bind(self.auto_friend)
+        .bind(DateTime::now().timestamp)
+        .execute(&store.pool)
+        .await?;
+
+        Ok(code)
+    }
+}
+
+// ==================== // RegisterArg // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct RegisterArg {
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 32, message = "Invite code cannot be empty"))
+    )]
+    pub code: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 128, message = "Username cannot be empty"))
+    )]
+    pub username: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(custom(function = "validate_password_strength"))
+    )]
+    pub password: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 128, message = "Confirm password cannot be empty"))
+    )]
+    pub confirm_password: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+struct UsedInvite {
+    id: i64,
+    creator_id: i64,
+    role: UserRole,
+    auto_friend: bool,
+}
+
+impl RegisterArg {
+    /// Create the user with the invite code and log in, returns the friendship with
+    /// the inviter if the code makes them friends
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn call(
+        &self,
+        client: &ClientInfo,
+        store: &Store,
+    ) -> Result<(String, User, Option<FriendShip>)> {
+        if self.password != self.confirm_password {
+            return Err(Error::BadRequest(String::from(
+                "The password confirmation does not match.",
+            )));
+        }
+
+        let username = self.username.trim();
+        let (exists,): (bool,) =
+            sqlx::query_as("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1)")
+                .bind(username)
+                .fetch_one(&store.pool)
+                .await?;
+        if exists {
+            return Err(Error::BadRequest(String::from("The username is taken")));
+        }
+
+        // take one use of the code, so that it cannot be used more than allowed
+        let invite: Option<UsedInvite> = sqlx::query_as(
+            "
+            UPDATE invites SET uses = uses + 1
+            WHERE code = $1 AND (max_uses = 0 OR uses < max_uses)
+                AND (expire_at = 0 OR expire_at > $2)
+            RETURNING id, creator_id, role, auto_friend",
+        )
+        .bind(self.code.trim().to_ascii_uppercase())
+        .bind(DateTime::now().timestamp)
+        .fetch_optional(&store.pool)
+        .await?;
+        let invite = invite.ok_or(Error::BadRequest(String::from(
+            "The invite code is invalid or has expired",
+        )))?;
+
+        let arg = InsertUserArg {
+            username: username.to_owned(),
+            password: self.password.clone(),
+            role: invite.role,
+            active: true,
+            must_change_password: false,
+        };
+        if let Err(err) = arg.insert(store).await {
+            sqlx::query("UPDATE invites SET uses = uses - 1 WHERE id = $1")
+                .bind(invite.id)
+                .execute(&store.pool)
+                .await?;
+            return Err(err);
+        }
+
+        let user = UserEntity::find(username, store)
+            .await?
+            .map(User::from)
+            .ok_or(Error::InternalServer)?;
+
+        let friendship = if invite.auto_friend {
+            FriendShip::add(invite.creator_id, user.id, store).await?;
+            Some(FriendShip::accept(user.id, invite.creator_id, store).await?)
+        } else {
+            None
+        };
+
+        let session = Session::create(&user, client, store).await?;
+        Ok((session, user, friendship))
+    }
+}
@@ -0,0 +1,279 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use std::{collections::HashSet, sync::{Arc, Mutex}, time::{Duration, Instant}};
+    use tokio::time::MissedTickBehavior;
+    use crate::{
+        CallReport, Config, DateTime, Error, FileManager, Message, Result, Session, Store,
+    };
+}}
+
+use serde::{Deserialize, Serialize};
+
+// ==================== // JobKind // ==================== //
+
+/// Maintenance tasks run periodically by the scheduler
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    FileExpiry,
+    AvatarCleanup,
+    SessionPruning,
+    HistoryRetention,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 4] = [
+        JobKind::FileExpiry,
+        JobKind::AvatarCleanup,
+        JobKind::SessionPruning,
+        JobKind::HistoryRetention,
+    ];
+
+    /// Name of the job in the database
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::FileExpiry => "file_expiry",
+            JobKind::AvatarCleanup => "avatar_cleanup",
+            JobKind::SessionPruning => "session_pruning",
+            JobKind::HistoryRetention => "history_retention",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobKind::FileExpiry => "File expiry",
+            JobKind::AvatarCleanup => "Orphan avatars",
+            JobKind::SessionPruning => "Stale sessions",
+            JobKind::HistoryRetention => "History retention",
+        }
+    }
+}
+
+// ==================== // JobStatus // ==================== //
+
+/// The last run of a job
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct JobStatus {
+    pub kind: JobKind,
+    /// Seconds between runs, zero if the job only runs manually
+    pub interval: u64,
+    /// Unix timestamp of the last run, zero if never run
+    pub last_run: i64,
+    /// Milliseconds taken by the last run
+    pub duration: i64,
+    pub success: bool,
+    pub result: String,
+    pub running: bool,
+}
+
+impl JobStatus {
+    /// Format the interval, e.g. `every 6h`
+    ///
+    pub fn interval_label(&self) -> String {
+        match self.interval {
+            0 => String::from("manual"),
+            secs if secs % 86400 == 0 => format!("every {}d", secs / 86400),
+            secs if secs % 3600 == 0 => format!("every {}h", secs / 3600),
+            secs => format!("every {}m", secs.div_ceil(60)),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+struct JobRow {
+    name: String,
+    last_run: i64,
+    duration: i64,
+    success: bool,
+    result: String,
+}
+
+// ==================== // Scheduler // ==================== //
+
+/// Mark the job as no longer running when dropped, even if it panics
+///
+#[cfg(feature = "ssr")]
+struct RunningGuard<'a> {
+    running: &'a Mutex<HashSet<JobKind>>,
+    kind: JobKind,
+}
+
+#[cfg(feature = "ssr")]
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&self.kind);
+        }
+    }
+}
+
+/// Run the maintenance jobs periodically in the background, a job can also be run
+/// manually but never twice at the same time
+///
+#[cfg(feature = "ssr")]
+#[derive(Clone)]
+pub struct Scheduler {
+    store: Store,
+    config: Arc<Config>,
+    running: Arc<Mutex<HashSet<JobKind>>>,
+}
+
+#[cfg(feature = "ssr")]
+impl Scheduler {
+    pub fn new(store: Store, config: Arc<Config>) -> Self {
+        Self {
+            store,
+            config,
+            running: Arc::default(),
+        }
+    }
+
+    /// Spawn a task for every job with an interval
+    ///
+    pub fn start(&self) {
+        for kind in JobKind::ALL {
+            let interval = self.interval(kind);
+            if interval.is_zero() {
+                continue;
+            }
+
+            let scheduler = self.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+                // the first tick completes at once, wait a full interval after startup
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    if let Err(err) = scheduler.run(kind).await {
+                        log::error!("job {} failed to start: {}", kind.name(), err);
+                    }
+                }
+            });
+            log::info!("job {} runs every {:?}", kind.name(), interval);
+        }
+    }
+
+    /// Run the job now and record the result
+    ///
+    pub async fn run(&self, kind: JobKind) -> Result<JobStatus> {
+        if !self.running.lock().unwrap().insert(kind) {
+            return Err(Error::BadRequest(String::from(
+                "The job is already running",
+            )));
+        }
+
+        let started = Instant::now();
+        let guard = RunningGuard {
+            running: &self.running,
+            kind,
+        };
+        let result = self.execute(kind).await;
+        drop(guard);
+
+        let (success, result) = match result {
+            Ok(v) => (true, v),
+            Err(err) => {
+                log::error!("job {} failed: {}", kind.name(), err);
+                (false, err.to_string())
+            }
+        };
+        let status = JobStatus {
+            kind,
+            interval: self.interval(kind).as_secs(),
+            last_run: DateTime::now().timestamp,
+            duration: started.elapsed().as_millis() as i64,
+            success,
+            result,
+            running: false,
+        };
+
+        sqlx::query(
+            "
+            INSERT INTO jobs (name, last_run, duration, success, result) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (name) DO UPDATE SET
+                last_run = excluded.last_run, duration = excluded.duration,
+                success = excluded.success, result = excluded.result",
+        )
+        .bind(kind.name())
+        .bind(status.last_run)
+        .bind(status.duration)
+        .bind(status.success)
+        .bind(&status.result)
+        .execute(&self.store.pool)
+        .await?;
+
+        log::info!("job {} finished: {}", kind.name(), status.result);
+        Ok(status)
+    }
+
+    /// Get the status of all jobs
+    ///
+    pub async fn list(&self) -> Result<Vec<JobStatus>> {
+        let rows: Vec<JobRow> = sqlx::query_as("SELECT * FROM jobs")
+            .fetch_all(&self.store.pool)
+            .await?;
+        let running = self.running.lock().unwrap().clone();
+
+        let jobs = JobKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let row = rows.iter().find(|v| v.name == kind.name());
+                JobStatus {
+                    kind,
+                    interval: self.interval(kind).as_secs(),
+                    last_run: row.map(|v| v.last_run).unwrap_or_default(),
+                    duration: row.map(|v| v.duration).unwrap_or_default(),
+                    success: row.map(|v| v.success).unwrap_or(true),
+                    result: row.map(|v| v.result.clone()).unwrap_or_default(),
+                    running: running.contains(&kind),
+                }
+            })
+            .collect();
+        Ok(jobs)
+    }
+
+    /// Do the work of the job, returns a summary of the result
+    ///
+    async fn execute(&self, kind: JobKind) -> Result<String> {
+        let (store, config) = (&self.store, &self.config);
+        let now = DateTime::now().timestamp;
+
+        match kind {
+            JobKind::FileExpiry => {
+                let size = FileManager::clean_outdated_files(store, config.clone()).await?;
+                Ok(format!("{} removed", size))
+            }
+            JobKind::AvatarCleanup => {
+                let size = FileManager::clean_orphan_avatars(store, config).await?;
+                Ok(format!("{} removed", size))
+            }
+            JobKind::SessionPruning => {
+                let count = Session::prune(now, store).await?;
+                Ok(format!("{} sessions removed", count))
+            }
+            JobKind::HistoryRetention => {
+                let before = now - config.jobs.history_retention.as_secs() as i64;
+                let messages = Message::clean(before, store).await?;
+                let reports = CallReport::clean(before, store).await?;
+                Ok(format!(
+                    "{} messages and {} call reports removed",
+                    messages, reports
+                ))
+            }
+        }
+    }
+
+    fn interval(&self, kind: JobKind) -> Duration {
+        let jobs = &self.config.jobs;
+        match kind {
+            JobKind::FileExpiry => jobs.file_expiry,
+            JobKind::AvatarCleanup => jobs.avatar_cleanup,
+            JobKind::SessionPruning => jobs.session_pruning,
+            JobKind::HistoryRetention => jobs.history_retention_interval,
+        }
+    }
+}
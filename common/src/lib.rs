@@ -1,14 +1,28 @@
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
     pub use chat::Chats;
     pub use friendship::FriendShip;
-    pub use file::FileManager;
-    pub use extractors::{StoreExtractor, AuthExtractor, ArgsValidator, HubManager, ConfigExtractor, HostExtractor};
+    pub use file::{Blob, FileManager, LinkDownload, RoomFile};
+    pub use extractors::{StoreExtractor, AuthExtractor, ArgsValidator, HubManager, ConfigExtractor, HostExtractor, SchedulerExtractor, ClientIpExtractor};
 
     pub use hub::Hub;
     mod hub;
 
-    pub use store::{Store, Config};
+    pub use store::{Store, Config, JobConfig, LoginConfig, OidcConfig, S3Config, SessionConfig};
     mod store;
+
+    pub use job::Scheduler;
+
+    pub use auth::{ClientInfo, LoginStep};
+
+    pub use throttle::LoginThrottle;
+
+    pub use oidc::{OidcLogin, OidcStep};
+
+    pub use csrf::{CsrfConfig, CsrfGuard};
+    mod csrf;
+
+    pub use storage::{ByteStream, LocalStorage, S3Storage, Storage, StorageObject};
+    mod storage;
 }}
 
 pub use error::{Error, FnError, FnResult, Result};
@@ -17,10 +31,19 @@ mod error;
 pub use extractors::{CookieManager, FeedData, HubData};
 mod extractors;
 
-pub use file::{FileInfo, FileLink, FileLinks, FileMeta};
+pub use file::{
+    content_type, is_media, percent_encode, FileInfo, FileLink, FileLinks, FileMeta, ImageMeta,
+    StorageUsage, UpdateLinkArg, MAX_VOICE_DURATION,
+};
 mod file;
 
-pub use chat::{Event, HungUpReson, IceCandidate, Message, MessageKind, Room};
+pub use call::{CallReport, CallReports, CallStats, InsertCallReportArg};
+mod call;
+
+pub use chat::{
+    CallPeer, Event, HungUpReson, IceCandidate, Message, MessageKind, PeerSignal, Room, SignalData,
+    MAX_ALBUM_FILES,
+};
 mod chat;
 
 pub use friendship::{Friend, FriendStatus};
@@ -31,8 +54,29 @@ pub use user::{
 };
 mod user;
 
-pub use auth::{LoginArg, Session};
+pub use auth::{LoginArg, Session, VerifyLoginArg};
 mod auth;
 
+pub use totp::{TwoFactor, TwoFactorSetup, TwoFactorStatus};
+mod totp;
+
+pub use invite::{InsertInviteArg, Invite, RegisterArg};
+mod invite;
+
+pub use throttle::{LockKind, LockedLogin};
+mod throttle;
+
+pub use oidc::Identity;
+mod oidc;
+
+pub use token::{ApiToken, InsertTokenArg, TokenScope};
+mod token;
+
 pub use datetime::DateTime;
 mod datetime;
+
+pub use upload::{InitUploadArg, UploadSession, UPLOAD_CHUNK_SIZE};
+mod upload;
+
+pub use job::{JobKind, JobStatus};
+mod job;
@@ -0,0 +1,434 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use std::collections::HashMap;
+    use data_encoding::BASE64URL_NOPAD;
+    use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+    use rand::{distributions::Alphanumeric, thread_rng, Rng};
+    use redis::AsyncCommands;
+    use serde_json::{Map, Value};
+    use sha2::{Digest, Sha256};
+    use crate::{percent_encode, ClientInfo, DateTime, Error, InsertUserArg, OidcConfig, Result, Session, Store, TwoFactor, User, UserRole, VerifyLoginArg};
+    use crate::user::UserEntity;
+}}
+
+use serde::{Deserialize, Serialize};
+
+/// Seconds to finish the login at the provider
+#[cfg(feature = "ssr")]
+const LOGIN_EXPIRE: i64 = 600;
+
+// ==================== // Identity // ==================== //
+
+/// An account at the identity provider linked to a user
+///
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    /// Username or email at the provider
+    pub name: String,
+    pub create_at: i64,
+}
+
+#[cfg(feature = "ssr")]
+impl Identity {
+    /// List the identities linked to the user
+    ///
+    pub async fn list(user_id: i64, store: &Store) -> Result<Vec<Self>> {
+        let identities: Vec<Self> = sqlx::query_as(
+            "
+            SELECT issuer, subject, name, create_at FROM identities
+            WHERE user_id = $1 ORDER BY create_at",
+        )
+        .bind(user_id)
+        .fetch_all(&store.pool)
+        .await?;
+        Ok(identities)
+    }
+
+    /// Remove the link of an identity, the user can still log in with the password
+    ///
+    pub async fn unlink(user_id: i64, issuer: &str, subject: &str, store: &Store) -> Result<()> {
+        let result = sqlx::query(
+            "DELETE FROM identities WHERE user_id = $1 AND issuer = $2 AND subject = $3",
+        )
+        .bind(user_id)
+        .bind(issuer)
+        .bind(subject)
+        .execute(&store.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Link the identity to the user, an identity belongs to one user only
+    ///
+    async fn link(user_id: i64, issuer: &str, subject: &str, name: &str, store: &Store) -> Result<()> {
+        if let Some(linked) = Self::find_user(issuer, subject, store).await? {
+            if linked == user_id {
+                return Ok(());
+            }
+            return Err(Error::BadRequest(String::from(
+                "The identity is already linked to another account",
+            )));
+        }
+
+        sqlx::query(
+            "
+            INSERT INTO identities (issuer, subject, user_id, name, create_at)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(issuer)
+        .bind(subject)
+        .bind(user_id)
+        .bind(name)
+        .bind(DateTime::now().timestamp)
+        .execute(&store.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Find the user linked to the identity
+    ///
+    async fn find_user(issuer: &str, subject: &str, store: &Store) -> Result<Option<i64>> {
+        let user_id: Option<i64> = sqlx::query_scalar(
+            "SELECT user_id FROM identities WHERE issuer = $1 AND subject = $2",
+        )
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(&store.pool)
+        .await?;
+        Ok(user_id)
+    }
+}
+
+// ==================== // OidcLogin // ==================== //
+
+/// Result of the callback from the provider
+///
+#[cfg(feature = "ssr")]
+pub enum OidcStep {
+    /// The user is logged in with a new session
+    Session(String, User),
+    /// The user has 2FA, the code is verified on the login page with the challenge
+    TwoFactor(String),
+    /// The identity is linked to the logged in user
+    Linked,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Login with an OpenID Connect provider by the authorization code flow with PKCE.
+/// The verifier and nonce of a login are kept in the redis under its state until
+/// the provider redirects back
+///
+#[cfg(feature = "ssr")]
+pub struct OidcLogin;
+
+#[cfg(feature = "ssr")]
+impl OidcLogin {
+    /// Start a login, returns the authorization url of the provider and the state.
+    /// The identity is linked to the user instead of logging in if one is given
+    ///
+    pub async fn start(
+        link_user: Option<i64>,
+        config: &OidcConfig,
+        store: &Store,
+    ) -> Result<(String, String)> {
+        let metadata = Self::discover(config).await?;
+
+        let state = random_token(32);
+        let nonce = random_token(32);
+        let verifier = random_token(64);
+        let challenge = BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()));
+
+        let key = Self::make_key(&state);
+        let mut con = store.con.clone();
+        let _: () = redis::pipe()
+            .hset_multiple(
+                &key,
+                &[
+                    ("verifier", verifier),
+                    ("nonce", nonce.clone()),
+                    ("user_id", link_user.unwrap_or_default().to_string()),
+                ],
+            )
+            .ignore()
+            .expire(&key, LOGIN_EXPIRE)
+            .ignore()
+            .query_async(&mut con)
+            .await?;
+
+        let sep = if metadata.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let url = format!(
+            "{}{}response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&nonce={}&code_challenge={}&code_challenge_method=S256",
+            metadata.authorization_endpoint,
+            sep,
+            percent_encode(&config.client_id),
+            percent_encode(&config.redirect_url),
+            percent_encode(&config.scopes),
+            state,
+            nonce,
+            challenge,
+        );
+        Ok((url, state))
+    }
+
+    /// Exchange the code from the provider, verify the identity token and log in the
+    /// linked user, who is created if auto provisioning is enabled
+    ///
+    pub async fn finish(
+        state: &str,
+        code: &str,
+        client: &ClientInfo,
+        config: &OidcConfig,
+        store: &Store,
+    ) -> Result<OidcStep> {
+        let key = Self::make_key(state);
+        let mut con = store.con.clone();
+        let (login,): (HashMap<String, String>,) = redis::pipe()
+            .hgetall(&key)
+            .del(&key)
+            .ignore()
+            .query_async(&mut con)
+            .await?;
+
+        let expired = || Error::BadRequest(String::from("The login has expired, please sign in again"));
+        let verifier = login.get("verifier").ok_or_else(expired)?;
+        let nonce = login.get("nonce").ok_or_else(expired)?;
+        let link_user = login
+            .get("user_id")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or_default();
+
+        let metadata = Self::discover(config).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", config.redirect_url.as_str()),
+            ("client_id", config.client_id.as_str()),
+            ("code_verifier", verifier.as_str()),
+        ];
+        if let Some(ref secret) = config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let token: TokenResponse = reqwest::Client::new()
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let claims = Self::verify_token(&token.id_token, nonce, &metadata, config).await?;
+        let subject = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or(Error::BadRequest(String::from("The identity has no subject")))?;
+        let name = claim_str(&claims, &config.username_claim)
+            .or_else(|| claim_str(&claims, "email"))
+            .unwrap_or(subject)
+            .trim()
+            .to_owned();
+
+        if link_user > 0 {
+            Identity::link(link_user, &metadata.issuer, subject, &name, store).await?;
+            return Ok(OidcStep::Linked);
+        }
+
+        let mut user = match Identity::find_user(&metadata.issuer, subject, store).await? {
+            Some(user_id) => User::get(user_id, store)
+                .await
+                .map_err(|_| Error::Forbidden)?,
+            None if config.auto_provision => {
+                let user = Self::provision(&name, Self::role(&claims, config), store).await?;
+                Identity::link(user.id, &metadata.issuer, subject, &name, store).await?;
+                log::info!("user {} is provisioned by {}", user.username, metadata.issuer);
+                user
+            }
+            None => {
+                return Err(Error::BadRequest(String::from(
+                    "No account is linked to this identity, please contact an admin",
+                )))
+            }
+        };
+
+        if !user.active {
+            return Err(Error::Forbidden);
+        }
+
+        // the provider decides the role if the claim is configured
+        if let Some(role) = Self::role(&claims, config) {
+            if role != user.role {
+                sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
+                    .bind(&role)
+                    .bind(user.id)
+                    .execute(&store.pool)
+                    .await?;
+                let _: () = con.del(user.key()).await?;
+                user.role = role;
+            }
+        }
+
+        // the provider replaces the password, not the second factor
+        if TwoFactor::secret(user.id, store).await?.is_some() {
+            let challenge = VerifyLoginArg::challenge(user.id, &user.username, store).await?;
+            return Ok(OidcStep::TwoFactor(challenge));
+        }
+
+        let session = Session::create(&user, client, store).await?;
+        Ok(OidcStep::Session(session, user))
+    }
+
+    /// Fetch the metadata of the provider
+    ///
+    async fn discover(config: &OidcConfig) -> Result<ProviderMetadata> {
+        let url = format!("{}/.well-known/openid-configuration", config.issuer);
+        let metadata: ProviderMetadata = reqwest::get(&url)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if metadata.issuer.trim_end_matches('/') != config.issuer {
+            log::error!("oidc: unexpected issuer {}", metadata.issuer);
+            return Err(Error::InternalServer);
+        }
+        Ok(metadata)
+    }
+
+    /// Verify the signature, issuer, audience, expiry and nonce of the identity token.
+    /// Only the configured algorithms are accepted whatever the token header says
+    ///
+    async fn verify_token(
+        token: &str,
+        nonce: &str,
+        metadata: &ProviderMetadata,
+        config: &OidcConfig,
+    ) -> Result<Map<String, Value>> {
+        let header = decode_header(token)?;
+        if !config.algorithms.contains(&header.alg) {
+            log::warn!("oidc: identity token signed with {:?}", header.alg);
+            return Err(Error::BadRequest(String::from("The identity token is invalid")));
+        }
+
+        let jwks: JwkSet = reqwest::get(&metadata.jwks_uri)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // a token without a key id is only accepted if the provider has one key
+        let jwk = match header.kid {
+            Some(ref kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or(Error::BadRequest(String::from("The signing key is unknown")))?;
+        let key = DecodingKey::from_jwk(jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = config.algorithms.clone();
+        validation.set_audience(&[&config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        let claims = decode::<Map<String, Value>>(token, &key, &validation)?.claims;
+
+        if claim_str(&claims, "nonce") != Some(nonce) {
+            return Err(Error::BadRequest(String::from("The identity token is invalid")));
+        }
+        Ok(claims)
+    }
+
+    /// Map the role claim to the role, the claim may be a string or a list
+    ///
+    fn role(claims: &Map<String, Value>, config: &OidcConfig) -> Option<UserRole> {
+        let claim = config.role_claim.as_ref()?;
+        let is_admin = match claims.get(claim) {
+            Some(Value::String(v)) => v == &config.admin_value,
+            Some(Value::Array(values)) => values
+                .iter()
+                .any(|v| v.as_str() == Some(config.admin_value.as_str())),
+            _ => false,
+        };
+        Some(if is_admin {
+            UserRole::Admin
+        } else {
+            UserRole::User
+        })
+    }
+
+    /// Create a user for the identity, the password is random since the user logs in
+    /// with the provider
+    ///
+    async fn provision(username: &str, role: Option<UserRole>, store: &Store) -> Result<User> {
+        if username.is_empty() || username.len() > 128 {
+            return Err(Error::BadRequest(String::from("The identity has no valid username")));
+        }
+        if UserEntity::find(username, store).await?.is_some() {
+            return Err(Error::BadRequest(format!(
+                "The username {} is taken, please ask an admin to link your account",
+                username
+            )));
+        }
+
+        let arg = InsertUserArg {
+            username: username.to_owned(),
+            password: random_token(32),
+            role: role.unwrap_or(UserRole::User),
+            active: true,
+            must_change_password: false,
+        };
+        arg.insert(store).await?;
+
+        UserEntity::find(username, store)
+            .await?
+            .map(User::from)
+            .ok_or(Error::InternalServer)
+    }
+
+    /// Create a key of the login for redis
+    ///
+    fn make_key(state: &str) -> String {
+        format!("oidc:{}", state)
+    }
+}
+
+// ==================== // UTILS // ==================== //
+
+#[cfg(feature = "ssr")]
+fn claim_str<'a>(claims: &'a Map<String, Value>, name: &str) -> Option<&'a str> {
+    claims
+        .get(name)
+        .and_then(Value::as_str)
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(feature = "ssr")]
+fn random_token(length: usize) -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
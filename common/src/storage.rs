@@ -0,0 +1,337 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use axum::{async_trait, body::Bytes};
+use futures_util::{Stream, TryStreamExt};
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter, SeekFrom},
+};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::{datetime::days_from_ymd, store::S3Config, Error, Result};
+
+/// A stream of bytes read from the storage
+///
+pub type ByteStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// A file kept in the storage
+///
+#[derive(Debug, Clone)]
+pub struct StorageObject {
+    /// Url of the file, e.g. `/assets/share/abc.png`
+    pub url: String,
+    pub size: u64,
+    /// Last modified time as a Unix timestamp
+    pub modified: i64,
+}
+
+// ==================== // Storage // ==================== //
+
+/// Backend keeping the uploaded files, the files are addressed by their url
+///
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Save the stream as the file of the url
+    ///
+    async fn put(&self, url: &str, stream: ByteStream) -> Result<()>;
+
+    /// Get a stream of the file
+    ///
+    async fn get(&self, url: &str) -> Result<ByteStream>;
+
+    /// Get a stream of `len` bytes of the file from the start offset,
+    /// the default skips the bytes before the offset
+    ///
+    async fn get_range(&self, url: &str, start: u64, len: u64) -> Result<ByteStream> {
+        let mut reader = StreamReader::new(self.get(url).await?);
+        tokio::io::copy(&mut (&mut reader).take(start), &mut tokio::io::sink()).await?;
+        Ok(Box::pin(ReaderStream::new(reader.take(len))))
+    }
+
+    /// Delete the file, a missing file is not an error
+    ///
+    async fn delete(&self, url: &str) -> Result<()>;
+
+    /// List the files in the directory
+    ///
+    async fn list(&self, dir: &str) -> Result<Vec<StorageObject>>;
+
+    /// Get the size of the file, none if it does not exist
+    ///
+    async fn size(&self, url: &str) -> Result<Option<u64>>;
+
+    /// Save a local file as the file of the url, the local file is removed afterwards
+    ///
+    async fn put_file(&self, url: &str, path: &Path) -> Result<()> {
+        let result = async {
+            let file = File::open(path).await?;
+            self.put(url, Box::pin(ReaderStream::new(file))).await
+        }
+        .await;
+
+        let _ = tokio::fs::remove_file(path).await;
+        result
+    }
+
+    /// Get a presigned url to download the file from the storage directly,
+    /// none if the file is served by the app
+    ///
+    async fn presign(&self, _url: &str, _expires: Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+// ==================== // LocalStorage // ==================== //
+
+/// Keep the files on the local disk under the site root
+///
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+
+    /// Get the full path of the file or directory
+    ///
+    fn fullpath(&self, url: &str) -> Result<PathBuf> {
+        Ok(self.root.join(object_key(url)?))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, url: &str, stream: ByteStream) -> Result<()> {
+        let path = self.fullpath(url)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let mut reader = StreamReader::new(stream);
+        let mut file = BufWriter::new(File::create(path).await?);
+        tokio::io::copy_buf(&mut reader, &mut file).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn get(&self, url: &str) -> Result<ByteStream> {
+        let file = File::open(self.fullpath(url)?).await.map_err(not_found)?;
+        Ok(Box::pin(ReaderStream::new(file)))
+    }
+
+    async fn get_range(&self, url: &str, start: u64, len: u64) -> Result<ByteStream> {
+        let mut file = File::open(self.fullpath(url)?).await.map_err(not_found)?;
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(Box::pin(ReaderStream::new(file.take(len))))
+    }
+
+    async fn delete(&self, url: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.fullpath(url)?).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, dir: &str) -> Result<Vec<StorageObject>> {
+        let mut objects = Vec::new();
+        let mut entries = match tokio::fs::read_dir(self.fullpath(dir)?).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(objects),
+            Err(err) => return Err(err.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let Some(name) = entry.file_name().to_str().map(|v| v.to_owned()) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+                .map(|v| v.as_secs() as i64)
+                .unwrap_or_default();
+
+            objects.push(StorageObject {
+                url: format!("{}/{}", dir.trim_end_matches('/'), name),
+                size: metadata.len(),
+                modified,
+            });
+        }
+        Ok(objects)
+    }
+
+    async fn size(&self, url: &str) -> Result<Option<u64>> {
+        match tokio::fs::metadata(self.fullpath(url)?).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn put_file(&self, url: &str, path: &Path) -> Result<()> {
+        let dest = self.fullpath(url)?;
+        if let Some(dir) = dest.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        // renaming fails across file systems, e.g. a tmpfs temp directory
+        if tokio::fs::rename(path, &dest).await.is_err() {
+            let result = tokio::fs::copy(path, &dest).await;
+            let _ = tokio::fs::remove_file(path).await;
+            result?;
+        }
+        Ok(())
+    }
+}
+
+// ==================== // S3Storage // ==================== //
+
+/// Keep the files in a bucket of an S3 compatible object storage,
+/// e.g. AWS S3 or MinIO, downloads are redirected to presigned urls
+///
+pub struct S3Storage {
+    bucket: Box<Bucket>,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3Config) -> Self {
+        let region = Region::Custom {
+            region: config.region.clone(),
+            endpoint: config.endpoint.clone(),
+        };
+        let credentials = Credentials::new(
+            Some(&config.access_key),
+            Some(&config.secret_key),
+            None,
+            None,
+            None,
+        )
+        .expect("failed to create s3 credentials");
+
+        let bucket =
+            Bucket::new(&config.bucket, region, credentials).expect("failed to create s3 bucket");
+        let bucket = if config.path_style {
+            bucket.with_path_style()
+        } else {
+            bucket
+        };
+
+        Self { bucket }
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, url: &str, stream: ByteStream) -> Result<()> {
+        let mut reader = StreamReader::new(stream);
+        self.bucket
+            .put_object_stream(&mut reader, object_key(url)?)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, url: &str) -> Result<ByteStream> {
+        let rsp = self.bucket.get_object_stream(object_key(url)?).await?;
+        let stream = rsp
+            .bytes
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+        Ok(Box::pin(stream))
+    }
+
+    async fn delete(&self, url: &str) -> Result<()> {
+        match self.bucket.delete_object(object_key(url)?).await {
+            Err(S3Error::HttpFailWithBody(404, _)) => Ok(()),
+            Err(err) => Err(err.into()),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn list(&self, dir: &str) -> Result<Vec<StorageObject>> {
+        let prefix = format!("{}/", object_key(dir)?);
+        let results = self.bucket.list(prefix, None).await?;
+
+        let objects = results
+            .into_iter()
+            .flat_map(|result| result.contents)
+            .map(|object| StorageObject {
+                url: format!("/{}", object.key),
+                size: object.size,
+                modified: parse_timestamp(&object.last_modified),
+            })
+            .collect();
+        Ok(objects)
+    }
+
+    async fn size(&self, url: &str) -> Result<Option<u64>> {
+        match self.bucket.head_object(object_key(url)?).await {
+            Ok((head, _)) => Ok(Some(head.content_length.unwrap_or_default() as u64)),
+            Err(S3Error::HttpFailWithBody(404, _)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn presign(&self, url: &str, expires: Duration) -> Result<Option<String>> {
+        let presigned = self
+            .bucket
+            .presign_get(object_key(url)?, expires.as_secs() as u32, None)
+            .await?;
+        Ok(Some(presigned))
+    }
+}
+
+// ==================== // UTILS // ==================== //
+
+/// Convert the url to the key of the storage, the url must not leave the root
+///
+fn object_key(url: &str) -> Result<&str> {
+    let key = url.trim_matches('/');
+    if key.is_empty()
+        || key
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        return Err(Error::NotFound);
+    }
+    Ok(key)
+}
+
+fn not_found(err: io::Error) -> Error {
+    if err.kind() == io::ErrorKind::NotFound {
+        Error::NotFound
+    } else {
+        err.into()
+    }
+}
+
+/// Parse a RFC 3339 time in UTC, e.g. `2024-10-12T17:50:30.000Z`, into a Unix timestamp
+///
+fn parse_timestamp(value: &str) -> i64 {
+    let num = |range: std::ops::Range<usize>| {
+        value
+            .get(range)
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or_default()
+    };
+    let (y, m, d) = (num(0..4), num(5..7), num(8..10));
+    let (hh, mm, ss) = (num(11..13), num(14..16), num(17..19));
+
+    let days = days_from_ymd(y as i32, m as i32, d as i32) as i64;
+    days * 86400 + hh * 3600 + mm * 60 + ss
+}
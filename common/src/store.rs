@@ -1,10 +1,13 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use redis::aio::MultiplexedConnection;
+use jsonwebtoken::Algorithm;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use redis::{aio::MultiplexedConnection, AsyncCommands};
 use sqlx::{pool::PoolOptions, sqlite::SqlitePool};
 
 use super::user::UserEntity;
-use crate::{InsertUserArg, UserRole};
+use crate::storage::{LocalStorage, S3Storage, Storage};
+use crate::{CsrfConfig, InsertUserArg, User, UserRole};
 
 // ==================== // Store // ==================== //
 
@@ -12,6 +15,9 @@ use crate::{InsertUserArg, UserRole};
 pub struct Store {
     pub pool: SqlitePool,
     pub con: MultiplexedConnection,
+    pub storage: Arc<dyn Storage>,
+    /// Limits enforced whenever a session is verified
+    pub session: SessionConfig,
 }
 
 impl Store {
@@ -20,9 +26,16 @@ impl Store {
     pub async fn new(config: &Config) -> Self {
         let pool = Store::create_database_pool(config).await;
         let con = Store::create_redis_connection(config).await;
+        let storage = Store::create_storage(config);
+        let session = config.session;
 
-        let store = Self { pool, con };
-        store.init().await;
+        let store = Self {
+            pool,
+            con,
+            storage,
+            session,
+        };
+        store.init(config).await;
 
         store
     }
@@ -68,20 +81,66 @@ impl Store {
         con
     }
 
-    /// Create an account for admin if not exists
+    /// Create the storage of uploaded files, the local disk by default
     ///
-    async fn init(&self) {
-        if let Ok(None) = UserEntity::find("admin", self).await {
-            let user = InsertUserArg {
-                username: String::from("admin"),
-                password: String::from("123456"),
-                role: UserRole::Admin,
-                active: true,
-            };
-            user.insert(self)
-                .await
-                .expect("failed to create admin account");
-            log::info!("admin account created");
+    fn create_storage(config: &Config) -> Arc<dyn Storage> {
+        match config.s3 {
+            Some(ref s3) => {
+                log::info!("files are stored in s3 bucket {}", s3.bucket);
+                Arc::new(S3Storage::new(s3))
+            }
+            None => Arc::new(LocalStorage::new(&config.site_root)),
+        }
+    }
+
+    /// Create an account for admin if not exists, the password is taken from the env
+    /// or generated and logged once, and must be changed at the first login
+    ///
+    async fn init(&self, config: &Config) {
+        match UserEntity::find("admin", self).await {
+            Ok(None) => {
+                let password = match config.admin_password {
+                    Some(ref password) => password.clone(),
+                    None => {
+                        let password: String = thread_rng()
+                            .sample_iter(&Alphanumeric)
+                            .take(16)
+                            .map(char::from)
+                            .collect();
+                        log::warn!("initial password of the admin account: {}", password);
+                        password
+                    }
+                };
+
+                let user = InsertUserArg {
+                    username: String::from("admin"),
+                    password,
+                    role: UserRole::Admin,
+                    active: true,
+                    must_change_password: true,
+                };
+                user.insert(self)
+                    .await
+                    .expect("failed to create admin account");
+                log::info!("admin account created");
+            }
+            // the admin seeded by older versions may still have the default password
+            Ok(Some(entity)) if entity.verify_password("123456").is_ok() => {
+                let user = User::from(entity);
+                sqlx::query("UPDATE users SET must_change_password = 1 WHERE id = $1")
+                    .bind(user.id)
+                    .execute(&self.pool)
+                    .await
+                    .expect("failed to update admin account");
+
+                let mut con = self.con.clone();
+                let _: () = con
+                    .del(user.key())
+                    .await
+                    .expect("failed to update admin account");
+                log::warn!("admin account has the default password, it must be changed");
+            }
+            _ => {}
         }
     }
 }
@@ -97,6 +156,93 @@ pub struct Config {
     pub archive_dir: String,
     pub share_dir: String,
     pub expire_duration: Duration,
+    pub strip_metadata: bool,
+    pub max_file_size: u64,
+    pub max_avatar_size: u64,
+    pub max_archive_size: u64,
+    pub default_quota: u64,
+    /// S3 compatible storage for uploaded files, none for the local disk
+    pub s3: Option<S3Config>,
+    /// Single sign-on with an OpenID Connect provider, none if disabled
+    pub oidc: Option<OidcConfig>,
+    pub jobs: JobConfig,
+    pub session: SessionConfig,
+    /// Initial password of the seeded admin, generated if none
+    pub admin_password: Option<String>,
+    pub login: LoginConfig,
+    pub csrf: CsrfConfig,
+}
+
+#[derive(Debug)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    pub endpoint: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Use `endpoint/bucket` urls instead of subdomains, required by MinIO
+    pub path_style: bool,
+}
+
+#[derive(Debug)]
+pub struct OidcConfig {
+    /// Issuer url, the provider metadata is discovered from it
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// Callback url registered at the provider, e.g. `{host}/auth/oidc/callback`
+    pub redirect_url: String,
+    pub scopes: String,
+    /// Name shown on the login button
+    pub label: String,
+    /// Claim of the username of provisioned users
+    pub username_claim: String,
+    /// Claim whose value decides the role, none to keep the role in the database
+    pub role_claim: Option<String>,
+    /// Value of the role claim given to admins
+    pub admin_value: String,
+    /// Create a user for an identity which is not linked yet
+    pub auto_provision: bool,
+    /// Algorithms accepted for the signature of the identity token
+    pub algorithms: Vec<Algorithm>,
+}
+
+/// Intervals of the maintenance jobs, a zero interval disables the job
+///
+#[derive(Debug)]
+pub struct JobConfig {
+    pub file_expiry: Duration,
+    pub avatar_cleanup: Duration,
+    pub session_pruning: Duration,
+    pub history_retention_interval: Duration,
+    /// Messages and call reports older than the duration are removed
+    pub history_retention: Duration,
+}
+
+/// Limits of the login sessions, zero disables a limit
+///
+#[derive(Debug, Clone, Copy)]
+pub struct SessionConfig {
+    /// Sessions kept per user, the least recently used are removed on login
+    pub max_count: usize,
+    /// Sessions expire the duration after the login
+    pub lifetime: Duration,
+    /// Sessions not used within the duration expire
+    pub idle_timeout: Duration,
+}
+
+/// Limits of the failed logins
+///
+#[derive(Debug)]
+pub struct LoginConfig {
+    /// Failures of an account before it is locked
+    pub max_failures: u64,
+    /// Failures from a client address before it is locked
+    pub max_ip_failures: u64,
+    /// Duration of the lockout, failures are forgotten after it
+    pub lockout: Duration,
+    /// Take the client address from `X-Forwarded-For` behind a reverse proxy
+    pub trust_proxy: bool,
 }
 
 impl Config {
@@ -106,6 +252,15 @@ impl Config {
         let expire_days = env_default("CHAT_EXPIRE_DAYS", "3")
             .parse::<u64>()
             .expect("failed to parse expire days");
+        let strip_metadata = env_default("CHAT_STRIP_METADATA", "true")
+            .parse::<bool>()
+            .expect("failed to parse strip metadata");
+        let megabytes = |key: &str, default: &str| {
+            env_default(key, default)
+                .parse::<u64>()
+                .map(|mb| mb * 1_000_000)
+                .unwrap_or_else(|_| panic!("failed to parse {}", key))
+        };
 
         Self {
             db_url: env_default("CHAT_DATABASE_URL", "sqlite://db/chat_dev.db"),
@@ -115,7 +270,183 @@ impl Config {
             archive_dir: env_default("CHAT_ARCHIVE_DIR", "/assets/archive"),
             share_dir: env_default("CHAT_SHARE_DIR", "/assets/share"),
             expire_duration: Duration::from_secs(expire_days * 60 * 60 * 24),
+            strip_metadata,
+            max_file_size: megabytes("CHAT_MAX_FILE_MB", "50"),
+            max_avatar_size: megabytes("CHAT_MAX_AVATAR_MB", "5"),
+            max_archive_size: megabytes("CHAT_MAX_ARCHIVE_MB", "200"),
+            default_quota: megabytes("CHAT_USER_QUOTA_MB", "1024"),
+            s3: S3Config::from_env(),
+            oidc: OidcConfig::from_env(),
+            jobs: JobConfig::from_env(),
+            session: SessionConfig::from_env(),
+            admin_password: std::env::var("CHAT_ADMIN_PASSWORD")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            login: LoginConfig::from_env(),
+            csrf: CsrfConfig::from_env(),
+        }
+    }
+}
+
+impl CsrfConfig {
+    /// Create csrf config from env
+    ///
+    fn from_env() -> Self {
+        let allowed_origins = env_default("CHAT_ALLOWED_ORIGINS", "")
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect();
+        let trust_proxy = env_default("CHAT_TRUST_PROXY", "false")
+            .parse::<bool>()
+            .expect("failed to parse trust proxy");
+
+        Self {
+            allowed_origins,
+            trust_proxy,
+        }
+    }
+}
+
+impl LoginConfig {
+    /// Create login config from env
+    ///
+    fn from_env() -> Self {
+        let number = |key: &str, default: &str| {
+            env_default(key, default)
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("failed to parse {}", key))
+        };
+        let trust_proxy = env_default("CHAT_TRUST_PROXY", "false")
+            .parse::<bool>()
+            .expect("failed to parse trust proxy");
+
+        Self {
+            max_failures: number("CHAT_LOGIN_MAX_FAILURES", "5"),
+            max_ip_failures: number("CHAT_LOGIN_MAX_IP_FAILURES", "20"),
+            lockout: Duration::from_secs(number("CHAT_LOGIN_LOCKOUT_MINS", "15") * 60),
+            trust_proxy,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Create session config from env
+    ///
+    fn from_env() -> Self {
+        let days = |key: &str, default: &str| {
+            env_default(key, default)
+                .parse::<u64>()
+                .map(|days| Duration::from_secs(days * 60 * 60 * 24))
+                .unwrap_or_else(|_| panic!("failed to parse {}", key))
+        };
+        let max_count = env_default("CHAT_SESSION_MAX", "5")
+            .parse::<usize>()
+            .expect("failed to parse max sessions");
+
+        Self {
+            max_count,
+            lifetime: days("CHAT_SESSION_LIFETIME_DAYS", "90"),
+            idle_timeout: days("CHAT_SESSION_EXPIRE_DAYS", "30"),
+        }
+    }
+}
+
+impl JobConfig {
+    /// Create job config from env
+    ///
+    fn from_env() -> Self {
+        let minutes = |key: &str, default: &str| {
+            env_default(key, default)
+                .parse::<u64>()
+                .map(|mins| Duration::from_secs(mins * 60))
+                .unwrap_or_else(|_| panic!("failed to parse {}", key))
+        };
+        let days = |key: &str, default: &str| {
+            env_default(key, default)
+                .parse::<u64>()
+                .map(|days| Duration::from_secs(days * 60 * 60 * 24))
+                .unwrap_or_else(|_| panic!("failed to parse {}", key))
+        };
+
+        Self {
+            file_expiry: minutes("CHAT_JOB_FILE_EXPIRY_MINS", "60"),
+            avatar_cleanup: minutes("CHAT_JOB_AVATAR_CLEANUP_MINS", "1440"),
+            session_pruning: minutes("CHAT_JOB_SESSION_PRUNING_MINS", "360"),
+            history_retention_interval: minutes("CHAT_JOB_HISTORY_RETENTION_MINS", "1440"),
+            history_retention: days("CHAT_HISTORY_RETENTION_DAYS", "90"),
+        }
+    }
+}
+
+impl S3Config {
+    /// Create s3 config from env if `CHAT_STORAGE` is `s3`
+    ///
+    fn from_env() -> Option<Self> {
+        if env_default("CHAT_STORAGE", "local") != "s3" {
+            return None;
         }
+
+        let path_style = env_default("CHAT_S3_PATH_STYLE", "true")
+            .parse::<bool>()
+            .expect("failed to parse s3 path style");
+
+        Some(Self {
+            bucket: env_default("CHAT_S3_BUCKET", "chat"),
+            region: env_default("CHAT_S3_REGION", "us-east-1"),
+            endpoint: env_default("CHAT_S3_ENDPOINT", "http://localhost:9000"),
+            access_key: env_default("CHAT_S3_ACCESS_KEY", "minioadmin"),
+            secret_key: env_default("CHAT_S3_SECRET_KEY", "minioadmin"),
+            path_style,
+        })
+    }
+}
+
+impl OidcConfig {
+    /// Create oidc config from env if `CHAT_OIDC_ISSUER` is set
+    ///
+    fn from_env() -> Option<Self> {
+        let issuer = std::env::var("CHAT_OIDC_ISSUER")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+
+        let auto_provision = env_default("CHAT_OIDC_AUTO_PROVISION", "false")
+            .parse::<bool>()
+            .expect("failed to parse oidc auto provision");
+        // the keys of the provider are public, so a shared secret algorithm is refused
+        let algorithms = env_default("CHAT_OIDC_ALGORITHMS", "RS256,ES256")
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| match v.parse::<Algorithm>() {
+                Ok(Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) | Err(_) => {
+                    panic!("unsupported oidc algorithm {}", v)
+                }
+                Ok(alg) => alg,
+            })
+            .collect();
+
+        Some(Self {
+            issuer: issuer.trim_end_matches('/').to_owned(),
+            client_id: env_default("CHAT_OIDC_CLIENT_ID", "chat"),
+            client_secret: std::env::var("CHAT_OIDC_CLIENT_SECRET")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            redirect_url: env_default(
+                "CHAT_OIDC_REDIRECT_URL",
+                "http://localhost:3000/auth/oidc/callback",
+            ),
+            scopes: env_default("CHAT_OIDC_SCOPES", "openid profile email"),
+            label: env_default("CHAT_OIDC_LABEL", "SSO"),
+            username_claim: env_default("CHAT_OIDC_USERNAME_CLAIM", "preferred_username"),
+            role_claim: std::env::var("CHAT_OIDC_ROLE_CLAIM")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            admin_value: env_default("CHAT_OIDC_ADMIN_VALUE", "admin"),
+            auto_provision,
+            algorithms,
+        })
     }
 }
 
@@ -0,0 +1,201 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use redis::{AsyncCommands, AsyncIter};
+    use crate::{Error, LoginConfig, Result, Store};
+}}
+
+use serde::{Deserialize, Serialize};
+
+/// The longest wait in seconds between two failed logins
+#[cfg(feature = "ssr")]
+const MAX_BACKOFF: u64 = 300;
+
+// ==================== // LockedLogin // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockKind {
+    /// Failures of a username
+    Account,
+    /// Failures from a client address
+    Address,
+}
+
+impl LockKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LockKind::Account => "Account",
+            LockKind::Address => "Address",
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    fn prefix(&self) -> &'static str {
+        match self {
+            LockKind::Account => "login-fail:user:",
+            LockKind::Address => "login-fail:ip:",
+        }
+    }
+}
+
+/// An account or a client address locked out after too many failed logins
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LockedLogin {
+    pub kind: LockKind,
+    /// Username or client address
+    pub name: String,
+    pub failures: u64,
+    /// Seconds until the lockout ends
+    pub expire_in: i64,
+}
+
+// ==================== // LoginThrottle // ==================== //
+
+/// Count the failed logins per username and per client address in the redis. Every
+/// failure doubles the wait before the next attempt, and after too many failures the
+/// login is refused until the lockout ends, so the password is never verified while
+/// throttled
+///
+#[cfg(feature = "ssr")]
+pub struct LoginThrottle;
+
+#[cfg(feature = "ssr")]
+impl LoginThrottle {
+    /// Refuse the login if the account or the address is locked or must wait
+    ///
+    pub async fn check(
+        username: &str,
+        ip: &str,
+        config: &LoginConfig,
+        store: &Store,
+    ) -> Result<()> {
+        let mut con = store.con.clone();
+
+        for (kind, name, max) in [
+            (LockKind::Account, username, config.max_failures),
+            (LockKind::Address, ip, config.max_ip_failures),
+        ] {
+            let key = Self::fail_key(kind, name);
+            let failures: Option<u64> = con.get(&key).await?;
+            if failures.unwrap_or_default() >= max {
+                let ttl: i64 = con.ttl(&key).await?;
+                let minutes = (ttl.max(1) + 59) / 60;
+                let msg = match kind {
+                    LockKind::Account => format!(
+                        "The account is locked after too many failed logins, try again in {} min",
+                        minutes
+                    ),
+                    LockKind::Address => format!(
+                        "Too many failed logins from your address, try again in {} min",
+                        minutes
+                    ),
+                };
+                return Err(Error::TooManyRequests(msg));
+            }
+
+            let wait: i64 = con.ttl(Self::wait_key(kind, name)).await?;
+            if wait > 0 {
+                return Err(Error::TooManyRequests(format!(
+                    "Please wait {} seconds before trying again",
+                    wait
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a failed login, the wait grows exponentially with the failures
+    ///
+    pub async fn fail(username: &str, ip: &str, config: &LoginConfig, store: &Store) -> Result<()> {
+        let mut con = store.con.clone();
+        let lockout = config.lockout.as_secs() as i64;
+
+        for (kind, name) in [(LockKind::Account, username), (LockKind::Address, ip)] {
+            let key = Self::fail_key(kind, name);
+            let (failures,): (u64,) = redis::pipe()
+                .incr(&key, 1)
+                .expire(&key, lockout)
+                .ignore()
+                .query_async(&mut con)
+                .await?;
+
+            let backoff = 1_u64
+                .checked_shl(failures.saturating_sub(1) as u32)
+                .unwrap_or(MAX_BACKOFF)
+                .min(MAX_BACKOFF);
+            let _: () = con.set_ex(Self::wait_key(kind, name), 1, backoff).await?;
+        }
+
+        log::warn!("failed login of {} from {}", username, ip);
+        Ok(())
+    }
+
+    /// Forget the failures of the account after a successful login, the failures of
+    /// the address are kept so that one valid account cannot reset them
+    ///
+    pub async fn reset(username: &str, store: &Store) -> Result<()> {
+        Self::unlock(LockKind::Account, username, store).await
+    }
+
+    /// Remove the lockout and the wait
+    ///
+    pub async fn unlock(kind: LockKind, name: &str, store: &Store) -> Result<()> {
+        let mut con = store.con.clone();
+        let _: () = con
+            .del(&[Self::fail_key(kind, name), Self::wait_key(kind, name)])
+            .await?;
+        Ok(())
+    }
+
+    /// List the accounts and addresses which are locked
+    ///
+    pub async fn list_locked(config: &LoginConfig, store: &Store) -> Result<Vec<LockedLogin>> {
+        let mut con = store.con.clone();
+
+        let keys: Vec<String> = {
+            let mut iter: AsyncIter<String> = con.scan_match("login-fail:*").await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+
+        let mut locked = Vec::new();
+        for key in keys {
+            let (kind, name, max) = if let Some(v) = key.strip_prefix(LockKind::Account.prefix()) {
+                (LockKind::Account, v, config.max_failures)
+            } else if let Some(v) = key.strip_prefix(LockKind::Address.prefix()) {
+                (LockKind::Address, v, config.max_ip_failures)
+            } else {
+                continue;
+            };
+
+            let failures: Option<u64> = con.get(&key).await?;
+            let failures = failures.unwrap_or_default();
+            if failures < max {
+                continue;
+            }
+            let expire_in: i64 = con.ttl(&key).await?;
+            locked.push(LockedLogin {
+                kind,
+                name: name.to_owned(),
+                failures,
+                expire_in,
+            });
+        }
+
+        locked.sort_by(|a, b| b.expire_in.cmp(&a.expire_in));
+        Ok(locked)
+    }
+
+    fn fail_key(kind: LockKind, name: &str) -> String {
+        format!("{}{}", kind.prefix(), name)
+    }
+
+    fn wait_key(kind: LockKind, name: &str) -> String {
+        format!(
+            "login-wait:{}",
+            &Self::fail_key(kind, name)["login-fail:".len()..]
+        )
+    }
+}
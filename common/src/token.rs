@@ -0,0 +1,271 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use data_encoding::HEXLOWER;
+    use rand::{distributions::Alphanumeric, thread_rng, Rng};
+    use sha2::{Digest, Sha256};
+    use crate::{DateTime, Error, Result, Store, User};
+}}
+
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the tokens, so that leaked tokens are easy to recognize
+#[cfg(feature = "ssr")]
+const TOKEN_PREFIX: &str = "chat_";
+
+/// Length of the random part of the tokens
+#[cfg(feature = "ssr")]
+const TOKEN_LENGTH: usize = 40;
+
+/// Seconds between the updates of the last used time
+#[cfg(feature = "ssr")]
+const TOUCH_INTERVAL: i64 = 60;
+
+// ==================== // TokenScope // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenScope {
+    ReadMessages,
+    SendMessages,
+    ManageFiles,
+}
+
+impl TokenScope {
+    pub const ALL: [Self; 3] = [Self::ReadMessages, Self::SendMessages, Self::ManageFiles];
+
+    /// Bit of the scope in the stored scopes
+    ///
+    pub fn bit(&self) -> i64 {
+        match self {
+            Self::ReadMessages => 1,
+            Self::SendMessages => 2,
+            Self::ManageFiles => 4,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ReadMessages => "Read messages",
+            Self::SendMessages => "Send messages",
+            Self::ManageFiles => "Manage files",
+        }
+    }
+}
+
+// ==================== // ApiToken // ==================== //
+
+/// A personal access token for scripts, only its hash is stored
+///
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    /// The beginning of the token to recognize it
+    pub prefix: String,
+    /// Bits of the scopes
+    pub scopes: i64,
+    /// Unix timestamp after which the token expires, zero if it never expires
+    pub expire_at: i64,
+    /// Zero if the token has never been used
+    pub last_used_at: i64,
+    pub create_at: i64,
+}
+
+impl ApiToken {
+    /// Check whether the token is granted the scope
+    ///
+    pub fn allows(&self, scope: TokenScope) -> bool {
+        self.scopes & scope.bit() != 0
+    }
+
+    /// Labels of the granted scopes
+    ///
+    pub fn scope_labels(&self) -> Vec<&'static str> {
+        TokenScope::ALL
+            .iter()
+            .filter(|v| self.allows(**v))
+            .map(TokenScope::label)
+            .collect()
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expire_at > 0 && self.expire_at <= now
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl ApiToken {
+    /// List the tokens of the user, the newest first
+    ///
+    pub async fn list(user_id: i64, store: &Store) -> Result<Vec<Self>> {
+        let tokens: Vec<Self> = sqlx::query_as(
+            "
+            SELECT id, user_id, name, prefix, scopes, expire_at, last_used_at, create_at
+            FROM api_tokens WHERE user_id = $1 ORDER BY id DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&store.pool)
+        .await?;
+        Ok(tokens)
+    }
+
+    /// Revoke a token of the user
+    ///
+    pub async fn revoke(user_id: i64, token_id: i64, store: &Store) -> Result<()> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE id = $1 AND user_id = $2")
+            .bind(token_id)
+            .bind(user_id)
+            .execute(&store.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Revoke all tokens of the user, e.g. when it is deactivated
+    ///
+    pub async fn revoke_all(user_id: i64, store: &Store) -> Result<()> {
+        sqlx::query("DELETE FROM api_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&store.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the user of a token, the token must not be expired and its user must be
+    /// active
+    ///
+    pub async fn verify(token: &str, store: &Store) -> Result<(User, Self)> {
+        let api_token: Option<Self> = sqlx::query_as(
+            "
+            SELECT id, user_id, name, prefix, scopes, expire_at, last_used_at, create_at
+            FROM api_tokens WHERE token_hash = $1",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&store.pool)
+        .await?;
+
+        let now = DateTime::now().timestamp;
+        let api_token = match api_token {
+            Some(v) if !v.is_expired(now) => v,
+            _ => return Err(Error::Unauthorized),
+        };
+
+        let user = User::get(api_token.user_id, store)
+            .await
+            .map_err(|_| Error::Unauthorized)?;
+        if !user.active {
+            return Err(Error::Unauthorized);
+        }
+        user.ensure_password_changed()?;
+
+        if now - api_token.last_used_at > TOUCH_INTERVAL {
+            sqlx::query("UPDATE api_tokens SET last_used_at = $1 WHERE id = $2")
+                .bind(now)
+                .bind(api_token.id)
+                .execute(&store.pool)
+                .await?;
+        }
+        Ok((user, api_token))
+    }
+
+    /// Ensure the token is granted the scope
+    ///
+    pub fn require(&self, scope: TokenScope) -> Result<()> {
+        if self.allows(scope) {
+            Ok(())
+        } else {
+            Err(Error::Forbidden)
+        }
+    }
+
+    /// The name of the token among the sessions of the hub, so that its sockets are
+    /// closed when it is revoked
+    ///
+    pub fn session_key(token_id: i64) -> String {
+        format!("token:{}", token_id)
+    }
+}
+
+// ==================== // InsertTokenArg // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct InsertTokenArg {
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 64, message = "Token name cannot be empty"))
+    )]
+    pub name: String,
+    pub read_messages: bool,
+    pub send_messages: bool,
+    pub manage_files: bool,
+    /// Days until the token expires, zero if it never expires
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 0, max = 3650, message = "Invalid expiry"))
+    )]
+    pub expire_days: i64,
+}
+
+impl InsertTokenArg {
+    /// Create a token of the user, returns the token which is shown only once
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn call(&self, user_id: i64, store: &Store) -> Result<String> {
+        let scopes = [
+            (self.read_messages, TokenScope::ReadMessages),
+            (self.send_messages, TokenScope::SendMessages),
+            (self.manage_files, TokenScope::ManageFiles),
+        ]
+        .iter()
+        .filter(|(granted, _)| *granted)
+        .fold(0, |acc, (_, scope)| acc | scope.bit());
+        if scopes == 0 {
+            return Err(Error::BadRequest(String::from(
+                "The token needs at least one scope",
+            )));
+        }
+
+        let now = DateTime::now().timestamp;
+        let expire_at = match self.expire_days {
+            0 => 0,
+            days => now + days * 86400,
+        };
+
+        let random: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        let token = format!("{}{}", TOKEN_PREFIX, random);
+        let prefix = token[..TOKEN_PREFIX.len() + 6].to_owned();
+
+        sqlx::query(
+            "
+            INSERT INTO api_tokens (user_id, name, prefix, token_hash, scopes, expire_at, create_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(user_id)
+        .bind(self.name.trim())
+        .bind(prefix)
+        .bind(hash_token(&token))
+        .bind(scopes)
+        .bind(expire_at)
+        .bind(now)
+        .execute(&store.pool)
+        .await?;
+
+        Ok(token)
+    }
+}
+
+// ==================== // UTILS // ==================== //
+
+/// The tokens are random enough that a plain hash cannot be reversed
+///
+#[cfg(feature = "ssr")]
+fn hash_token(token: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,308 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use std::io::Cursor;
+    use data_encoding::{BASE32_NOPAD, BASE64};
+    use hmac::{Hmac, Mac};
+    use image::{DynamicImage, ImageFormat, Luma};
+    use qrcode::QrCode;
+    use rand::{distributions::Alphanumeric, thread_rng, Rng};
+    use redis::AsyncCommands;
+    use sha1::Sha1;
+    use sha2::{Digest, Sha256};
+    use crate::{percent_encode, DateTime, Error, Result, Store, User};
+    use crate::user::verify_password;
+}}
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the issuer shown in the authenticator apps
+#[cfg(feature = "ssr")]
+const ISSUER: &str = "Chat";
+
+/// Seconds of a time step of the codes
+#[cfg(feature = "ssr")]
+const TIME_STEP: i64 = 30;
+
+/// Number of recovery codes given when 2FA is enabled
+#[cfg(feature = "ssr")]
+const RECOVERY_CODES: usize = 10;
+
+/// Seconds to confirm a new secret before it is discarded
+#[cfg(feature = "ssr")]
+const SETUP_EXPIRE: u64 = 600;
+
+// ==================== // TwoFactorStatus // ==================== //
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    /// Number of unused recovery codes
+    pub recovery_codes: i64,
+}
+
+/// A new secret waiting to be confirmed with a code
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TwoFactorSetup {
+    /// Base32 secret for entering manually
+    pub secret: String,
+    /// QR code of the `otpauth` uri as a data url
+    pub qrcode: String,
+}
+
+// ==================== // TwoFactor // ==================== //
+
+/// Time-based one-time passwords (RFC 6238) with SHA-1, 6 digits and 30 seconds
+/// steps, which all common authenticator apps support
+///
+pub struct TwoFactor;
+
+#[cfg(feature = "ssr")]
+impl TwoFactor {
+    /// Get whether 2FA is enabled for the user
+    ///
+    pub async fn status(user_id: i64, store: &Store) -> Result<TwoFactorStatus> {
+        let enabled = Self::secret(user_id, store).await?.is_some();
+        let (recovery_codes,): (i64,) =
+            sqlx::query_as("SELECT count(*) FROM recovery_codes WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&store.pool)
+                .await?;
+
+        Ok(TwoFactorStatus {
+            enabled,
+            recovery_codes,
+        })
+    }
+
+    /// Generate a new secret for the user, it is kept in the redis until confirmed
+    ///
+    pub async fn setup(user: &User, store: &Store) -> Result<TwoFactorSetup> {
+        if Self::secret(user.id, store).await?.is_some() {
+            return Err(Error::BadRequest(String::from(
+                "Two-factor authentication is already enabled",
+            )));
+        }
+
+        let bytes: [u8; 20] = thread_rng().gen();
+        let secret = BASE32_NOPAD.encode(&bytes);
+
+        let uri = format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits=6&period={}",
+            ISSUER,
+            percent_encode(&user.username),
+            secret,
+            ISSUER,
+            TIME_STEP
+        );
+        let qrcode = qrcode_data_url(&uri)?;
+
+        let mut con = store.con.clone();
+        let _: () = con
+            .set_ex(Self::setup_key(user.id), &secret, SETUP_EXPIRE)
+            .await?;
+
+        Ok(TwoFactorSetup { secret, qrcode })
+    }
+
+    /// Confirm the new secret with a code from the app, returns the recovery codes
+    ///
+    pub async fn enable(user_id: i64, code: &str, store: &Store) -> Result<Vec<String>> {
+        let mut con = store.con.clone();
+        let secret: Option<String> = con.get(Self::setup_key(user_id)).await?;
+        let secret = secret.ok_or(Error::BadRequest(String::from(
+            "The setup has expired, please start again",
+        )))?;
+
+        if !Self::check_code(user_id, &secret, code, store).await? {
+            return Err(Error::BadRequest(String::from("The code is incorrect")));
+        }
+
+        sqlx::query("UPDATE users SET totp_secret = $1 WHERE id = $2")
+            .bind(&secret)
+            .bind(user_id)
+            .execute(&store.pool)
+            .await?;
+        let _: () = con.del(Self::setup_key(user_id)).await?;
+
+        Self::new_recovery_codes(user_id, store).await
+    }
+
+    /// Turn off 2FA after confirming the password
+    ///
+    pub async fn disable(user_id: i64, password: &str, store: &Store) -> Result<()> {
+        let hash: String = sqlx::query_scalar("SELECT password FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&store.pool)
+            .await?;
+        verify_password(&hash, password)?;
+
+        Self::reset(user_id, store).await
+    }
+
+    /// Remove the secret and recovery codes of the user, e.g. by an admin when the
+    /// user lost the device
+    ///
+    pub async fn reset(user_id: i64, store: &Store) -> Result<()> {
+        if Self::secret(user_id, store).await?.is_none() {
+            return Err(Error::BadRequest(String::from(
+                "Two-factor authentication is not enabled",
+            )));
+        }
+
+        sqlx::query("UPDATE users SET totp_secret = '' WHERE id = $1")
+            .bind(user_id)
+            .execute(&store.pool)
+            .await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&store.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Verify a code from the app or a recovery code, which can only be used once
+    ///
+    pub async fn verify(user_id: i64, code: &str, store: &Store) -> Result<()> {
+        let secret = Self::secret(user_id, store)
+            .await?
+            .ok_or(Error::Unauthorized)?;
+
+        let code = code.trim();
+        let valid = if code.len() == 6 {
+            Self::check_code(user_id, &secret, code, store).await?
+        } else {
+            sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1 AND code = $2")
+                .bind(user_id)
+                .bind(hash_recovery_code(code))
+                .execute(&store.pool)
+                .await?
+                .rows_affected()
+                == 1
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(Error::BadRequest(String::from("The code is incorrect"))),
+        }
+    }
+
+    /// Get the base32 secret of the user, none if 2FA is not enabled
+    ///
+    pub async fn secret(user_id: i64, store: &Store) -> Result<Option<String>> {
+        let secret: String = sqlx::query_scalar("SELECT totp_secret FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&store.pool)
+            .await?;
+        Ok(Some(secret).filter(|v| !v.is_empty()))
+    }
+
+    /// Replace the recovery codes of the user, only their hashes are stored
+    ///
+    async fn new_recovery_codes(user_id: i64, store: &Store) -> Result<Vec<String>> {
+        let mut tx = store.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut codes = Vec::with_capacity(RECOVERY_CODES);
+        for _ in 0..RECOVERY_CODES {
+            let raw: String = thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(10)
+                .map(|v| char::from(v).to_ascii_lowercase())
+                .collect();
+            let code = format!("{}-{}", &raw[..5], &raw[5..]);
+
+            sqlx::query("INSERT INTO recovery_codes (user_id, code) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(hash_recovery_code(&code))
+                .execute(&mut *tx)
+                .await?;
+            codes.push(code);
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// Check the code in the current and adjacent time steps, a code cannot be used
+    /// twice so that an observed code is useless
+    ///
+    async fn check_code(user_id: i64, secret: &str, code: &str, store: &Store) -> Result<bool> {
+        let Ok(code) = code.trim().parse::<u32>() else {
+            return Ok(false);
+        };
+        let key = BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .map_err(|_| Error::InternalServer)?;
+
+        let current = DateTime::now().timestamp / TIME_STEP;
+        for step in current - 1..=current + 1 {
+            if totp_code(&key, step)? != code {
+                continue;
+            }
+
+            let mut con = store.con.clone();
+            let fresh: Option<String> = redis::cmd("SET")
+                .arg(format!("totp-used:{}:{}", user_id, step))
+                .arg(1)
+                .arg("NX")
+                .arg("EX")
+                .arg(TIME_STEP * 3)
+                .query_async(&mut con)
+                .await?;
+            return Ok(fresh.is_some());
+        }
+        Ok(false)
+    }
+
+    /// Create a key of the secret in setup for redis
+    ///
+    fn setup_key(user_id: i64) -> String {
+        format!("totp-setup:{}", user_id)
+    }
+}
+
+/// Compute the 6 digits code of the time step (RFC 4226)
+///
+#[cfg(feature = "ssr")]
+fn totp_code(key: &[u8], step: i64) -> Result<u32> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).map_err(|_| Error::InternalServer)?;
+    mac.update(&(step as u64).to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    Ok(value % 1_000_000)
+}
+
+/// Hash a recovery code ignoring case and the dash
+///
+#[cfg(feature = "ssr")]
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|v| v.to_ascii_lowercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// Render the text as a QR code in a PNG data url
+///
+#[cfg(feature = "ssr")]
+pub(crate) fn qrcode_data_url(text: &str) -> Result<String> {
+    let code = QrCode::new(text).map_err(|_| Error::InternalServer)?;
+    let image = code.render::<Luma<u8>>().min_dimensions(200, 200).build();
+    let mut png = Cursor::new(Vec::new());
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|_| Error::InternalServer)?;
+    Ok(format!("data:image/png;base64,{}", BASE64.encode(png.get_ref())))
+}
@@ -0,0 +1,320 @@
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use std::{io::SeekFrom, path::PathBuf, sync::Arc};
+    use axum::body::Bytes;
+    use tokio::{fs::OpenOptions, io::{AsyncSeekExt, AsyncWriteExt}};
+    use uuid::Uuid;
+    use crate::file::{hash_file, split_filename, stringify_size};
+    use crate::{Config, DateTime, Error, FileManager, FileMeta, Result, StorageUsage, Store};
+}}
+
+use serde::{Deserialize, Serialize};
+
+/// Max size of a chunk in a resumable upload
+pub const UPLOAD_CHUNK_SIZE: u64 = 1 << 20;
+
+/// Seconds before an unfinished upload is removed
+#[cfg(feature = "ssr")]
+const UPLOAD_EXPIRE: i64 = 24 * 60 * 60;
+
+/// Unfinished uploads a user can have at once
+#[cfg(feature = "ssr")]
+const MAX_OPEN_UPLOADS: i64 = 5;
+
+// ==================== // UploadSession // ==================== //
+
+/// A resumable upload of a shared file, chunks are appended in order
+///
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct UploadSession {
+    pub id: String,
+    pub name: String,
+    pub size: i64,
+    /// Bytes received, the next chunk starts here
+    pub received: i64,
+}
+
+#[cfg(feature = "ssr")]
+impl UploadSession {
+    /// Get the unfinished upload of the user
+    ///
+    async fn get(user_id: i64, id: &str, store: &Store) -> Result<Self> {
+        let session: Option<Self> = sqlx::query_as(
+            "SELECT id, name, size, received FROM upload_sessions WHERE id = $1 AND user_id = $2",
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&store.pool)
+        .await?;
+
+        session.ok_or(Error::BadRequest(String::from(
+            "Upload not found, please retry",
+        )))
+    }
+
+    /// Append a chunk starting at the offset, returns the bytes received.
+    /// A chunk received before is ignored so that retries are safe
+    ///
+    pub async fn write_chunk(
+        user_id: i64,
+        id: &str,
+        offset: i64,
+        chunk: Bytes,
+        store: &Store,
+    ) -> Result<i64> {
+        let session = Self::get(user_id, id, store).await?;
+
+        if chunk.len() as u64 > UPLOAD_CHUNK_SIZE {
+            return Err(Error::BadRequest(String::from("Chunk is too large")));
+        }
+        let end = offset + chunk.len() as i64;
+        if end > session.size {
+            return Err(Error::BadRequest(String::from(
+                "Chunk exceeds the file size",
+            )));
+        }
+        if end <= session.received {
+            return Ok(session.received);
+        }
+        if offset != session.received {
+            return Err(Error::BadRequest(String::from("Unexpected chunk offset")));
+        }
+
+        // drop the bytes of an interrupted write before appending
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(chunk_path(&session.id))
+            .await?;
+        file.set_len(offset as u64).await?;
+        file.seek(SeekFrom::Start(offset as u64)).await?;
+        file.write_all(&chunk).await?;
+        file.flush().await?;
+
+        sqlx::query("UPDATE upload_sessions SET received = $1, update_at = $2 WHERE id = $3")
+            .bind(end)
+            .bind(DateTime::now().timestamp)
+            .bind(&session.id)
+            .execute(&store.pool)
+            .await?;
+        Ok(end)
+    }
+
+    /// Check the assembled file against the SHA-256 checksum and save it in share directory
+    ///
+    pub async fn finish(
+        user_id: i64,
+        id: &str,
+        checksum: &str,
+        store: &Store,
+        config: Arc<Config>,
+    ) -> Result<FileMeta> {
+        let session = Self::get(user_id, id, store).await?;
+        if session.received != session.size {
+            return Err(Error::BadRequest(String::from("Upload is not complete")));
+        }
+
+        let (mime,): (String,) = sqlx::query_as("SELECT mime FROM upload_sessions WHERE id = $1")
+            .bind(&session.id)
+            .fetch_one(&store.pool)
+            .await?;
+
+        // the upload is finished either way, a mismatch has to start over
+        Self::remove(&session.id, store).await?;
+        let path = chunk_path(&session.id);
+
+        let hashed = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || hash_file(&path))
+                .await
+                .map_err(|_| Error::InternalServer)?
+        };
+        match hashed {
+            Ok((hash, size)) if hash == checksum.to_lowercase() && size == session.size as u64 => {}
+            _ => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(Error::BadRequest(String::from(
+                    "Checksum mismatch, please upload again",
+                )));
+            }
+        }
+
+        // other uploads may have used up the quota in the meantime
+        let limit = StorageUsage::limit(user_id, config.max_file_size, store, &config).await;
+        if limit.map(|v| v < session.size as u64).unwrap_or(true) {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(Error::BadRequest(String::from("Storage quota exceeded")));
+        }
+
+        let (name, ext) = split_filename(&session.name);
+        let img = mime.starts_with("image");
+        FileManager::share_local_file(user_id, name, &ext, img, &path, store, &config).await
+    }
+
+    /// Remove the record of an upload
+    ///
+    async fn remove(id: &str, store: &Store) -> Result<()> {
+        sqlx::query("DELETE FROM upload_sessions WHERE id = $1")
+            .bind(id)
+            .execute(&store.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the uploads not updated within the expire time and their partial files
+    ///
+    pub async fn clean_abandoned(store: &Store) -> Result<()> {
+        let expire_at = DateTime::now().timestamp - UPLOAD_EXPIRE;
+        let ids: Vec<(String,)> =
+            sqlx::query_as("SELECT id FROM upload_sessions WHERE update_at < $1")
+                .bind(expire_at)
+                .fetch_all(&store.pool)
+                .await?;
+
+        for (id,) in ids {
+            let _ = tokio::fs::remove_file(chunk_path(&id)).await;
+            Self::remove(&id, store).await?;
+        }
+        Ok(())
+    }
+}
+
+// ==================== // InitUploadArg // ==================== //
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "ssr", derive(validator::Validate))]
+pub struct InitUploadArg {
+    /// Identify the same file of a user to resume the upload
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 512, message = "Invalid upload key"))
+    )]
+    pub key: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(min = 1, max = 256, message = "Invalid file name"))
+    )]
+    pub name: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(length(max = 128, message = "Invalid file type"))
+    )]
+    pub mime: String,
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 1, message = "File cannot be empty"))
+    )]
+    pub size: i64,
+}
+
+impl InitUploadArg {
+    /// Start a new upload, or resume the unfinished upload of the same file
+    ///
+    #[cfg(feature = "ssr")]
+    pub async fn init(
+        &self,
+        user_id: i64,
+        store: &Store,
+        config: Arc<Config>,
+    ) -> Result<UploadSession> {
+        UploadSession::clean_abandoned(store).await?;
+
+        // the other unfinished uploads of the user hold their declared size, so that
+        // the partial files cannot grow beyond the quota
+        let (open, reserved): (i64, i64) = sqlx::query_as(
+            "
+            SELECT COUNT(*), COALESCE(SUM(size), 0) FROM upload_sessions
+            WHERE user_id = $1 AND key != $2",
+        )
+        .bind(user_id)
+        .bind(&self.key)
+        .fetch_one(&store.pool)
+        .await?;
+        if open >= MAX_OPEN_UPLOADS {
+            return Err(Error::BadRequest(String::from(
+                "Too many uploads in progress, please finish them first",
+            )));
+        }
+
+        let limit = StorageUsage::limit(user_id, config.max_file_size, store, &config).await?;
+        if self.size as u64 > limit {
+            return Err(Error::BadRequest(format!(
+                "File exceeds the limit of {}",
+                stringify_size(limit as f64)
+            )));
+        }
+        let usage = StorageUsage::get(user_id, store, &config).await?;
+        if usage.used + reserved as u64 + self.size as u64 > usage.quota {
+            return Err(Error::BadRequest(String::from(
+                "Storage quota exceeded by the uploads in progress",
+            )));
+        }
+
+        let now = DateTime::now().timestamp;
+        let existing: Option<UploadSession> = sqlx::query_as(
+            "
+            SELECT id, name, size, received FROM upload_sessions
+            WHERE user_id = $1 AND key = $2",
+        )
+        .bind(user_id)
+        .bind(&self.key)
+        .fetch_optional(&store.pool)
+        .await?;
+
+        match existing {
+            Some(mut session) if session.size == self.size => {
+                // the partial file may be shorter after a restart of the server
+                let len = tokio::fs::metadata(chunk_path(&session.id))
+                    .await
+                    .map(|v| v.len() as i64)
+                    .unwrap_or_default();
+                session.received = session.received.min(len);
+
+                sqlx::query(
+                    "UPDATE upload_sessions SET received = $1, update_at = $2 WHERE id = $3",
+                )
+                .bind(session.received)
+                .bind(now)
+                .bind(&session.id)
+                .execute(&store.pool)
+                .await?;
+                return Ok(session);
+            }
+            Some(session) => {
+                // the file changed since, start over
+                let _ = tokio::fs::remove_file(chunk_path(&session.id)).await;
+                UploadSession::remove(&session.id, store).await?;
+            }
+            None => {}
+        }
+
+        let session = UploadSession {
+            id: Uuid::new_v4().to_string(),
+            name: self.name.trim().to_owned(),
+            size: self.size,
+            received: 0,
+        };
+        sqlx::query(
+            "
+            INSERT INTO upload_sessions (id, user_id, key, name, mime, size, update_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&session.id)
+        .bind(user_id)
+        .bind(&self.key)
+        .bind(&session.name)
+        .bind(&self.mime)
+        .bind(session.size)
+        .bind(now)
+        .execute(&store.pool)
+        .await?;
+        Ok(session)
+    }
+}
+
+/// Get the path of the partial file of an upload
+///
+#[cfg(feature = "ssr")]
+fn chunk_path(id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chat-upload-{}.part", id))
+}
@@ -1,6 +1,8 @@
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    use std::borrow::Cow;
     use rand::Rng;
     use redis::AsyncCommands;
+    use validator::ValidationError;
     use crate::{Error, Result, Store};
 }}
 
@@ -25,6 +27,10 @@ pub struct User {
     pub avatar: String,
     pub role: UserRole,
     pub active: bool,
+    /// The password was set by an admin and must be changed before using the app
+    #[serde(default)]
+    #[cfg_attr(feature = "ssr", sqlx(default))]
+    pub must_change_password: bool,
 }
 
 impl User {
@@ -43,7 +49,7 @@ impl User {
 
         let user: User = sqlx::query_as(
             "
-            SELECT id, username, nickname, avatar, role, active FROM users
+            SELECT id, username, nickname, avatar, role, active, must_change_password FROM users
             WHERE id = $1 AND active = 1",
         )
         .bind(&user_id)
@@ -61,7 +67,7 @@ impl User {
     pub async fn find(keyword: &str, store: &Store) -> Result<Option<Self>> {
         let user: Option<Self> = sqlx::query_as(
             "
-            SELECT id, username, nickname, avatar, role, active FROM users
+            SELECT id, username, nickname, avatar, role, active, must_change_password FROM users
             WHERE username = $1 OR nickname = $1",
         )
         .bind(&keyword)
@@ -82,6 +88,10 @@ impl User {
             .bind(&user_id)
             .execute(&store.pool)
             .await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(&user_id)
+            .execute(&store.pool)
+            .await?;
 
         let key = Self::make_key(user_id);
         let mut con = store.con.clone();
@@ -98,6 +108,16 @@ impl User {
         }
     }
 
+    /// Refuse to use the app until the password set by an admin is changed
+    ///
+    #[cfg(feature = "ssr")]
+    pub fn ensure_password_changed(&self) -> Result<()> {
+        if self.must_change_password {
+            return Err(Error::PasswordChange);
+        }
+        Ok(())
+    }
+
     /// Returns a key of user for redis
     ///
     #[cfg(feature = "ssr")]
@@ -150,7 +170,8 @@ impl ListUsersArg {
 
         let result: Vec<ListUsersRow> = sqlx::query_as(
             "
-            SELECT id, username, nickname, avatar, role, active, count(*) OVER() AS total
+            SELECT id, username, nickname, avatar, role, active, must_change_password,
+                count(*) OVER() AS total
             FROM users WHERE role = coalesce($1, role) LIMIT $2 OFFSET $3",
         )
         .bind(self.role)
@@ -184,6 +205,7 @@ struct ListUsersRow {
     avatar: String,
     role: UserRole,
     active: bool,
+    must_change_password: bool,
     total: Option<i32>,
 }
 
@@ -197,6 +219,7 @@ impl From<ListUsersRow> for User {
             avatar: v.avatar,
             role: v.role,
             active: v.active,
+            must_change_password: v.must_change_password,
         }
     }
 }
@@ -218,6 +241,9 @@ pub struct InsertUserArg {
     pub password: String,
     pub role: UserRole,
     pub active: bool,
+    /// Require the user to change the password at the first login
+    #[serde(default)]
+    pub must_change_password: bool,
 }
 
 impl InsertUserArg {
@@ -229,8 +255,8 @@ impl InsertUserArg {
 
         sqlx::query(
             "
-            INSERT INTO users (username, password, nickname, avatar, role, active)
-            VALUES ($1, $2, $3, $4, $5, $6)",
+            INSERT INTO users (username, password, nickname, avatar, role, active, must_change_password)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
         )
         .bind(&self.username)
         .bind(&hash)
@@ -238,6 +264,7 @@ impl InsertUserArg {
         .bind("")
         .bind(&self.role)
         .bind(&self.active)
+        .bind(&self.must_change_password)
         .execute(&store.pool)
         .await?;
 
@@ -274,10 +301,17 @@ pub struct UpdateUserArg {
     pub avatar: Option<String>,
     pub role: Option<UserRole>,
     pub active: Option<bool>,
+    /// Storage quota in megabytes, zero for the default quota
+    #[cfg_attr(
+        feature = "ssr",
+        validate(range(min = 0, message = "Quota cannot be negative"))
+    )]
+    pub quota: Option<i64>,
 }
 
 impl UpdateUserArg {
-    /// Update the user in the database
+    /// Update the user in the database, a password set by an admin must be changed
+    /// by the user at the next login
     ///
     #[cfg(feature = "ssr")]
     pub async fn call(&self, store: &Store) -> Result<User> {
@@ -295,11 +329,13 @@ impl UpdateUserArg {
             SET
                 username = coalesce($1, username),
                 password = coalesce($2, password),
+                must_change_password = CASE WHEN $2 IS NULL THEN must_change_password ELSE 1 END,
                 nickname = coalesce($3, nickname),
                 avatar = coalesce($4, avatar),
                 role = coalesce($5, role),
-                active = coalesce($6, active)
-            WHERE id = $7
+                active = coalesce($6, active),
+                quota = coalesce($7, quota)
+            WHERE id = $8
             RETURNING *",
         )
         .bind(&self.username)
@@ -308,14 +344,20 @@ impl UpdateUserArg {
         .bind(&self.avatar)
         .bind(&self.role)
         .bind(&self.active)
+        .bind(&self.quota)
         .bind(&self.id)
         .fetch_one(&store.pool)
         .await?;
 
+        // a deactivated user is not cached, so that it is no longer found
         let mut con = store.con.clone();
         let key = user.key();
-        let user_str = serde_json::to_string(&user)?;
-        let _: () = con.set_ex(&key, user_str, 604800).await?;
+        if user.active {
+            let user_str = serde_json::to_string(&user)?;
+            let _: () = con.set_ex(&key, user_str, 604800).await?;
+        } else {
+            let _: () = con.del(&key).await?;
+        }
         Ok(user)
     }
 }
@@ -334,18 +376,19 @@ pub struct UpdatePasswordArg {
     pub old_password: String,
     #[cfg_attr(
         feature = "ssr",
-        validate(length(min = 6, max = 128, message = "New password is too short"))
+        validate(custom(function = "validate_password_strength"))
     )]
     pub new_password: String,
     #[cfg_attr(
         feature = "ssr",
-        validate(length(min = 6, max = 128, message = "Confirm password is too short"))
+        validate(length(min = 1, max = 128, message = "Confirm password cannot be empty"))
     )]
     pub confirm_password: String,
 }
 
 impl UpdatePasswordArg {
-    /// Update the password in the database
+    /// Update the password in the database, which also clears the requirement to
+    /// change it
     ///
     #[cfg(feature = "ssr")]
     pub async fn call(&self, store: &Store) -> Result<()> {
@@ -354,6 +397,11 @@ impl UpdatePasswordArg {
                 "The password confirmation does not match.",
             )));
         }
+        if self.new_password == self.old_password {
+            return Err(Error::BadRequest(String::from(
+                "The new password must be different from the old one.",
+            )));
+        }
 
         let hash: String = sqlx::query_scalar("SELECT password FROM users WHERE id = $1")
             .bind(self.id)
@@ -362,12 +410,15 @@ impl UpdatePasswordArg {
         verify_password(&hash, &self.old_password)?;
 
         let password = hash_password(&self.new_password)?;
-        sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+        sqlx::query("UPDATE users SET password = $1, must_change_password = 0 WHERE id = $2")
             .bind(password)
             .bind(self.id)
             .execute(&store.pool)
             .await?;
 
+        // the cached user still has the old flag
+        let mut con = store.con.clone();
+        let _: () = con.del(User::make_key(self.id)).await?;
         Ok(())
     }
 }
@@ -384,6 +435,7 @@ pub struct UserEntity {
     avatar: String,
     role: UserRole,
     active: bool,
+    must_change_password: bool,
 }
 
 #[cfg(feature = "ssr")]
@@ -418,6 +470,7 @@ impl From<UserEntity> for User {
             avatar: val.avatar,
             role: val.role,
             active: val.active,
+            must_change_password: val.must_change_password,
         }
     }
 }
@@ -427,7 +480,7 @@ impl From<UserEntity> for User {
 /// Returns the hash of a password string
 ///
 #[cfg(feature = "ssr")]
-fn hash_password(pwd: &str) -> Result<String> {
+pub(crate) fn hash_password(pwd: &str) -> Result<String> {
     let salt: [u8; 32] = rand::thread_rng().gen();
     let config = argon2::Config::rfc9106_low_mem();
     let hash = argon2::hash_encoded(pwd.as_bytes(), &salt, &config)?;
@@ -437,9 +490,46 @@ fn hash_password(pwd: &str) -> Result<String> {
 /// Verifies the password with the encoded hash
 ///
 #[cfg(feature = "ssr")]
-fn verify_password(hash: &str, password: &str) -> Result<()> {
+pub(crate) fn verify_password(hash: &str, password: &str) -> Result<()> {
     if !argon2::verify_encoded(hash, password.as_bytes())? {
         return Err(Error::BadRequest(String::from("The password is incorrect")));
     }
     Ok(())
 }
+
+/// Common passwords which are refused even if long enough
+#[cfg(feature = "ssr")]
+const WEAK_PASSWORDS: [&str; 8] = [
+    "password1",
+    "password123",
+    "passw0rd",
+    "qwerty123",
+    "abcd1234",
+    "abc12345",
+    "1q2w3e4r",
+    "letmein1",
+];
+
+/// Rules of the passwords chosen by users: at least 8 characters with both letters
+/// and digits, and not a common password
+///
+#[cfg(feature = "ssr")]
+pub(crate) fn validate_password_strength(password: &str) -> core::result::Result<(), ValidationError> {
+    let message = if password.chars().count() < 8 {
+        "The password must have at least 8 characters"
+    } else if password.len() > 128 {
+        "The password is too long"
+    } else if !password.chars().any(|c| c.is_alphabetic())
+        || !password.chars().any(|c| c.is_ascii_digit())
+    {
+        "The password must contain both letters and digits"
+    } else if WEAK_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+        "The password is too common"
+    } else {
+        return Ok(());
+    };
+
+    let mut err = ValidationError::new("weak_password");
+    err.message = Some(Cow::from(message));
+    Err(err)
+}
@@ -0,0 +1,302 @@
+#![cfg(feature = "ssr")]
+
+use axum::{
+    body::Body,
+    extract::WebSocketUpgrade,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use tower::ServiceExt;
+
+use common::{ApiToken, CsrfConfig, CsrfGuard};
+
+const HOST: &str = "chat.example.com";
+const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+fn config() -> CsrfConfig {
+    CsrfConfig {
+        allowed_origins: vec![String::from("https://app.example.com")],
+        trust_proxy: false,
+    }
+}
+
+fn app() -> Router {
+    Router::new()
+        .route("/", get(|| async { "page" }))
+        .route("/api/update_password", post(|| async { "updated" }))
+        .route("/api/v1/rooms/:room_id/messages", post(|| async { "sent" }))
+        .route("/s/:token", post(|| async { "download" }))
+        .route("/ws", get(ws_handler))
+        .layer(middleware::from_fn_with_state(
+            config(),
+            CsrfGuard::middleware,
+        ))
+}
+
+async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(|_| async {})
+}
+
+/// The access token as the server inserts it once verified
+fn api_token() -> ApiToken {
+    ApiToken {
+        id: 1,
+        user_id: 1,
+        name: String::from("script"),
+        prefix: String::from("chat_0123"),
+        scopes: 7,
+        expire_at: 0,
+        last_used_at: 0,
+        create_at: 0,
+    }
+}
+
+async fn send(request: Request<Body>) -> Response {
+    app().oneshot(request).await.expect("failed to send the request")
+}
+
+async fn send_verified(request: Request<Body>) -> Response {
+    app()
+        .layer(Extension(api_token()))
+        .oneshot(request)
+        .await
+        .expect("failed to send the request")
+}
+
+fn bearer(path: &str) -> Request<Body> {
+    Request::post(path)
+        .header(header::HOST, HOST)
+        .header(header::AUTHORIZATION, "Bearer chat_token")
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn upgrade(origin: &str) -> Request<Body> {
+    Request::get("/ws")
+        .header(header::HOST, HOST)
+        .header(header::ORIGIN, origin)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_VERSION, "13")
+        .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn server_fn(origin: Option<&str>, cookie: Option<&str>, token: Option<&str>) -> Request<Body> {
+    let mut builder = Request::post("/api/update_password").header(header::HOST, HOST);
+    if let Some(origin) = origin {
+        builder = builder.header(header::ORIGIN, origin);
+    }
+    if let Some(cookie) = cookie {
+        builder = builder.header(header::COOKIE, format!("csrf={}; sess=secret", cookie));
+    }
+    if let Some(token) = token {
+        builder = builder.header("x-csrf-token", token);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+fn headers(origin: Option<&str>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::HOST, HOST.parse().unwrap());
+    if let Some(origin) = origin {
+        headers.insert(header::ORIGIN, origin.parse().unwrap());
+    }
+    headers
+}
+
+// ==================== // Server functions // ==================== //
+
+#[tokio::test]
+async fn accepts_same_origin_with_token() {
+    let request = server_fn(Some("https://chat.example.com"), Some(TOKEN), Some(TOKEN));
+    assert_eq!(send(request).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn accepts_allowed_origin() {
+    let request = server_fn(Some("https://app.example.com"), Some(TOKEN), Some(TOKEN));
+    assert_eq!(send(request).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn rejects_cross_site_origin() {
+    let request = server_fn(Some("https://evil.example.net"), Some(TOKEN), Some(TOKEN));
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_null_origin() {
+    let request = server_fn(Some("null"), Some(TOKEN), Some(TOKEN));
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_cross_site_referer() {
+    let request = Request::post("/api/update_password")
+        .header(header::HOST, HOST)
+        .header(header::REFERER, "https://evil.example.net/form.html")
+        .header(header::COOKIE, format!("csrf={}", TOKEN))
+        .header("x-csrf-token", TOKEN)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_missing_token() {
+    let request = server_fn(Some("https://chat.example.com"), Some(TOKEN), None);
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_missing_cookie() {
+    let request = server_fn(Some("https://chat.example.com"), None, Some(TOKEN));
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_mismatched_token() {
+    let request = server_fn(
+        Some("https://chat.example.com"),
+        Some(TOKEN),
+        Some("fedcba9876543210fedcba9876543210"),
+    );
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_missing_origin_without_token() {
+    let request = server_fn(None, Some(TOKEN), None);
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_bearer_header_without_csrf_token() {
+    let request = bearer("/api/update_password");
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_unverified_bearer_token_on_api() {
+    let request = bearer("/api/v1/rooms/abc/messages");
+    assert_eq!(send(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn accepts_verified_bearer_token_on_api() {
+    let request = bearer("/api/v1/rooms/abc/messages");
+    assert_eq!(send_verified(request).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn rejects_verified_bearer_token_on_server_fn() {
+    let request = bearer("/api/update_password");
+    assert_eq!(send_verified(request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn exempt_form_is_checked_for_origin() {
+    let same_site = Request::post("/s/abc")
+        .header(header::HOST, HOST)
+        .header(header::ORIGIN, "https://chat.example.com")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(same_site).await.status(), StatusCode::OK);
+
+    let cross_site = Request::post("/s/abc")
+        .header(header::HOST, HOST)
+        .header(header::ORIGIN, "https://evil.example.net")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(cross_site).await.status(), StatusCode::FORBIDDEN);
+}
+
+// ==================== // Token cookie // ==================== //
+
+#[tokio::test]
+async fn issues_token_cookie() {
+    let request = Request::get("/")
+        .header(header::HOST, HOST)
+        .body(Body::empty())
+        .unwrap();
+    let response = send(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cookie = response
+        .headers()
+        .get(header::SET_COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .expect("the token cookie is missing");
+    assert!(cookie.starts_with("csrf="));
+    assert!(cookie.contains("SameSite=Strict"));
+    assert!(!cookie.contains("HttpOnly"));
+}
+
+#[tokio::test]
+async fn keeps_existing_token_cookie() {
+    let request = Request::get("/")
+        .header(header::HOST, HOST)
+        .header(header::COOKIE, format!("csrf={}", TOKEN))
+        .body(Body::empty())
+        .unwrap();
+    let response = send(request).await;
+    assert!(response.headers().get(header::SET_COOKIE).is_none());
+}
+
+// ==================== // WebSocket upgrade // ==================== //
+
+#[test]
+fn websocket_rejects_cross_site_origin() {
+    let headers = headers(Some("https://evil.example.net"));
+    assert!(CsrfGuard::check_origin(&headers, &config()).is_err());
+}
+
+#[test]
+fn websocket_accepts_same_origin() {
+    let headers = headers(Some("https://chat.example.com"));
+    assert!(CsrfGuard::check_origin(&headers, &config()).is_ok());
+}
+
+#[test]
+fn websocket_accepts_allowed_origin() {
+    let headers = headers(Some("https://app.example.com"));
+    assert!(CsrfGuard::check_origin(&headers, &config()).is_ok());
+}
+
+#[test]
+fn websocket_accepts_clients_without_origin() {
+    let headers = headers(None);
+    assert!(CsrfGuard::check_origin(&headers, &config()).is_ok());
+}
+
+#[test]
+fn websocket_uses_forwarded_host_behind_proxy() {
+    let mut headers = headers(Some("https://chat.example.com"));
+    headers.insert(header::HOST, "127.0.0.1:3000".parse().unwrap());
+    headers.insert("x-forwarded-host", HOST.parse().unwrap());
+    assert!(CsrfGuard::check_origin(&headers, &config()).is_err());
+
+    let config = CsrfConfig {
+        trust_proxy: true,
+        ..config()
+    };
+    assert!(CsrfGuard::check_origin(&headers, &config).is_ok());
+}
+
+#[tokio::test]
+async fn websocket_upgrade_rejects_cross_site_origin() {
+    let response = send(upgrade("https://evil.example.net")).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn websocket_upgrade_passes_same_origin() {
+    // the test request cannot be upgraded, so the socket itself answers 426
+    let response = send(upgrade("https://chat.example.com")).await;
+    assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+}
@@ -0,0 +1,24 @@
+// Sends the token of the `csrf` cookie back in the `X-CSRF-Token` header, the
+// server rejects the requests changing the state without it
+(() => {
+  const fetch = window.fetch;
+  const token = () =>
+    document.cookie
+      .split("; ")
+      .find((v) => v.startsWith("csrf="))
+      ?.slice("csrf=".length);
+
+  window.fetch = (input, init) => {
+    const value = token();
+    const url = new URL(input instanceof Request ? input.url : input, location.href);
+    if (value && url.origin === location.origin) {
+      if (input instanceof Request) {
+        input.headers.set("X-CSRF-Token", value);
+      } else {
+        init = { ...init, headers: new Headers(init?.headers) };
+        init.headers.set("X-CSRF-Token", value);
+      }
+    }
+    return fetch(input, init);
+  };
+})();
@@ -0,0 +1,239 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, Path, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::state::AppState;
+use common::{
+    ApiToken, Blob, Chats, CsrfGuard, Error, FileManager, Message, Room, RoomFile, StorageUsage,
+    TokenScope, User,
+};
+
+// ==================== // Routes // ==================== //
+
+/// The api for scripts under its own prefix, apart from the server functions. The
+/// access token is verified before the CSRF check, which lets these routes through
+/// without the token of the cookie
+///
+pub fn routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/rooms", get(list_rooms_handler))
+        .route(
+            "/api/v1/rooms/:room_id/messages",
+            get(list_messages_handler).post(send_message_handler),
+        )
+        .route("/api/v1/rooms/:room_id/files", post(send_file_handler))
+        .route("/api/v1/files/usage", get(storage_usage_handler))
+        .layer(middleware::from_fn_with_state(
+            state.config.csrf.clone(),
+            CsrfGuard::middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            token_middleware,
+        ))
+}
+
+// ==================== // TokenAuth // ==================== //
+
+/// The user of the personal access token in the `Authorization: Bearer` header,
+/// verified by [`token_middleware`]
+///
+pub struct TokenAuth(pub User, pub ApiToken);
+
+#[async_trait]
+impl FromRequestParts<AppState> for TokenAuth {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = parts.extensions.get::<User>().cloned();
+        let api_token = parts.extensions.get::<ApiToken>().cloned();
+        match (user, api_token) {
+            (Some(user), Some(api_token)) => Ok(TokenAuth(user, api_token)),
+            _ => Err(Error::Unauthorized),
+        }
+    }
+}
+
+/// Verify the access token of the request for [`TokenAuth`]
+///
+async fn token_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(token) = bearer_token(request.headers()) {
+        match ApiToken::verify(token, &state.store).await {
+            Ok((user, api_token)) => {
+                request.extensions_mut().insert(user);
+                request.extensions_mut().insert(api_token);
+            }
+            Err(err) => return err.into_response(),
+        }
+    }
+    next.run(request).await
+}
+
+/// Get the token in the `Authorization: Bearer` header
+///
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+// ==================== // Messages // ==================== //
+
+/// List the rooms of the user with their latest message
+///
+pub async fn list_rooms_handler(
+    State(state): State<AppState>,
+    TokenAuth(user, token): TokenAuth,
+) -> Result<Json<Vec<Room>>, Error> {
+    token.require(TokenScope::ReadMessages)?;
+
+    let chats = Chats::init(user.id, &state.store).await?;
+    Ok(Json(chats.rooms))
+}
+
+/// List the recent messages of a room
+///
+pub async fn list_messages_handler(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    TokenAuth(user, token): TokenAuth,
+) -> Result<Json<Vec<Message>>, Error> {
+    token.require(TokenScope::ReadMessages)?;
+    ensure_member(&user, &room_id, &state).await?;
+
+    let mut con = state.store.con.clone();
+    let messages = Message::list(&room_id, &mut con).await?;
+    Ok(Json(messages))
+}
+
+#[derive(Deserialize)]
+pub struct SendMessageArg {
+    content: String,
+}
+
+/// Send a text message to a room
+///
+pub async fn send_message_handler(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    TokenAuth(user, token): TokenAuth,
+    Json(arg): Json<SendMessageArg>,
+) -> Result<Json<Message>, Error> {
+    token.require(TokenScope::SendMessages)?;
+    ensure_member(&user, &room_id, &state).await?;
+
+    let content = arg.content.trim();
+    if content.is_empty() || content.len() > 4096 {
+        return Err(Error::BadRequest(String::from(
+            "The content must have 1 to 4096 bytes",
+        )));
+    }
+
+    let message = Message::text(room_id, user, content.to_owned());
+    let message = post_message(message, &state).await?;
+    Ok(Json(message))
+}
+
+// ==================== // Files // ==================== //
+
+/// Upload the file in the multipart field `file` and send it to a room
+///
+pub async fn send_file_handler(
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    TokenAuth(user, token): TokenAuth,
+    body: Body,
+) -> Result<Json<Message>, Error> {
+    token.require(TokenScope::ManageFiles)?;
+    token.require(TokenScope::SendMessages)?;
+    ensure_member(&user, &room_id, &state).await?;
+
+    let boundary = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| multer::parse_boundary(v).ok())
+        .ok_or(Error::BadRequest(String::from("Multipart data is expected")))?;
+    let mut multipart = multer::Multipart::new(body.into_data_stream(), boundary);
+
+    // a broken body is an error of the client, not the end of the fields
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| Error::BadRequest(String::from("The multipart data is invalid")))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_meta =
+            FileManager::save_shared_file(user.id, field, &state.store, state.config.clone())
+                .await?;
+
+        let message = Message::file(room_id, user, file_meta);
+        let message = post_message(message, &state).await?;
+        return Ok(Json(message));
+    }
+    Err(Error::BadRequest(String::from("No file found in multipart data")))
+}
+
+/// Get the storage used by the files of the user
+///
+pub async fn storage_usage_handler(
+    State(state): State<AppState>,
+    TokenAuth(user, token): TokenAuth,
+) -> Result<Json<StorageUsage>, Error> {
+    token.require(TokenScope::ManageFiles)?;
+
+    let usage = StorageUsage::get(user.id, &state.store, &state.config).await?;
+    Ok(Json(usage))
+}
+
+// ==================== // UTILS // ==================== //
+
+async fn ensure_member(user: &User, room_id: &str, state: &AppState) -> Result<(), Error> {
+    if Room::is_member(user.id, room_id, &state.store).await? {
+        Ok(())
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// Deliver the message to the room even if no member is online, the same as a
+/// message sent from the socket
+///
+async fn post_message(message: Message, state: &AppState) -> Result<Message, Error> {
+    let store = &state.store;
+
+    let urls: Vec<String> = message.urls().into_iter().map(String::from).collect();
+    for url in &urls {
+        RoomFile::record(message.sender.id, &message.room_id, url, store).await?;
+    }
+
+    let message = state.hub.deliver(message)?;
+    message.cache(store).await?;
+
+    // the shared files are kept as long as the message references them
+    let owner = format!("message:{}", message.id);
+    for url in &urls {
+        Blob::reference(url, &owner, store).await?;
+    }
+    Ok(message)
+}
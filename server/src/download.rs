@@ -0,0 +1,351 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::cookie::CookieJar;
+
+use crate::state::AppState;
+use common::{
+    content_type, percent_encode, CookieManager, Error, RoomFile, Session, Storage, User, UserRole,
+};
+
+/// Expire time of the presigned urls
+const PRESIGN_EXPIRE: Duration = Duration::from_secs(60 * 60);
+
+// ==================== // download_handler // ==================== //
+
+/// Download an uploaded file from the storage, shared files can only be downloaded by
+/// the uploader and members of the rooms they were sent to, archived files of the
+/// QR links only by admins. The public downloads of the links go through `link_handler`
+///
+pub async fn download_handler(
+    uri: Uri,
+    headers: HeaderMap,
+    cookie_jar: CookieJar,
+    State(state): State<AppState>,
+) -> Result<Response, Error> {
+    let url = uri.path();
+    if url.starts_with(&state.config.share_dir) {
+        let (user_id, session) = CookieManager::extract_auth(cookie_jar)?;
+        let user = Session::verify(user_id, session, false, &state.store).await?;
+        user.ensure_password_changed()?;
+
+        if !RoomFile::authorize(user_id, url, &state.store).await? {
+            log::warn!("user {} is denied to download {}", user_id, url);
+            return Err(Error::Forbidden);
+        }
+    } else if url.starts_with(&state.config.archive_dir) {
+        let _ = verify_admin(cookie_jar, &state).await?;
+    }
+
+    serve_file(url, None, &headers, &state).await
+}
+
+/// Verify the session in the cookies belongs to an admin
+///
+pub async fn verify_admin(cookie_jar: CookieJar, state: &AppState) -> Result<User, Error> {
+    let (user_id, session) = CookieManager::extract_auth(cookie_jar)?;
+    let user = Session::verify(user_id, session, false, &state.store).await?;
+    user.ensure_password_changed()?;
+    if user.role != UserRole::Admin {
+        return Err(Error::Forbidden);
+    }
+    Ok(user)
+}
+
+// ==================== // serve_file // ==================== //
+
+/// Send the file from the storage, the storage may redirect to a presigned url
+/// instead of streaming the file. A single `Range` is answered with partial content
+/// so that media players can seek. The file is sent as an attachment if it is named
+///
+pub async fn serve_file(
+    url: &str,
+    name: Option<&str>,
+    headers: &HeaderMap,
+    state: &AppState,
+) -> Result<Response, Error> {
+    send_file(state.store.storage.as_ref(), url, name, headers).await
+}
+
+async fn send_file(
+    storage: &dyn Storage,
+    url: &str,
+    name: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response, Error> {
+    if let Some(presigned) = storage.presign(url, PRESIGN_EXPIRE).await? {
+        return Ok(Redirect::temporary(&presigned).into_response());
+    }
+
+    let size = storage.size(url).await?.ok_or(Error::NotFound)?;
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| ByteRange::parse(v, size))
+        .unwrap_or(ByteRange::Full);
+
+    let disposition = match name {
+        Some(name) => format!(
+            "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+            name.replace(
+                |c: char| !c.is_ascii() || c == '"' || c == '\\' || c.is_control(),
+                "_"
+            ),
+            percent_encode(name)
+        ),
+        None => String::from("inline"),
+    };
+    let common_headers = [
+        (header::CONTENT_TYPE, content_type(url).to_owned()),
+        (header::ACCEPT_RANGES, String::from("bytes")),
+        (
+            header::CACHE_CONTROL,
+            String::from("private, max-age=86400"),
+        ),
+        (header::CONTENT_DISPOSITION, disposition),
+    ];
+
+    match range {
+        ByteRange::Full => {
+            let stream = storage.get(url).await?;
+            let headers = [(header::CONTENT_LENGTH, size.to_string())];
+            Ok((common_headers, headers, Body::from_stream(stream)).into_response())
+        }
+        ByteRange::Partial(start, end) => {
+            let len = end - start + 1;
+            let stream = storage.get_range(url, start, len).await?;
+            let headers = [
+                (header::CONTENT_LENGTH, len.to_string()),
+                (
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end, size),
+                ),
+            ];
+            Ok((
+                StatusCode::PARTIAL_CONTENT,
+                common_headers,
+                headers,
+                Body::from_stream(stream),
+            )
+                .into_response())
+        }
+        ByteRange::Unsatisfiable => {
+            let headers = [
+                (header::CONTENT_RANGE, format!("bytes */{}", size)),
+                (header::ACCEPT_RANGES, String::from("bytes")),
+            ];
+            Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response())
+        }
+    }
+}
+
+// ==================== // ByteRange // ==================== //
+
+/// Byte range requested by the `Range` header
+///
+enum ByteRange {
+    /// No valid range, the whole file is sent
+    Full,
+    /// Inclusive start and end of the bytes
+    Partial(u64, u64),
+    /// The range starts after the end of the file
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Parse a single range, e.g. `bytes=0-499`, `bytes=500-` or `bytes=-500`.
+    /// Multiple ranges and invalid values are ignored and answered in full
+    ///
+    fn parse(value: &str, size: u64) -> Self {
+        let Some(spec) = value.trim().strip_prefix("bytes=") else {
+            return Self::Full;
+        };
+        if spec.contains(',') {
+            return Self::Full;
+        }
+        let Some((start, end)) = spec.trim().split_once('-') else {
+            return Self::Full;
+        };
+        let (start, end) = (start.trim(), end.trim());
+
+        // suffix range of the last bytes
+        if start.is_empty() {
+            return match end.parse::<u64>() {
+                Ok(0) => Self::Unsatisfiable,
+                Ok(_) if size == 0 => Self::Unsatisfiable,
+                Ok(len) => Self::Partial(size.saturating_sub(len), size - 1),
+                Err(_) => Self::Full,
+            };
+        }
+
+        let Ok(start) = start.parse::<u64>() else {
+            return Self::Full;
+        };
+        let end = if end.is_empty() {
+            None
+        } else {
+            match end.parse::<u64>() {
+                Ok(end) if end >= start => Some(end),
+                _ => return Self::Full,
+            }
+        };
+
+        if start >= size {
+            return Self::Unsatisfiable;
+        }
+        let end = end.map(|v| v.min(size - 1)).unwrap_or(size - 1);
+        Self::Partial(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use common::LocalStorage;
+
+    const SIZE: u64 = 1000;
+
+    #[test]
+    fn parses_closed_range() {
+        assert!(matches!(
+            ByteRange::parse("bytes=0-499", SIZE),
+            ByteRange::Partial(0, 499)
+        ));
+    }
+
+    #[test]
+    fn parses_open_range() {
+        assert!(matches!(
+            ByteRange::parse("bytes=500-", SIZE),
+            ByteRange::Partial(500, 999)
+        ));
+    }
+
+    #[test]
+    fn parses_suffix_range() {
+        assert!(matches!(
+            ByteRange::parse("bytes=-500", SIZE),
+            ByteRange::Partial(500, 999)
+        ));
+        assert!(matches!(
+            ByteRange::parse("bytes=-5000", SIZE),
+            ByteRange::Partial(0, 999)
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_suffix_range() {
+        assert!(matches!(
+            ByteRange::parse("bytes=-0", SIZE),
+            ByteRange::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn clamps_end_to_size() {
+        assert!(matches!(
+            ByteRange::parse("bytes=900-1999", SIZE),
+            ByteRange::Partial(900, 999)
+        ));
+    }
+
+    #[test]
+    fn rejects_start_past_size() {
+        assert!(matches!(
+            ByteRange::parse("bytes=1000-", SIZE),
+            ByteRange::Unsatisfiable
+        ));
+        assert!(matches!(
+            ByteRange::parse("bytes=1500-1600", SIZE),
+            ByteRange::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn ignores_multiple_and_malformed_ranges() {
+        for value in [
+            "bytes=0-1,5-9",
+            "bytes=abc-",
+            "bytes=5-1",
+            "bytes=1",
+            "items=0-9",
+            "bytes=-x",
+        ] {
+            assert!(
+                matches!(ByteRange::parse(value, SIZE), ByteRange::Full),
+                "{}",
+                value
+            );
+        }
+    }
+
+    /// A local storage with a file of `SIZE` bytes counting up
+    ///
+    fn storage() -> (LocalStorage, std::path::PathBuf) {
+        let root = std::env::temp_dir().join(format!("chat-download-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("assets/share")).unwrap();
+        let bytes: Vec<u8> = (0..SIZE).map(|v| v as u8).collect();
+        std::fs::write(root.join("assets/share/data.bin"), bytes).unwrap();
+        (LocalStorage::new(&root.to_string_lossy()), root)
+    }
+
+    async fn download(range: Option<&str>) -> Response {
+        let (storage, root) = storage();
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(header::RANGE, range.parse().unwrap());
+        }
+        let response = send_file(&storage, "/assets/share/data.bin", None, &headers)
+            .await
+            .unwrap();
+        let (parts, body) = response.into_parts();
+        let bytes = to_bytes(body, usize::MAX).await.unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+        Response::from_parts(parts, Body::from(bytes))
+    }
+
+    fn header_value(response: &Response, name: header::HeaderName) -> &str {
+        response.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_partial_content() {
+        let response = download(Some("bytes=500-")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            header_value(&response, header::CONTENT_RANGE),
+            "bytes 500-999/1000"
+        );
+        assert_eq!(header_value(&response, header::CONTENT_LENGTH), "500");
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(bytes.len(), 500);
+        assert_eq!(bytes[0], (500 % 256) as u8);
+    }
+
+    #[tokio::test]
+    async fn sends_unsatisfiable_range() {
+        let response = download(Some("bytes=1000-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            header_value(&response, header::CONTENT_RANGE),
+            "bytes */1000"
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_full_content() {
+        for range in [None, Some("bytes=0-1,5-9"), Some("bytes=abc")] {
+            let response = download(range).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(header_value(&response, header::CONTENT_LENGTH), "1000");
+            assert!(response.headers().get(header::CONTENT_RANGE).is_none());
+        }
+    }
+}
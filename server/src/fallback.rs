@@ -1,53 +1,30 @@
-use axum::extract::{FromRequestParts, State};
-use axum::http::request::Parts;
+use axum::extract::State;
 use axum::{
-    async_trait,
     body::Body,
     http::{Request, Response, StatusCode, Uri},
     response::{IntoResponse, Response as AxumResponse},
 };
-use axum_extra::extract::cookie::CookieJar;
 use leptos::LeptosOptions;
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
 
-use crate::state::AppState;
 use app::App;
-use common::{CookieManager, Error, Session};
 
-// ==================== // ShareGuard // ==================== //
-
-pub struct ShareGuard;
-
-#[async_trait]
-impl FromRequestParts<AppState> for ShareGuard {
-    type Rejection = Error;
-
-    async fn from_request_parts(
-        parts: &mut Parts,
-        state: &AppState,
-    ) -> Result<Self, Self::Rejection> {
-        let uri = Uri::from_request_parts(parts, state).await?;
-        if uri.path().starts_with(&state.config.share_dir) {
-            let cookie_jar = CookieJar::from_request_parts(parts, state).await?;
-            let (user_id, session) = CookieManager::extract_auth(cookie_jar)?;
-
-            let _ = Session::verify(user_id, session, false, &state.store).await?;
-        }
-        Ok(ShareGuard)
-    }
-}
+/// Paths of the public assets besides the build output in `site_pkg_dir`. The
+/// uploads are stored under the site root as well, they are only served by the
+/// download handler which checks the access
+const PUBLIC_PATHS: [&str; 3] = ["/default/", "/favicon.ico", "/csrf.js"];
 
 // ==================== // file_and_error_handler // ==================== //
 
 pub async fn file_and_error_handler(
     uri: Uri,
-    _share: ShareGuard,
     State(options): State<LeptosOptions>,
     req: Request<Body>,
 ) -> AxumResponse {
-    let root = options.site_root.clone();
-    let res = get_static_file(uri.clone(), &root).await.unwrap();
+    let res = get_static_file(uri.clone(), &options.site_root, &options.site_pkg_dir)
+        .await
+        .unwrap();
 
     if res.status() == StatusCode::OK {
         res.into_response()
@@ -57,7 +34,15 @@ pub async fn file_and_error_handler(
     }
 }
 
-async fn get_static_file(uri: Uri, root: &str) -> Result<Response<Body>, (StatusCode, String)> {
+async fn get_static_file(
+    uri: Uri,
+    root: &str,
+    pkg_dir: &str,
+) -> Result<Response<Body>, (StatusCode, String)> {
+    if !is_static_path(uri.path(), pkg_dir) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
     let req = Request::builder()
         .uri(uri.clone())
         .body(Body::empty())
@@ -72,3 +57,66 @@ async fn get_static_file(uri: Uri, root: &str) -> Result<Response<Body>, (Status
         )),
     }
 }
+
+/// Match the raw path, encoded or empty segments could reach another directory
+/// once `ServeDir` decodes them
+///
+fn is_static_path(path: &str, pkg_dir: &str) -> bool {
+    if path.contains('%') || path.contains("//") || path.contains("/.") {
+        return false;
+    }
+    let pkg_path = format!("/{}/", pkg_dir.trim_matches('/'));
+    path.starts_with(&pkg_path)
+        || PUBLIC_PATHS.iter().any(|v| {
+            if v.ends_with('/') {
+                path.starts_with(v)
+            } else {
+                path == *v
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A site root with a build file and an uploaded file
+    ///
+    fn site_root() -> String {
+        let root = std::env::temp_dir().join(format!("chat-site-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("pkg")).unwrap();
+        std::fs::create_dir_all(root.join("assets/share")).unwrap();
+        std::fs::write(root.join("pkg/chat.js"), "export {}").unwrap();
+        std::fs::write(root.join("assets/share/secret.png"), "secret").unwrap();
+        root.to_string_lossy().into_owned()
+    }
+
+    async fn status(path: &str, root: &str) -> StatusCode {
+        let uri: Uri = path.parse().unwrap();
+        get_static_file(uri, root, "pkg").await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn serves_build_output() {
+        let root = site_root();
+        assert_eq!(status("/pkg/chat.js", &root).await, StatusCode::OK);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn hides_uploaded_files() {
+        let root = site_root();
+        for path in [
+            "/assets/share/secret.png",
+            "/assets/%73hare/secret.png",
+            "/assets//share/secret.png",
+            "/%61ssets/share/secret.png",
+            "/pkg/../assets/share/secret.png",
+            "/pkg/%2e%2e/assets/share/secret.png",
+            "/pkg//../assets/share/secret.png",
+        ] {
+            assert_eq!(status(path, &root).await, StatusCode::NOT_FOUND, "{}", path);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Form,
+};
+use axum_extra::extract::cookie::CookieJar;
+
+use crate::{
+    download::{serve_file, verify_admin},
+    state::AppState,
+};
+use common::{Error, FileLink, LinkDownload};
+
+// ==================== // link_handler // ==================== //
+
+/// Download the file of a QR link, the link may be expired, revoked, limited in
+/// downloads or protected by a password which is asked with a small form. Every
+/// attempt except showing the form is recorded for the analytics
+///
+pub async fn link_handler(
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Response, Error> {
+    download_link(&token, None, &headers, &state).await
+}
+
+/// Submit the password of a QR link
+///
+pub async fn link_password_handler(
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Response, Error> {
+    let password = form.get("password").map(String::as_str).unwrap_or_default();
+    download_link(&token, Some(password), &headers, &state).await
+}
+
+async fn download_link(
+    token: &str,
+    password: Option<&str>,
+    headers: &HeaderMap,
+    state: &AppState,
+) -> Result<Response, Error> {
+    let result = match FileLink::access(token, password, &state.store).await {
+        Ok((url, name)) => serve_file(&url, Some(&name), headers, state).await,
+        Err(Error::Unauthorized) if password.is_none() => return Ok(password_page(false)),
+        Err(Error::Unauthorized) => {
+            log::warn!("incorrect password for the link {}", token);
+            Ok(password_page(true))
+        }
+        Err(err) => Err(err),
+    };
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let success =
+        matches!(&result, Ok(rsp) if rsp.status().is_success() || rsp.status().is_redirection());
+    if let Err(err) = LinkDownload::record(token, user_agent, success, &state.store).await {
+        log::error!(
+            "failed to record the download of the link {}: {}",
+            token,
+            err
+        );
+    }
+    result
+}
+
+// ==================== // export_handler // ==================== //
+
+/// Export the download attempts of all QR links as CSV for admins
+///
+pub async fn export_handler(
+    cookie_jar: CookieJar,
+    State(state): State<AppState>,
+) -> Result<Response, Error> {
+    let _ = verify_admin(cookie_jar, &state).await?;
+
+    let csv = LinkDownload::export_csv(&state.store).await?;
+    let headers = [
+        (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+        (
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"link-downloads.csv\"",
+        ),
+    ];
+    Ok((headers, csv).into_response())
+}
+
+/// The form posts the password back to the same link
+///
+fn password_page(incorrect: bool) -> Response {
+    let hint = if incorrect {
+        r#"<p style="color:#dc2626">The password is incorrect</p>"#
+    } else {
+        ""
+    };
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Protected file</title>
+</head>
+<body style="font-family:sans-serif;max-width:320px;margin:80px auto;padding:0 16px">
+<h3>This file is protected</h3>
+<p>Enter the password to download it.</p>
+{}
+<form method="post">
+<input type="password" name="password" autofocus required style="width:100%;height:32px;box-sizing:border-box">
+<button type="submit" style="width:100%;height:32px;margin-top:12px">Download</button>
+</form>
+</body>
+</html>"#,
+        hint
+    );
+    (StatusCode::UNAUTHORIZED, Html(page)).into_response()
+}
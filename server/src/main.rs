@@ -1,15 +1,29 @@
-use std::time::Duration;
+use std::{net::SocketAddr, time::Duration};
 use tokio::{net::TcpListener, signal};
 
-use axum::{error_handling::HandleErrorLayer, http::StatusCode, routing::get, BoxError, Router};
+use axum::{
+    error_handling::HandleErrorLayer,
+    http::StatusCode,
+    middleware,
+    routing::get,
+    BoxError, Router,
+};
 use leptos::provide_context;
 use leptos_axum::LeptosRoutes;
 use tower::{timeout::TimeoutLayer, ServiceBuilder};
 
 use app::App;
+use common::CsrfGuard;
+use download::download_handler;
+use link::{export_handler, link_handler, link_password_handler};
+use oidc::{oidc_callback_handler, oidc_login_handler};
 use ws::ws_handler;
 
+mod api;
+mod download;
 mod fallback;
+mod link;
+mod oidc;
 mod state;
 mod ws;
 
@@ -18,12 +32,22 @@ async fn main() {
     simple_logger::init_with_level(log::Level::Info).expect("failed to initialize logging");
 
     let app_state = state::AppState::new().await;
+    app_state.scheduler.start();
+
     let addr = app_state.leptos_options.site_addr;
     let routes = leptos_axum::generate_route_list(App);
+    let files = |dir: &str| format!("{}/*key", dir);
 
     // build our application with a route
     let app = Router::new()
         .route("/ws", get(ws_handler))
+        .route(&files(&app_state.config.avatar_dir), get(download_handler))
+        .route(&files(&app_state.config.archive_dir), get(download_handler))
+        .route(&files(&app_state.config.share_dir), get(download_handler))
+        .route("/s/:token", get(link_handler).post(link_password_handler))
+        .route("/links/downloads.csv", get(export_handler))
+        .route("/auth/oidc/login", get(oidc_login_handler))
+        .route("/auth/oidc/callback", get(oidc_callback_handler))
         .leptos_routes_with_context(
             &app_state,
             routes,
@@ -31,15 +55,23 @@ async fn main() {
                 let config = app_state.config.clone();
                 let store = app_state.store.clone();
                 let hub = app_state.hub.clone();
+                let scheduler = app_state.scheduler.clone();
                 move || {
                     provide_context(config.clone());
                     provide_context(store.clone());
                     provide_context(hub.clone());
+                    provide_context(scheduler.clone());
                 }
             },
             App,
         )
         .fallback(fallback::file_and_error_handler)
+        .layer(middleware::from_fn_with_state(
+            app_state.config.csrf.clone(),
+            CsrfGuard::middleware,
+        ))
+        // the api for scripts verifies its access token before the CSRF check
+        .merge(api::routes(&app_state))
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|_: BoxError| async {
@@ -54,10 +86,14 @@ async fn main() {
         .await
         .expect("failed to create tcp listener");
 
-    axum::serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .expect("failed to start the http server");
+    // the client address is needed to throttle the failed logins
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .expect("failed to start the http server");
 }
 
 async fn shutdown_signal() {
@@ -0,0 +1,144 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+
+use crate::state::AppState;
+use common::{percent_encode, ClientInfo, CookieManager, Error, OidcLogin, OidcStep, Session};
+
+/// Cookie which binds the login to the browser that started it
+const STATE_KEY: &str = "oidc_state";
+
+// ==================== // oidc_login_handler // ==================== //
+
+/// Redirect to the identity provider, `?link=true` links the identity to the
+/// logged in user instead of logging in
+///
+pub async fn oidc_login_handler(
+    cookie_jar: CookieJar,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<Response, Error> {
+    let config = state.config.oidc.as_ref().ok_or(Error::NotFound)?;
+
+    let link_user = if query.get("link").map(String::as_str) == Some("true") {
+        let (user_id, session) = CookieManager::extract_auth(cookie_jar.clone())?;
+        let user = Session::verify(user_id, session, false, &state.store).await?;
+        user.ensure_password_changed()?;
+        Some(user.id)
+    } else {
+        None
+    };
+
+    let (url, login_state) = OidcLogin::start(link_user, config, &state.store).await?;
+
+    // the provider redirects back from another site, so the cookie cannot be strict
+    let cookie_jar = cookie_jar.add(
+        Cookie::build((STATE_KEY, login_state))
+            .path("/auth/oidc")
+            .same_site(SameSite::Lax)
+            .secure(true)
+            .http_only(true),
+    );
+    Ok((cookie_jar, Redirect::to(&url)).into_response())
+}
+
+// ==================== // oidc_callback_handler // ==================== //
+
+/// Finish the login when the provider redirects back with the code
+///
+pub async fn oidc_callback_handler(
+    cookie_jar: CookieJar,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Response {
+    let client = ClientInfo::new(&headers, addr, state.config.login.trust_proxy);
+    let result = oidc_callback(&cookie_jar, &query, &client, &state).await;
+    // the state is used once
+    let cookie_jar = cookie_jar.remove(Cookie::build(STATE_KEY).path("/auth/oidc"));
+
+    match result {
+        Ok(OidcStep::Session(session, user)) => {
+            log::info!(
+                "user {} logged in with the identity provider",
+                user.username
+            );
+            let auth = CookieManager::auth_cookies(user.id, session, state.config.session.lifetime);
+            let cookie_jar = auth
+                .iter()
+                .fold(cookie_jar.clone(), |jar, c| jar.add(c.clone()));
+            let path = if user.must_change_password {
+                "/password"
+            } else {
+                "/chats"
+            };
+            (cookie_jar, refresh_page(path)).into_response()
+        }
+        Ok(OidcStep::TwoFactor(challenge)) => {
+            let path = format!("/login?sso_challenge={}", percent_encode(&challenge));
+            (cookie_jar, refresh_page(&path)).into_response()
+        }
+        Ok(OidcStep::Linked) => (cookie_jar, refresh_page("/settings/security")).into_response(),
+        Err(err) => {
+            log::warn!("oidc login failed: {}", err);
+            let path = format!("/login?sso_error={}", percent_encode(&err.to_string()));
+            (cookie_jar, refresh_page(&path)).into_response()
+        }
+    }
+}
+
+async fn oidc_callback(
+    cookie_jar: &CookieJar,
+    query: &HashMap<String, String>,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<OidcStep, Error> {
+    let config = state.config.oidc.as_ref().ok_or(Error::NotFound)?;
+
+    if let Some(error) = query.get("error") {
+        let description = query.get("error_description").unwrap_or(error);
+        return Err(Error::BadRequest(description.to_owned()));
+    }
+
+    let login_state = query.get("state").map(String::as_str).unwrap_or_default();
+    let expected = cookie_jar
+        .get(STATE_KEY)
+        .map(|c| c.value())
+        .unwrap_or_default();
+    if login_state.is_empty() || login_state != expected {
+        return Err(Error::BadRequest(String::from(
+            "The login was started in another browser, please sign in again",
+        )));
+    }
+
+    let code = query.get("code").ok_or(Error::BadRequest(String::from(
+        "The authorization code is missing",
+    )))?;
+    OidcLogin::finish(login_state, code, client, config, &state.store).await
+}
+
+/// The auth cookies are strict, they are not sent on the redirect from the provider
+/// but they are on a navigation within the site
+///
+fn refresh_page(path: &str) -> Html<String> {
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="0;url={0}">
+<title>Signing in</title>
+</head>
+<body style="font-family:sans-serif;max-width:320px;margin:80px auto;padding:0 16px">
+<p>Signing in, <a href="{0}">continue</a> if the page does not change.</p>
+</body>
+</html>"#,
+        path.replace('"', "&quot;")
+    ))
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use axum::extract::FromRef;
 use leptos::LeptosOptions;
 
-use common::{Config, Hub, Store};
+use common::{Config, Hub, Scheduler, Store};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -11,6 +11,7 @@ pub struct AppState {
     pub config: Arc<Config>,
     pub store: Store,
     pub hub: Hub,
+    pub scheduler: Scheduler,
 }
 
 impl FromRef<AppState> for LeptosOptions {
@@ -26,14 +27,16 @@ impl AppState {
             .expect("failed to initialize Leptos Options")
             .leptos_options;
 
-        let config = Config::from_env();
+        let config = Arc::new(Config::from_env());
         let store = Store::new(&config).await;
+        let scheduler = Scheduler::new(store.clone(), config.clone());
 
         Self {
             leptos_options: Arc::new(leptos_options),
-            config: Arc::new(config),
+            config,
             store,
             hub: Hub::default(),
+            scheduler,
         }
     }
 }
@@ -1,11 +1,12 @@
 use std::fmt;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, oneshot};
 use uuid::Uuid;
 
 use crate::state::AppState;
 use common::{
-    Chats, Error, Event, Friend, FriendShip, Hub, HungUpReson, IceCandidate, Message, Result, Room,
-    Store,
+    ApiToken, Blob, CallPeer, Chats, Error, Event, Friend, FriendShip, Hub, HungUpReson,
+    IceCandidate, Message, MessageKind, PeerSignal, Result, Room, RoomFile, Store, TokenScope,
+    MAX_ALBUM_FILES,
 };
 
 /// A Client with a connection of user websocket
@@ -13,29 +14,38 @@ use common::{
 pub struct Client {
     id: Uuid,
     user_id: i64,
+    /// The token of a script, `None` for the session of a browser
+    api_token: Option<ApiToken>,
     store: Store,
     hub: Hub,
 }
 
 impl Client {
-    pub fn new(user_id: i64, state: AppState) -> Self {
+    pub fn new(user_id: i64, api_token: Option<ApiToken>, state: AppState) -> Self {
         Self {
             id: Uuid::new_v4(),
             user_id,
+            api_token,
             store: state.store,
             hub: state.hub,
         }
     }
 
-    /// register a connection in the Hub
-    pub async fn register(&self, tx: broadcast::Sender<Vec<u8>>) -> Result<()> {
+    /// register a connection of the session in the Hub
+    pub async fn register(
+        &self,
+        session: String,
+        tx: broadcast::Sender<Vec<u8>>,
+        kick: oneshot::Sender<String>,
+    ) -> Result<()> {
         let Chats {
             rooms,
             friends,
             messages_map,
         } = Chats::init(self.user_id, &self.store).await?;
 
-        self.hub.register(self.user_id, self.id, &rooms, tx.clone());
+        self.hub
+            .register(self.user_id, self.id, session, &rooms, tx.clone(), kick);
 
         tx.send(serde_json::to_vec(&Event::InitRooms(rooms))?)?;
         tx.send(serde_json::to_vec(&Event::InitFriends(friends))?)?;
@@ -46,11 +56,22 @@ impl Client {
 
     /// unregister the connection to Hub
     pub fn unregister(&self) {
+        for (room_id, members) in self.hub.leave_calls(self.peer()) {
+            let event = Event::ReceiveCallMembers(room_id.clone(), members);
+            if let Err(err) = self.hub.publish(&room_id, &event) {
+                log::error!("failed to publish call members: {}", err);
+            }
+        }
         self.hub.unregister(self.user_id, &self.id);
     }
 
     /// process Event from user
     pub async fn process(&self, event: Event) -> Result<()> {
+        if !self.allows(&event) {
+            log::warn!("event of the token ignored, user {}", self.user_id);
+            return Ok(());
+        }
+
         let ret = match event {
             Event::Send(message) => self.send_message(message).await,
             Event::AddFriend(friend_id) => self.add_friend(friend_id).await,
@@ -69,6 +90,9 @@ impl Client {
             Event::SendCandidate(friend_id, client_id, candidate) => {
                 self.send_candidate(friend_id, client_id, candidate)
             }
+            Event::JoinRoomCall(room_id) => self.join_room_call(room_id),
+            Event::LeaveRoomCall(room_id) => self.leave_room_call(room_id),
+            Event::SendSignal(signal) => self.send_signal(signal),
             _ => Ok(()),
         };
         if let Err(err) = ret {
@@ -84,10 +108,59 @@ impl Client {
         }
     }
 
+    /// Browsers may send every event, tokens need the scopes of the event
+    ///
+    fn allows(&self, event: &Event) -> bool {
+        match &self.api_token {
+            None => true,
+            Some(api_token) => Self::scopes(event)
+                .is_some_and(|scopes| scopes.iter().all(|v| api_token.allows(*v))),
+        }
+    }
+
+    /// Scopes a token needs to send the event, `None` if no token may send it, such
+    /// as the friendships and the calls
+    ///
+    fn scopes(event: &Event) -> Option<Vec<TokenScope>> {
+        match event {
+            Event::Send(message) if message.urls().is_empty() => {
+                Some(vec![TokenScope::SendMessages])
+            }
+            Event::Send(_) => Some(vec![TokenScope::SendMessages, TokenScope::ManageFiles]),
+            _ => None,
+        }
+    }
+
     async fn send_message(&self, message: Message) -> Result<()> {
+        if matches!(message.kind, MessageKind::Album)
+            && !(2..=MAX_ALBUM_FILES).contains(&message.files.len())
+        {
+            return Err(Error::BadRequest(format!(
+                "An album must have 2 to {} files",
+                MAX_ALBUM_FILES
+            )));
+        }
+
+        // members of the room may download the shared files from now on
+        let urls: Vec<String> = message.urls().into_iter().map(String::from).collect();
+        if !urls.is_empty() {
+            if !Room::is_member(self.user_id, &message.room_id, &self.store).await? {
+                return Err(Error::Forbidden);
+            }
+            for url in &urls {
+                RoomFile::record(self.user_id, &message.room_id, url, &self.store).await?;
+            }
+        }
+
         let message = self.hub.broadcast(message)?;
         message.cache(&self.store).await?;
 
+        // the shared files are kept as long as the message references them
+        let owner = format!("message:{}", message.id);
+        for url in &urls {
+            Blob::reference(url, &owner, &self.store).await?;
+        }
+
         Ok(())
     }
 
@@ -201,6 +274,59 @@ impl Client {
         Ok(())
     }
 
+    fn join_room_call(&self, room_id: String) -> Result<()> {
+        let peer = self.peer();
+        let mut members = self.hub.join_call(&room_id, peer)?;
+
+        let event = Event::ReceiveRoomCall(room_id.clone(), members.clone());
+        self.hub.notify(self.user_id, &self.id, event)?;
+
+        members.push(peer);
+        let event = Event::ReceiveCallMembers(room_id.clone(), members);
+        self.hub.publish(&room_id, &event)?;
+
+        Ok(())
+    }
+
+    fn leave_room_call(&self, room_id: String) -> Result<()> {
+        if let Some(members) = self.hub.leave_call(&room_id, self.peer()) {
+            let event = Event::ReceiveCallMembers(room_id.clone(), members);
+            self.hub.publish(&room_id, &event)?;
+        }
+        Ok(())
+    }
+
+    fn send_signal(&self, signal: PeerSignal) -> Result<()> {
+        let PeerSignal {
+            room_id,
+            peer,
+            data,
+        } = signal;
+
+        let source = self.peer();
+        if !self.hub.in_same_call(&room_id, &source, &peer) {
+            return Err(Error::BadRequest(String::from(
+                "The peer is not in the call",
+            )));
+        }
+
+        let event = Event::ReceiveSignal(PeerSignal {
+            room_id,
+            peer: source,
+            data,
+        });
+        self.hub.notify(peer.user_id, &peer.client_id, event)?;
+
+        Ok(())
+    }
+
+    fn peer(&self) -> CallPeer {
+        CallPeer {
+            user_id: self.user_id,
+            client_id: self.id,
+        }
+    }
+
     fn target_offline(&self) -> Result<()> {
         self.hub.notify(
             self.user_id,
@@ -1,6 +1,6 @@
 use futures_util::{sink::SinkExt, stream::StreamExt};
-use tokio::sync::broadcast;
-use tokio::time::{interval, Duration};
+use tokio::sync::{broadcast, oneshot};
+use tokio::time::{interval, interval_at, Duration, Instant};
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{FromRequestParts, State};
@@ -8,12 +8,60 @@ use axum::{async_trait, http::request::Parts, response::IntoResponse};
 use axum_extra::extract::cookie::CookieJar;
 
 use super::client::Client;
-use crate::state::AppState;
-use common::{CookieManager, Error, Event, Session, User};
+use crate::{api::bearer_token, state::AppState};
+use common::{
+    ApiToken, CookieManager, CsrfGuard, Error, Event, Session, Store, TokenScope, User,
+};
+
+/// How often the session of an open socket is verified
+const VERIFY_INTERVAL: Duration = Duration::from_secs(60);
 
 // ==================== // WsGuard // ==================== //
 
-pub struct WsGuard(pub User);
+/// The login user and the credential of the socket
+///
+pub struct WsGuard(pub User, pub WsAuth);
+
+/// Browsers connect with the session cookie, scripts with a personal access token
+///
+pub enum WsAuth {
+    Session(String),
+    Token(String, ApiToken),
+}
+
+impl WsAuth {
+    /// The name of the credential among the sessions of the hub
+    ///
+    fn session_key(&self) -> String {
+        match self {
+            WsAuth::Session(session) => session.clone(),
+            WsAuth::Token(_, api_token) => ApiToken::session_key(api_token.id),
+        }
+    }
+
+    /// The token whose scopes limit the events of the socket
+    ///
+    fn api_token(&self) -> Option<ApiToken> {
+        match self {
+            WsAuth::Session(_) => None,
+            WsAuth::Token(_, api_token) => Some(api_token.clone()),
+        }
+    }
+
+    /// Check that the credential is still valid, an open socket keeps a session active
+    ///
+    async fn verify(&self, user_id: i64, store: &Store) -> Result<(), Error> {
+        match self {
+            WsAuth::Session(session) => {
+                Session::verify(user_id, session.clone(), true, store).await?;
+            }
+            WsAuth::Token(token, _) => {
+                ApiToken::verify(token, store).await?;
+            }
+        }
+        Ok(())
+    }
+}
 
 #[async_trait]
 impl FromRequestParts<AppState> for WsGuard {
@@ -23,11 +71,22 @@ impl FromRequestParts<AppState> for WsGuard {
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
+        // the upgrade is a plain GET which the pages of other sites could send with
+        // the cookie of the user
+        CsrfGuard::check_origin(&parts.headers, &state.config.csrf)?;
+
+        if let Some(token) = bearer_token(&parts.headers) {
+            let (user, api_token) = ApiToken::verify(token, &state.store).await?;
+            api_token.require(TokenScope::ReadMessages)?;
+            return Ok(WsGuard(user, WsAuth::Token(token.to_owned(), api_token)));
+        }
+
         let cookie_jar = CookieJar::from_request_parts(parts, state).await?;
         let (user_id, session) = CookieManager::extract_auth(cookie_jar)?;
 
-        let user = Session::verify(user_id, session, false, &state.store).await?;
-        Ok(WsGuard(user))
+        let user = Session::verify(user_id, session.clone(), false, &state.store).await?;
+        user.ensure_password_changed()?;
+        Ok(WsGuard(user, WsAuth::Session(session)))
     }
 }
 
@@ -36,23 +95,29 @@ impl FromRequestParts<AppState> for WsGuard {
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
-    WsGuard(user): WsGuard,
+    WsGuard(user, auth): WsGuard,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| websocket(socket, state, user))
+    ws.on_upgrade(move |socket| websocket(socket, state, user, auth))
 }
 
-async fn websocket(socket: WebSocket, state: AppState, user: User) {
+async fn websocket(socket: WebSocket, state: AppState, user: User, auth: WsAuth) {
     let (mut sender, mut receiver) = socket.split();
     let (tx, mut rx) = broadcast::channel(128);
+    let (kick_tx, mut kick_rx) = oneshot::channel::<String>();
 
-    let client = Client::new(user.id, state);
-    if client.register(tx).await.is_err() {
+    let store = state.store.clone();
+    let client = Client::new(user.id, auth.api_token(), state);
+    if client.register(auth.session_key(), tx, kick_tx).await.is_err() {
         return;
     }
 
     // this task will receive message from broadcast channel and send to client
     let mut send_task = tokio::spawn(async move {
         let mut interval = interval(Duration::from_secs(20));
+        let mut verify_interval = interval_at(
+            Instant::now() + VERIFY_INTERVAL,
+            VERIFY_INTERVAL,
+        );
         loop {
             tokio::select! {
                 data = rx.recv() => {
@@ -69,6 +134,27 @@ async fn websocket(socket: WebSocket, state: AppState, user: User) {
                         break;
                     }
                 }
+                reason = &mut kick_rx => {
+                    // the session is revoked, tell the client why before closing
+                    if let Ok(reason) = reason {
+                        if let Ok(msg) = serde_json::to_vec(&Event::ReceiveKick(reason)) {
+                            let _ = sender.send(Message::Binary(msg)).await;
+                        }
+                    }
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+                _ = verify_interval.tick() => {
+                    // the socket is closed once the session or token expires or is deleted
+                    match auth.verify(user.id, &store).await {
+                        Err(Error::Unauthorized | Error::Forbidden | Error::BadRequest(_)) => {
+                            let _ = sender.send(Message::Close(None)).await;
+                            break;
+                        }
+                        Err(err) => log::error!("failed to verify the credential of the socket: {}", err),
+                        Ok(_) => {}
+                    }
+                }
             }
         }
     });